@@ -1,20 +1,76 @@
 use crate::*;
-use tokio::sync::mpsc;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+
+// Nearly every message is a `PokerMessage`, so boxing it would only add allocations
+#[allow(clippy::large_enum_variant)]
+enum PlayerActorMessage {
+    Poker(PokerMessage),
+    /// Binds a new socket to the player, replacing any previous one
+    Connect {
+        socket: mpsc::Sender<PokerMessage>,
+        respond_to: oneshot::Sender<usize>,
+    },
+    Disconnect {
+        connection: usize,
+    },
+    /// The player hasn't reconnected since `connection` closed
+    ReconnectTimeout {
+        connection: usize,
+    },
+}
 
 #[derive(Clone)]
 pub struct PlayerHandle {
-    pub sender: mpsc::Sender<PokerMessage>,
+    sender: mpsc::Sender<PlayerActorMessage>,
     pub id: PlayerId,
 }
 
 impl PlayerHandle {
     pub fn new(
         player: Player,
+        players: RegistryHandle<PlayerId, PlayerHandle>,
         rooms: RegistryHandle<RoomId, RoomHandle>,
-        socket: mpsc::Sender<PokerMessage>,
+        storage: StorageHandle,
+    ) -> Self {
+        Self::spawn(player, HashSet::new(), players, rooms, storage)
+    }
+
+    /// Brings back a player who was sitting at `seated` when the server
+    /// stopped. They keep their seats for `RECONNECT_TIMEOUT` unless they
+    /// reconnect, as if their connection had just closed.
+    pub async fn reseat(
+        player: Player,
+        seated: HashSet<RoomId>,
+        players: RegistryHandle<PlayerId, PlayerHandle>,
+        rooms: RegistryHandle<RoomId, RoomHandle>,
+        storage: StorageHandle,
+    ) -> Self {
+        let handle = Self::spawn(player, seated, players, rooms, storage);
+        handle.disconnect(0).await;
+        handle
+    }
+
+    fn spawn(
+        player: Player,
+        seated: HashSet<RoomId>,
+        players: RegistryHandle<PlayerId, PlayerHandle>,
+        rooms: RegistryHandle<RoomId, RoomHandle>,
+        storage: StorageHandle,
     ) -> Self {
         let (sender, receiver) = mpsc::channel(*CHANNEL_SIZE);
-        let player_actor = PlayerActor::new(player.clone(), rooms, receiver, socket);
+        let mut player_actor = PlayerActor::new(
+            player.clone(),
+            players,
+            rooms,
+            storage,
+            receiver,
+            sender.downgrade(),
+        );
+        player_actor.seated = seated;
         tokio::spawn(run(player_actor));
 
         Self {
@@ -23,44 +79,128 @@ impl PlayerHandle {
         }
     }
 
+    /// Sends the player's messages to `socket` from now on, returning an id
+    /// for the connection. Fails if the player has already left.
+    pub async fn connect(&self, socket: mpsc::Sender<PokerMessage>) -> Result<usize> {
+        let (send, recv) = oneshot::channel();
+        let msg = PlayerActorMessage::Connect {
+            socket,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        Ok(recv.await?)
+    }
+
+    /// Keeps the player's seats for `RECONNECT_TIMEOUT` after the connection
+    /// closes, unless they reconnect
+    pub async fn disconnect(&self, connection: usize) {
+        let msg = PlayerActorMessage::Disconnect { connection };
+        let _ = self.sender.send(msg).await;
+    }
+
     pub fn send_message(&self, msg: PokerMessage) -> Result<()> {
-        self.sender.try_send(msg)?;
+        self.sender.try_send(PlayerActorMessage::Poker(msg))?;
         Ok(())
     }
 
     pub fn send_error(&self, err: eyre::Error) -> Result<()> {
         debug!(err = ?err, "Responding with error to player");
         // TODO: Fix error_lobby, error_room
-        let _ = self
-            .sender
-            .try_send(PokerMessage::error_lobby(err.to_string()));
+        let _ = self.send_message(PokerMessage::error_lobby(err.to_string()));
         Ok(())
     }
 }
 
 struct PlayerActor {
+    player_registry: RegistryHandle<PlayerId, PlayerHandle>,
     room_registry: RegistryHandle<RoomId, RoomHandle>,
+    storage: StorageHandle,
     player: Player,
-    receiver: mpsc::Receiver<PokerMessage>,
-    socket: mpsc::Sender<PokerMessage>,
+    receiver: mpsc::Receiver<PlayerActorMessage>,
+    /// For timers, which shouldn't keep the actor alive
+    sender: mpsc::WeakSender<PlayerActorMessage>,
+    /// The connected socket, shared with room subscriptions so they follow
+    /// the player across reconnects
+    socket: watch::Sender<Option<mpsc::Sender<PokerMessage>>>,
+    /// Id of the latest connection
+    connection: usize,
+    /// Whether the player was stood up after not reconnecting in time
+    abandoned: bool,
+    /// Chips not at any table
+    balance: ChipInt,
+    /// Rooms the player has a seat in
+    seated: HashSet<RoomId>,
+    /// Tasks forwarding each subscribed room's broadcasts to the socket
+    subscriptions: HashMap<RoomId, JoinHandle<()>>,
 }
 
 impl PlayerActor {
     fn new(
         player: Player,
+        players: RegistryHandle<PlayerId, PlayerHandle>,
         rooms: RegistryHandle<RoomId, RoomHandle>,
-        receiver: mpsc::Receiver<PokerMessage>,
-        socket: mpsc::Sender<PokerMessage>,
+        storage: StorageHandle,
+        receiver: mpsc::Receiver<PlayerActorMessage>,
+        sender: mpsc::WeakSender<PlayerActorMessage>,
     ) -> Self {
         PlayerActor {
             player,
+            player_registry: players,
             room_registry: rooms,
+            storage,
             receiver,
-            socket,
+            sender,
+            socket: watch::channel(None).0,
+            connection: 0,
+            abandoned: false,
+            // Replaced by the stored balance once the actor starts
+            balance: *DEFAULT_CHIPS,
+            seated: HashSet::new(),
+            subscriptions: HashMap::new(),
         }
     }
 
-    async fn handle_message(&mut self, poker_msg: PokerMessage) {
+    /// Whether the player has gone, with nothing left at any table
+    fn is_finished(&self) -> bool {
+        self.abandoned && self.socket.borrow().is_none() && self.seated.is_empty()
+    }
+
+    async fn handle_message(&mut self, msg: PlayerActorMessage) {
+        match msg {
+            PlayerActorMessage::Poker(poker_msg) => self.handle_poker_message(poker_msg).await,
+            PlayerActorMessage::Connect { socket, respond_to } => {
+                self.connection += 1;
+                self.abandoned = false;
+                self.socket.send_replace(Some(socket));
+                let _ = respond_to.send(self.connection);
+                self.set_connected(true).await;
+                self.resync_seated().await;
+            }
+            PlayerActorMessage::Disconnect { connection } => {
+                // An old connection closing after the player reconnected
+                if connection != self.connection {
+                    return;
+                }
+                self.socket.send_replace(None);
+                self.set_connected(false).await;
+                let sender = self.sender.clone();
+                tokio::spawn(async move {
+                    sleep(Duration::from_secs(*RECONNECT_TIMEOUT)).await;
+                    if let Some(sender) = sender.upgrade() {
+                        let msg = PlayerActorMessage::ReconnectTimeout { connection };
+                        let _ = sender.send(msg).await;
+                    }
+                });
+            }
+            PlayerActorMessage::ReconnectTimeout { connection } => {
+                if connection == self.connection && self.socket.borrow().is_none() {
+                    self.handle_disconnect().await;
+                }
+            }
+        }
+    }
+
+    async fn handle_poker_message(&mut self, poker_msg: PokerMessage) {
         match poker_msg {
             PokerMessage::Client(msg) => match msg {
                 Either::Lobby(lobby_msg) => {
@@ -75,10 +215,137 @@ impl PlayerActor {
                     }
                 }
             },
+            PokerMessage::Server(Either::Room(RoomMessage {
+                room_id,
+                payload: ServerRoomPayload::StackReturned { stack },
+            })) => {
+                // The room has already added the chips to the stored balance
+                self.reload_balance().await;
+                self.seated.remove(&room_id);
+                self.send_to_socket(PokerMessage::stack_returned(room_id, stack));
+            }
+            PokerMessage::Server(Either::Room(RoomMessage {
+                room_id,
+                payload: ServerRoomPayload::TopUpRefunded { chips },
+            })) => {
+                self.reload_balance().await;
+                self.send_to_socket(PokerMessage::top_up_refunded(room_id, chips));
+            }
             msg @ PokerMessage::Server(_) => self.send_to_socket(msg),
         };
     }
 
+    /// Picks up the balance the player had last time, or saves the new
+    /// player with the default balance
+    async fn load(&mut self) {
+        match self.storage.load_balance(self.player.id.clone()).await {
+            Ok(balance) => {
+                self.balance = balance.unwrap_or(*DEFAULT_CHIPS);
+                self.save_balance().await;
+            }
+            Err(e) => error!(err = ?e, "Error loading player's balance"),
+        }
+    }
+
+    /// Catches up with chips rooms have returned to the stored balance
+    async fn reload_balance(&mut self) {
+        match self.storage.load_balance(self.player.id.clone()).await {
+            Ok(Some(balance)) => self.balance = balance,
+            Ok(None) => error!("Player's balance is missing"),
+            Err(e) => error!(err = ?e, "Error loading player's balance"),
+        }
+    }
+
+    /// Takes chips brought to a table from the stored balance, leaving any
+    /// chips rooms have returned to it in the meantime
+    async fn take_chips(&mut self, chips: ChipInt) {
+        match self
+            .storage
+            .debit_player(self.player.id.clone(), chips)
+            .await
+        {
+            Ok(balance) => self.balance = balance,
+            Err(e) => {
+                error!(err = ?e, "Error taking chips from player's balance");
+                self.balance = self.balance.saturating_sub(chips);
+            }
+        }
+    }
+
+    async fn save_balance(&self) {
+        if let Err(e) = self
+            .storage
+            .save_player(self.player.clone(), self.balance)
+            .await
+        {
+            error!(err = ?e, "Error saving player's balance");
+        }
+    }
+
+    async fn set_connected(&self, value: bool) {
+        for room_id in &self.seated {
+            if let Some(room) = self.room_registry.get(room_id.clone()).await {
+                let _ = room.set_connected(self.player.clone(), value).await;
+            }
+        }
+    }
+
+    /// Catches a reconnected player up with the tables they sit at
+    async fn resync_seated(&self) {
+        for room_id in &self.seated {
+            if let Some(room) = self.room_registry.get(room_id.clone()).await {
+                self.send_to_socket(room.table_state(self.player.clone()).await);
+            }
+        }
+    }
+
+    async fn subscribe(&mut self, room: &RoomHandle) {
+        debug!(room = room.id, "Subscribing to room");
+        let subscription = room.subscribe(self.player.id.clone()).await;
+        let task = tokio::spawn(forward_broadcasts(
+            room.clone(),
+            subscription,
+            self.socket.subscribe(),
+            self.player.clone(),
+        ));
+        self.subscriptions.insert(room.id.clone(), task);
+    }
+
+    /// Follows the tables a player brought back after a restart sits at
+    async fn subscribe_seated(&mut self) {
+        for room_id in self.seated.clone() {
+            if let Some(room) = self.room_registry.get(room_id).await {
+                self.subscribe(&room).await;
+            }
+        }
+    }
+
+    /// Stops following every room the player subscribed to
+    async fn unsubscribe_all(&mut self) {
+        for (room_id, task) in self.subscriptions.drain() {
+            task.abort();
+            if let Some(room) = self.room_registry.get(room_id).await {
+                room.unsubscribe(self.player.id.clone()).await;
+            }
+        }
+    }
+
+    /// Stands up from every table once the player has been gone too long
+    async fn handle_disconnect(&mut self) {
+        self.abandoned = true;
+        for room_id in self.seated.clone() {
+            let Some(room) = self.room_registry.get(room_id.clone()).await else {
+                self.seated.remove(&room_id);
+                continue;
+            };
+            // Seats are given up once the stack comes back
+            if let Err(e) = room.stand_up(self.player.clone()).await {
+                error!(err = ?e, "Error standing up disconnected player");
+                self.seated.remove(&room_id);
+            }
+        }
+    }
+
     async fn handle_lobby_message(&self, msg: ClientLobby) -> Result<()> {
         match msg {
             ClientLobby::GetTables => {
@@ -91,6 +358,49 @@ impl PlayerActor {
                 let tables_msg = PokerMessage::table_list(tables);
                 self.send_to_socket(tables_msg);
             }
+            ClientLobby::CreateTable { config } => {
+                let listing = RoomHandle::open(
+                    config,
+                    Some(self.player.id.clone()),
+                    self.player_registry.clone(),
+                    self.room_registry.clone(),
+                    self.storage.clone(),
+                )
+                .await?;
+                self.send_to_socket(PokerMessage::table_created(listing));
+            }
+            ClientLobby::CloseTable { id } => {
+                let room = self
+                    .room_registry
+                    .get(id.clone())
+                    .await
+                    .ok_or(eyre!("Not a valid room id"))?;
+                room.close(Some(self.player.clone())).await?;
+                self.send_to_socket(PokerMessage::table_closing(id));
+            }
+            ClientLobby::GetHandHistories { limit } => {
+                let limit = limit
+                    .unwrap_or(DEFAULT_HAND_HISTORIES)
+                    .min(MAX_HAND_HISTORIES);
+                let hands = self
+                    .storage
+                    .player_hands(self.player.id.clone(), limit)
+                    .await?
+                    .iter()
+                    .map(|history| history.seen_by(&self.player.id))
+                    .collect();
+                self.send_to_socket(PokerMessage::hand_histories(hands));
+            }
+            ClientLobby::ExportHandHistory { id } => {
+                let history = self
+                    .storage
+                    .load_hand(id)
+                    .await?
+                    .filter(|history| history.was_dealt(&self.player.id))
+                    .ok_or(eyre!("Not a hand you played"))?;
+                let text = history.seen_by(&self.player.id).to_pokerstars();
+                self.send_to_socket(PokerMessage::hand_history_export(id, text));
+            }
         }
         Ok(())
     }
@@ -113,29 +423,55 @@ impl PlayerActor {
                     .await
             }
             ClientRoomPayload::Subscribe => {
-                let mut subscription = room.subscribe().await;
-                let socket = self.socket.clone();
-                debug!(room = room.id, "Subscribing to room");
-                tokio::spawn(async move {
-                    while let Ok(msg) = subscription.recv().await {
-                        debug!("Broadcasting message to player's socket");
-                        if let Err(e) = socket.send(msg).await {
-                            error!(err = ?e, "Error broadcasting to socket");
-                            break;
-                        }
-                    }
-                });
+                // Subscribing again just resyncs
+                let subscribed = self
+                    .subscriptions
+                    .get(&room_id)
+                    .is_some_and(|task| !task.is_finished());
+                if !subscribed {
+                    self.subscribe(&room).await;
+                }
+                self.send_to_socket(room.table_state(self.player.clone()).await);
+                Ok(())
+            }
+            ClientRoomPayload::Unsubscribe => {
+                let task = self
+                    .subscriptions
+                    .remove(&room_id)
+                    .ok_or_else(|| eyre!("Not subscribed to room"))?;
+                task.abort();
+                room.unsubscribe(self.player.id.clone()).await;
                 Ok(())
             }
-            ClientRoomPayload::SitTable { chips } => {
-                // TODO: Read chips from smart contract
-                if chips > *DEFAULT_CHIPS {
+            ClientRoomPayload::Resync => {
+                self.send_to_socket(room.table_state(self.player.clone()).await);
+                Ok(())
+            }
+            ClientRoomPayload::SitTable { chips, seat } => {
+                if chips > self.balance {
                     bail!("Insufficient Chips");
                 }
-                let table_player = self.player.clone();
-                room.sit_table(table_player).await
+                room.sit_table(self.player.clone(), chips, seat).await?;
+                self.take_chips(chips).await;
+                self.seated.insert(room_id);
+                Ok(())
+            }
+            ClientRoomPayload::StandUp => room.stand_up(self.player.clone()).await,
+            ClientRoomPayload::TopUp { chips } => {
+                if chips.is_some_and(|chips| chips > self.balance) {
+                    bail!("Insufficient Chips");
+                }
+                let taken = room
+                    .top_up(self.player.clone(), chips, self.balance)
+                    .await?;
+                self.take_chips(taken).await;
+                Ok(())
             }
             ClientRoomPayload::Bet(chips) => room.bet(self.player.clone(), chips).await,
+            ClientRoomPayload::Check => room.check(self.player.clone()).await,
+            ClientRoomPayload::Call => room.call(self.player.clone()).await,
+            ClientRoomPayload::Raise { to } => room.raise(self.player.clone(), to).await,
+            ClientRoomPayload::AllIn => room.all_in(self.player.clone()).await,
             ClientRoomPayload::Fold => room.fold(self.player.clone()).await,
 
             ClientRoomPayload::SitOutNextHand(value) => {
@@ -152,18 +488,81 @@ impl PlayerActor {
                 room.check_fold(self.player.clone(), value).await
             }
             ClientRoomPayload::CallAny(value) => room.call_any(self.player.clone(), value).await,
+            ClientRoomPayload::ShowCards(cards) => {
+                room.show_cards(self.player.clone(), cards).await
+            }
+            ClientRoomPayload::MuckCards => room.muck_cards(self.player.clone()).await,
+            ClientRoomPayload::AutoMuck(value) => room.auto_muck(self.player.clone(), value).await,
+            ClientRoomPayload::ClientSeed(seed) => {
+                room.client_seed(self.player.clone(), seed).await
+            }
         }
     }
 
     fn send_to_socket(&self, msg: PokerMessage) {
-        if let Err(e) = self.socket.try_send(msg) {
+        let Some(socket) = self.socket.borrow().clone() else {
+            debug!(
+                player = self.player.id,
+                "Player is disconnected, dropping message"
+            );
+            return;
+        };
+        if let Err(e) = socket.try_send(msg) {
             error!(e = ?e, "Error sending table message to socket")
         }
     }
 }
 
+/// Sends a room's broadcasts meant for the player to whichever socket they
+/// are connected on. Subscriptions outlive sockets so they carry on after
+/// reconnecting, and end when the player unsubscribes or leaves for good.
+async fn forward_broadcasts(
+    room: RoomHandle,
+    mut subscription: broadcast::Receiver<RoomBroadcast>,
+    mut socket: watch::Receiver<Option<mpsc::Sender<PokerMessage>>>,
+    player: Player,
+) {
+    loop {
+        let msg = match subscription.recv().await {
+            Ok(broadcast) if broadcast.is_for(&player.id) => broadcast.msg,
+            Ok(_) => continue,
+            // Too slow to keep up, so skip what was missed and catch up from
+            // the table as it is now
+            Err(RecvError::Lagged(missed)) => {
+                debug!(player = player.id, missed, "Resyncing lagged subscriber");
+                subscription = subscription.resubscribe();
+                room.table_state(player.clone()).await
+            }
+            Err(RecvError::Closed) => break,
+        };
+        // The player has left for good
+        if socket.has_changed().is_err() {
+            break;
+        }
+        // Messages sent while disconnected are caught up on by resyncing
+        let Some(current) = socket.borrow_and_update().clone() else {
+            continue;
+        };
+        debug!("Broadcasting message to player's socket");
+        if let Err(e) = current.send(msg).await {
+            error!(err = ?e, "Error broadcasting to socket");
+        }
+    }
+}
+
 async fn run(mut player: PlayerActor) {
+    player.load().await;
+    player.subscribe_seated().await;
     while let Some(msg) = player.receiver.recv().await {
-        let _ = player.handle_message(msg).await;
+        player.handle_message(msg).await;
+        if player.is_finished() {
+            break;
+        }
     }
+    debug!(player = player.player.id, "Player left");
+    player.unsubscribe_all().await;
+    player
+        .player_registry
+        .delete(player.player.id.clone())
+        .await;
 }
@@ -1,32 +1,68 @@
 use crate::*;
-use tokio::sync::{broadcast, mpsc, oneshot};
-use tokio::time::{sleep, Duration};
+use rs_poker::core::{Card, Hand};
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::time::{sleep, sleep_until, Duration, Instant};
 
 pub type RoomId = String;
 
 enum RoomActorMessage {
     GetTable {
-        respond_to: oneshot::Sender<TableConfig>,
+        respond_to: oneshot::Sender<TableListing>,
+    },
+    /// `None` when an admin closes the table
+    Close {
+        player: Option<Player>,
+        respond_to: oneshot::Sender<Result<()>>,
     },
     Subscribe {
-        respond_to: oneshot::Sender<broadcast::Receiver<PokerMessage>>,
+        player: PlayerId,
+        respond_to: oneshot::Sender<broadcast::Receiver<RoomBroadcast>>,
+    },
+    Unsubscribe {
+        player: PlayerId,
+    },
+    TableState {
+        player: Player,
+        respond_to: oneshot::Sender<PokerMessage>,
     },
     SitTable {
         player: Player,
+        chips: ChipInt,
+        seat: Option<usize>,
         respond_to: oneshot::Sender<Result<()>>,
     },
-    Chat {
-        from: PlayerId,
-        message: String,
+    StandUp {
+        player: Player,
         respond_to: oneshot::Sender<Result<()>>,
     },
-    Bet {
+    TopUp {
         player: Player,
-        chips: ChipInt,
+        chips: Option<ChipInt>,
+        balance: ChipInt,
+        respond_to: oneshot::Sender<Result<ChipInt>>,
+    },
+    SetConnected {
+        player: Player,
+        value: bool,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+    /// A turn timer ran out
+    TimerExpired {
+        player: Player,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+    Chat {
+        from: PlayerId,
+        message: String,
         respond_to: oneshot::Sender<Result<()>>,
     },
-    Fold {
+    Action {
         player: Player,
+        action: Action,
         respond_to: oneshot::Sender<Result<()>>,
     },
     SitOutNextHand {
@@ -58,6 +94,35 @@ enum RoomActorMessage {
         value: bool,
         respond_to: oneshot::Sender<Result<()>>,
     },
+
+    ShowCards {
+        player: Player,
+        cards: Vec<Card>,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+
+    MuckCards {
+        player: Player,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+
+    AutoMuck {
+        player: Player,
+        value: bool,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+
+    ClientSeed {
+        player: Player,
+        seed: String,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+
+    Recover {
+        journal: Box<TableJournal>,
+        recovery: HandRecovery,
+        respond_to: oneshot::Sender<Result<Vec<Player>>>,
+    },
 }
 
 #[derive(Clone)]
@@ -69,27 +134,126 @@ pub struct RoomHandle {
 impl RoomHandle {
     pub fn new(
         table: Table,
+        owner: Option<PlayerId>,
         player_registry: RegistryHandle<PlayerId, PlayerHandle>,
         room_registry: RegistryHandle<RoomId, RoomHandle>,
+        storage: StorageHandle,
     ) -> Self {
         let (sender, receiver) = mpsc::channel(*CHANNEL_SIZE);
         let id = table.id().clone();
-        let room = Room::new(receiver, table, player_registry, room_registry);
+        let room = Room::new(
+            receiver,
+            table,
+            owner,
+            player_registry,
+            room_registry,
+            storage,
+        );
         tokio::spawn(run(room));
 
         Self { sender, id }
     }
 
-    pub async fn get_table(&self) -> TableConfig {
+    /// Spawns a room for a new table and registers it, returning the table's
+    /// listing with its generated id
+    pub async fn open(
+        mut config: TableConfig,
+        owner: Option<PlayerId>,
+        player_registry: RegistryHandle<PlayerId, PlayerHandle>,
+        room_registry: RegistryHandle<RoomId, RoomHandle>,
+        storage: StorageHandle,
+    ) -> Result<TableListing> {
+        config.validate()?;
+        config.id = hex::encode(rand::random::<[u8; 8]>());
+        let stored = StoredTable {
+            config: config.clone(),
+            owner: owner.clone(),
+        };
+        storage.save_table(stored).await?;
+        let table = Table::from_config(config.clone());
+        let room = RoomHandle::new(
+            table,
+            owner.clone(),
+            player_registry,
+            room_registry.clone(),
+            storage,
+        );
+        room_registry.set(room.id.clone(), room).await;
+        Ok(TableListing {
+            config,
+            owner,
+            spectators: 0,
+        })
+    }
+
+    /// Closes the table once any hand in progress is over. `player` must be
+    /// the table's owner, or `None` for an admin.
+    pub async fn close(&self, player: Option<Player>) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::Close {
+            player,
+            respond_to: send,
+        };
+        let _ = self.sender.send(msg).await;
+        recv.await.expect("Room task has been killed")
+    }
+
+    pub async fn get_table(&self) -> TableListing {
         let (send, recv) = oneshot::channel();
         let msg = RoomActorMessage::GetTable { respond_to: send };
         let _ = self.sender.send(msg).await;
         recv.await.expect("Room task has been killed")
     }
 
-    pub async fn subscribe(&self) -> broadcast::Receiver<PokerMessage> {
+    pub async fn subscribe(&self, player: PlayerId) -> broadcast::Receiver<RoomBroadcast> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::Subscribe {
+            player,
+            respond_to: send,
+        };
+        let _ = self.sender.send(msg).await;
+        recv.await.expect("Room task has been killed")
+    }
+
+    /// Stops counting the player as a subscriber. Their broadcast receiver is
+    /// dropped by the caller.
+    pub async fn unsubscribe(&self, player: PlayerId) {
+        let _ = self
+            .sender
+            .send(RoomActorMessage::Unsubscribe { player })
+            .await;
+    }
+
+    /// The table as `player` sees it, to catch up after missing broadcasts
+    pub async fn table_state(&self, player: Player) -> PokerMessage {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::TableState {
+            player,
+            respond_to: send,
+        };
+        let _ = self.sender.send(msg).await;
+        recv.await.expect("Room task has been killed")
+    }
+
+    /// Tells the room whether a seated player's socket is connected, giving
+    /// them some grace to come back if their time runs out while they're gone
+    pub async fn set_connected(&self, player: Player, value: bool) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::SetConnected {
+            player,
+            value,
+            respond_to: send,
+        };
+        let _ = self.sender.send(msg).await;
+        recv.await.expect("Room task has been killed")
+    }
+
+    async fn timer_expired(&self, player: Player) -> Result<()> {
         let (send, recv) = oneshot::channel();
-        let msg = RoomActorMessage::Subscribe { respond_to: send };
+        let msg = RoomActorMessage::TimerExpired {
+            player,
+            respond_to: send,
+        };
         let _ = self.sender.send(msg).await;
         recv.await.expect("Room task has been killed")
     }
@@ -105,31 +269,80 @@ impl RoomHandle {
         recv.await.expect("Room task has been killed")
     }
 
-    pub async fn sit_table(&self, player: Player) -> Result<()> {
+    pub async fn sit_table(
+        &self,
+        player: Player,
+        chips: ChipInt,
+        seat: Option<usize>,
+    ) -> Result<()> {
         let (send, recv) = oneshot::channel();
         let msg = RoomActorMessage::SitTable {
             player,
+            chips,
+            seat,
             respond_to: send,
         };
         let _ = self.sender.try_send(msg);
         recv.await.expect("Room task has been killed")
     }
 
-    pub async fn bet(&self, player: Player, chips: ChipInt) -> Result<()> {
+    pub async fn stand_up(&self, player: Player) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::StandUp {
+            player,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    /// Returns the chips taken from `balance`
+    pub async fn top_up(
+        &self,
+        player: Player,
+        chips: Option<ChipInt>,
+        balance: ChipInt,
+    ) -> Result<ChipInt> {
         let (send, recv) = oneshot::channel();
-        let msg = RoomActorMessage::Bet {
+        let msg = RoomActorMessage::TopUp {
             player,
             chips,
+            balance,
             respond_to: send,
         };
-        let _ = self.sender.try_send(msg);
-        recv.await.expect("Room task has been killed")
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn bet(&self, player: Player, chips: ChipInt) -> Result<()> {
+        self.act(player, Action::BetTo(chips)).await
+    }
+
+    pub async fn check(&self, player: Player) -> Result<()> {
+        self.act(player, Action::Check).await
+    }
+
+    pub async fn call(&self, player: Player) -> Result<()> {
+        self.act(player, Action::Call).await
+    }
+
+    pub async fn raise(&self, player: Player, to: ChipInt) -> Result<()> {
+        self.act(player, Action::Raise(to)).await
+    }
+
+    pub async fn all_in(&self, player: Player) -> Result<()> {
+        self.act(player, Action::AllIn).await
     }
 
     pub async fn fold(&self, player: Player) -> Result<()> {
+        self.act(player, Action::Fold).await
+    }
+
+    async fn act(&self, player: Player, action: Action) -> Result<()> {
         let (send, recv) = oneshot::channel();
-        let msg = RoomActorMessage::Fold {
+        let msg = RoomActorMessage::Action {
             player,
+            action,
             respond_to: send,
         };
         let _ = self.sender.try_send(msg);
@@ -186,32 +399,201 @@ impl RoomHandle {
         let _ = self.sender.try_send(msg);
         recv.await.expect("Room task has been killed")
     }
+    pub async fn show_cards(&self, player: Player, cards: Vec<Card>) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::ShowCards {
+            player,
+            cards,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+    pub async fn muck_cards(&self, player: Player) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::MuckCards {
+            player,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+    pub async fn auto_muck(&self, player: Player, value: bool) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::AutoMuck {
+            player,
+            value,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+    pub async fn client_seed(&self, player: Player, seed: String) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::ClientSeed {
+            player,
+            seed,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    /// Puts the table back from its journal after a restart, returning the
+    /// players sitting at it
+    pub async fn recover(
+        &self,
+        journal: TableJournal,
+        recovery: HandRecovery,
+    ) -> Result<Vec<Player>> {
+        let (send, recv) = oneshot::channel();
+        let msg = RoomActorMessage::Recover {
+            journal: Box::new(journal),
+            recovery,
+            respond_to: send,
+        };
+        let _ = self.sender.send(msg).await;
+        recv.await.expect("Room task has been killed")
+    }
+}
+
+/// Hands still to be shown or mucked after a game reaches showdown
+struct Showdown {
+    /// Game indexes of players still to show or muck, in showdown order
+    pending: VecDeque<usize>,
+    /// Game indexes of players who showed their hand
+    shown: Vec<usize>,
+}
+
+impl Showdown {
+    /// Picks the showdown up after the players who already showed (`true`)
+    /// or mucked
+    fn resume(game: &Game, decided: &[(Player, bool)]) -> Self {
+        let mut showdown = Self {
+            pending: game.showdown_order().into(),
+            shown: vec![],
+        };
+        for (player, showed) in decided {
+            let Some(idx) = game.player_idx(player) else {
+                continue;
+            };
+            showdown.pending.retain(|pending| *pending != idx);
+            if *showed {
+                showdown.shown.push(idx);
+            }
+        }
+        showdown
+    }
+}
+
+/// A message for a room's subscribers. With a spectator delay, everything
+/// but chat goes live to the players seated at the time and reaches everyone
+/// else still not seated once the delay is up.
+#[derive(Debug, Clone)]
+pub struct RoomBroadcast {
+    pub msg: PokerMessage,
+    audience: Audience,
+}
+
+#[derive(Debug, Clone)]
+enum Audience {
+    Everyone,
+    Only(Arc<HashSet<PlayerId>>),
+    Except(Arc<HashSet<PlayerId>>),
+}
+
+impl RoomBroadcast {
+    fn everyone(msg: PokerMessage) -> Self {
+        Self {
+            msg,
+            audience: Audience::Everyone,
+        }
+    }
+
+    pub fn is_for(&self, player: &PlayerId) -> bool {
+        match &self.audience {
+            Audience::Everyone => true,
+            Audience::Only(players) => players.contains(player),
+            Audience::Except(players) => !players.contains(player),
+        }
+    }
+}
+
+/// The turn being timed
+struct TurnClock {
+    player: Player,
+    timer: TurnTimer,
+    started: Instant,
 }
 
 struct Room {
     receiver: mpsc::Receiver<RoomActorMessage>,
     player_registry: RegistryHandle<PlayerId, PlayerHandle>,
     room_registry: RegistryHandle<RoomId, RoomHandle>,
-    broadcast: broadcast::Sender<PokerMessage>,
+    storage: StorageHandle,
+    broadcast: broadcast::Sender<RoomBroadcast>,
+    /// Queues broadcasts for spectators when the table has a spectator delay
+    spectator_queue: Option<mpsc::UnboundedSender<(Instant, RoomBroadcast)>>,
+    /// Players seated as of the last broadcast, who already got it and every
+    /// one after it live
+    seated: watch::Sender<Arc<HashSet<PlayerId>>>,
+    /// Players subscribed to the room, seated or not
+    subscribers: HashSet<PlayerId>,
     table: Table,
+    /// Player who created the table through the lobby, who may close it
+    owner: Option<PlayerId>,
     turn_timer_cancel: Option<mpsc::Sender<()>>,
+    /// When the running turn or showdown timer expires
+    timer_deadline: Option<Instant>,
+    turn_clock: Option<TurnClock>,
+    showdown: Option<Showdown>,
+    /// Winner of the hand just over that nobody called, who may show any of
+    /// their cards before the next deal unless they auto-muck
+    fold_winner: Option<(Player, Hand)>,
+    /// Closes once the hand in progress is over, so no new hands start
+    closing: bool,
+    /// Whether the table's journal has a snapshot to add changes to
+    journaled: bool,
 }
 
 impl Room {
     fn new(
         receiver: mpsc::Receiver<RoomActorMessage>,
         table: Table,
+        owner: Option<PlayerId>,
         player_registry: RegistryHandle<PlayerId, PlayerHandle>,
         room_registry: RegistryHandle<RoomId, RoomHandle>,
+        storage: StorageHandle,
     ) -> Self {
-        let (broadcast, _) = broadcast::channel(*CHANNEL_SIZE);
+        let (broadcast, _) = broadcast::channel(table.config.broadcast_capacity);
+        let (seated, _) = watch::channel(Arc::default());
+        let spectator_queue = (table.config.spectator_delay > 0).then(|| {
+            let (queue, delayed) = mpsc::unbounded_channel();
+            tokio::spawn(delay_broadcasts(
+                delayed,
+                broadcast.clone(),
+                seated.subscribe(),
+            ));
+            queue
+        });
         Room {
             receiver,
             table,
+            owner,
             broadcast,
+            spectator_queue,
+            seated,
+            subscribers: HashSet::new(),
             player_registry,
             room_registry,
+            storage,
             turn_timer_cancel: None,
+            timer_deadline: None,
+            turn_clock: None,
+            showdown: None,
+            fold_winner: None,
+            closing: false,
+            journaled: false,
         }
     }
 
@@ -219,14 +601,85 @@ impl Room {
         self.table.id()
     }
 
+    /// Subscribers who aren't sitting at the table
+    fn spectators(&self) -> usize {
+        let seated: HashSet<_> = self.table.players().map(|p| &p.info.id).collect();
+        self.subscribers
+            .iter()
+            .filter(|id| !seated.contains(id))
+            .count()
+    }
+
+    /// Broadcasts a table message, holding it back from spectators for the
+    /// table's spectator delay
+    fn publish(&self, msg: PokerMessage) -> Result<()> {
+        let Some(queue) = &self.spectator_queue else {
+            self.broadcast.send(RoomBroadcast::everyone(msg))?;
+            return Ok(());
+        };
+        let seated: Arc<HashSet<_>> =
+            Arc::new(self.table.players().map(|p| p.info.id.clone()).collect());
+        self.seated.send_replace(seated.clone());
+        let due = Instant::now() + Duration::from_secs(self.table.config.spectator_delay);
+        let delayed = RoomBroadcast {
+            msg: msg.clone(),
+            audience: Audience::Except(seated.clone()),
+        };
+        let _ = queue.send((due, delayed));
+        self.broadcast.send(RoomBroadcast {
+            msg,
+            audience: Audience::Only(seated),
+        })?;
+        Ok(())
+    }
+
     async fn handle_message(&mut self, msg: RoomActorMessage) {
         match msg {
             RoomActorMessage::GetTable { respond_to } => {
-                let _ = respond_to.send(self.table.config.clone());
+                let listing = TableListing {
+                    config: self.table.config.clone(),
+                    owner: self.owner.clone(),
+                    spectators: self.spectators(),
+                };
+                let _ = respond_to.send(listing);
             }
-            RoomActorMessage::Subscribe { respond_to } => {
+            RoomActorMessage::Close { player, respond_to } => {
+                let _ = respond_to.send(self.handle_close(player).await);
+            }
+            RoomActorMessage::Subscribe { player, respond_to } => {
+                self.subscribers.insert(player);
                 let _ = respond_to.send(self.broadcast.subscribe());
             }
+            RoomActorMessage::Unsubscribe { player } => {
+                self.subscribers.remove(&player);
+            }
+            RoomActorMessage::TableState { player, respond_to } => {
+                let timer = self
+                    .timer_deadline
+                    .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+                    .filter(|remaining| !remaining.is_zero())
+                    .map(|remaining| remaining.as_secs_f64().ceil() as u64);
+                // Spectators behind a delay catch up with the game from the next update
+                let live = self.table.config.spectator_delay == 0
+                    || self.table.player_seat(&player).is_some();
+                let _ = respond_to.send(PokerMessage::table_state(
+                    self.id().clone(),
+                    &self.table,
+                    &player,
+                    timer.filter(|_| live),
+                    live,
+                ));
+            }
+            RoomActorMessage::SetConnected {
+                player,
+                value,
+                respond_to,
+            } => {
+                let _ = respond_to.send(self.table.set_connected(&player, value));
+            }
+            RoomActorMessage::TimerExpired { player, respond_to } => {
+                let _ = respond_to.send(self.handle_timer_expired(player).await);
+            }
             RoomActorMessage::Chat {
                 from,
                 message,
@@ -234,25 +687,39 @@ impl Room {
             } => {
                 let _ = respond_to.send(self.handle_chat(from, message));
             }
-            RoomActorMessage::SitTable { player, respond_to } => {
-                let _ = respond_to.send(self.handle_sit(player).await);
+            RoomActorMessage::SitTable {
+                player,
+                chips,
+                seat,
+                respond_to,
+            } => {
+                let _ = respond_to.send(self.handle_sit(player, chips, seat).await);
+            }
+            RoomActorMessage::StandUp { player, respond_to } => {
+                let _ = respond_to.send(self.handle_stand_up(player).await);
             }
-            RoomActorMessage::Bet {
+            RoomActorMessage::TopUp {
                 player,
                 chips,
+                balance,
                 respond_to,
             } => {
-                let _ = respond_to.send(self.handle_bet(player, chips).await);
+                let _ = respond_to.send(self.handle_top_up(player, chips, balance).await);
             }
-            RoomActorMessage::Fold { player, respond_to } => {
-                let _ = respond_to.send(self.handle_fold(player).await);
+            RoomActorMessage::Action {
+                player,
+                action,
+                respond_to,
+            } => {
+                let _ = respond_to.send(self.handle_action(player, action).await);
             }
             RoomActorMessage::SitOutNextHand {
                 player,
                 value,
                 respond_to,
             } => {
-                let _ = respond_to.send(self.table.set_sit_out_next_hand(&player, value));
+                let setting = PlayerSetting::SitOutNextHand;
+                let _ = respond_to.send(self.handle_setting(player, setting, value).await);
             }
 
             RoomActorMessage::SitOutNextBigBlind {
@@ -260,7 +727,8 @@ impl Room {
                 value,
                 respond_to,
             } => {
-                let _ = respond_to.send(self.table.set_sit_out_next_big_blind(&player, value));
+                let setting = PlayerSetting::SitOutNextBigBlind;
+                let _ = respond_to.send(self.handle_setting(player, setting, value).await);
             }
 
             RoomActorMessage::WaitForBigBlind {
@@ -268,7 +736,8 @@ impl Room {
                 value,
                 respond_to,
             } => {
-                let _ = respond_to.send(self.table.set_wait_for_big_blind(&player, value));
+                let setting = PlayerSetting::WaitForBigBlind;
+                let _ = respond_to.send(self.handle_setting(player, setting, value).await);
             }
 
             RoomActorMessage::CheckFold {
@@ -276,48 +745,87 @@ impl Room {
                 value,
                 respond_to,
             } => {
-                let _ = respond_to.send(self.table.set_check_fold(&player, value));
+                let setting = PlayerSetting::CheckFold;
+                let _ = respond_to.send(self.handle_setting(player, setting, value).await);
             }
             RoomActorMessage::CallAny {
                 player,
                 value,
                 respond_to,
             } => {
-                let _ = respond_to.send(self.table.set_call_any(&player, value));
+                let setting = PlayerSetting::CallAny;
+                let _ = respond_to.send(self.handle_setting(player, setting, value).await);
+            }
+
+            RoomActorMessage::ShowCards {
+                player,
+                cards,
+                respond_to,
+            } => {
+                let _ = respond_to.send(self.handle_show_cards(player, cards).await);
+            }
+            RoomActorMessage::MuckCards { player, respond_to } => {
+                let _ = respond_to.send(self.handle_muck_cards(player).await);
+            }
+            RoomActorMessage::Recover {
+                journal,
+                recovery,
+                respond_to,
+            } => {
+                let _ = respond_to.send(self.handle_recover(*journal, recovery).await);
+            }
+            RoomActorMessage::AutoMuck {
+                player,
+                value,
+                respond_to,
+            } => {
+                let setting = PlayerSetting::AutoMuck;
+                let _ = respond_to.send(self.handle_setting(player, setting, value).await);
+            }
+            RoomActorMessage::ClientSeed {
+                player,
+                seed,
+                respond_to,
+            } => {
+                let _ = respond_to.send(self.handle_client_seed(player, seed).await);
             }
         }
     }
 
     fn handle_chat(&mut self, from: String, message: String) -> Result<()> {
         let broadcast_msg = PokerMessage::chat_broadcast(self.id().clone(), from, message);
-        if let Err(e) = self.broadcast.send(broadcast_msg) {
+        if let Err(e) = self.broadcast.send(RoomBroadcast::everyone(broadcast_msg)) {
             error!(err = ?e, "Error broadcasting chat message");
         }
         Ok(())
     }
 
-    async fn handle_sit(&mut self, player: Player) -> Result<()> {
-        // TODO: Handle min and max buy-in
+    async fn handle_sit(
+        &mut self,
+        player: Player,
+        chips: ChipInt,
+        seat: Option<usize>,
+    ) -> Result<()> {
         // TODO: Handle chips from smart contract
         // TODO: Handle "going south"
-        if self.table.num_players() >= self.table.max_players() {
-            debug!(player = ?player, "Max players at table");
-            bail!("Table is full")
-        }
-        if self.table.players.iter().any(|p| p.info.id == player.id) {
-            debug!(player = ?player, "Player already sat");
-            bail!("Already sitting at table")
+        if self.closing {
+            bail!("Table is closing")
         }
+        let seat = self
+            .table
+            .sit_player(player.clone(), chips, seat)
+            .inspect_err(|e| debug!(player = ?player, err = ?e, "Player can't sit"))?;
+        self.journal(JournalEntry::Sat {
+            player: player.clone(),
+            chips,
+            seat,
+        })
+        .await;
 
-        let sit_table_msg = PokerMessage::sit_table_broadcast(
-            self.table.id().clone(),
-            player.clone(),
-            self.table.players.len(),
-        );
-
-        self.table.players.push(player.into());
+        let sit_table_msg =
+            PokerMessage::sit_table_broadcast(self.table.id().clone(), player, seat, chips);
 
-        if let Err(e) = self.broadcast.send(sit_table_msg) {
+        if let Err(e) = self.publish(sit_table_msg) {
             error!(err = ?e, "Error broadcasting sat table");
         }
 
@@ -330,14 +838,30 @@ impl Room {
         if self.table.game().is_some() && !self.table.game().unwrap().is_over() {
             bail!("Game is already in progress");
         }
+        if self.showdown.is_some() {
+            bail!("Showdown is in progress");
+        }
+        if self.fold_winner.is_some() {
+            bail!("Winner may still show their cards");
+        }
+        if self.closing {
+            bail!("Table is closing");
+        }
 
-        self.table.start_new_game()?;
+        let next = self.table.next_game()?;
+        let deck = self.table.shuffle(&next);
+        let mut snapshot = TableSnapshot::new(&self.table);
+        self.table.deal(&next, deck.clone())?;
+        snapshot.hand = Some(JournaledHand {
+            next,
+            deck,
+            started_at: self.table.game().unwrap().started_at,
+        });
+        self.save_snapshot(snapshot).await;
 
-        self.run_turn_timer(self.table.current_player().unwrap().clone())
-            .await;
         let new_game_msg = PokerMessage::new_game(self.id().clone(), self.table.game().unwrap());
 
-        if let Err(e) = self.broadcast.send(new_game_msg) {
+        if let Err(e) = self.publish(new_game_msg) {
             error!(err = ?e, "Error broadcasting new game");
         }
         for (player, hand) in self.table.game().unwrap().players_hands() {
@@ -347,73 +871,705 @@ impl Room {
                 error!(err = ?e, "Error sending deal hand");
             }
         }
+
+        if self.table.game().unwrap().is_over() {
+            // Blinds put everyone all-in, so the hand plays itself out
+            self.broadcast_game_events();
+            let game_update_msg =
+                PokerMessage::game_update(self.id().clone(), self.table.game().unwrap());
+            let _ = self.publish(game_update_msg);
+            Box::pin(self.handle_game_over()).await;
+        } else {
+            self.run_turn_timer(self.table.current_player().unwrap().clone())
+                .await;
+        }
         Ok(())
     }
 
-    async fn handle_bet(&mut self, player: Player, chips: ChipInt) -> Result<()> {
+    async fn handle_action(&mut self, player: Player, action: Action) -> Result<()> {
         let room_id = self.id().clone();
-        if let Some(game) = self.table.game_mut() {
-            if game.is_players_turn(&player) {
-                match game.bet(chips) {
-                    Ok(additional_bet) => {
-                        let game_update_msg = PokerMessage::game_update(room_id, game);
-                        let _ = self.broadcast.send(game_update_msg);
-                        self.run_turn_timer(player).await;
-                        Ok(())
-                    }
-                    Err(e) => {
-                        bail!(e.to_string())
-                    }
-                }
-            } else {
-                bail!("Not your turn")
-            }
-        } else {
+        let Some(game) = self.table.game_mut().filter(|g| !g.is_over()) else {
             bail!("Game is not active")
+        };
+        if !game.is_players_turn(&player) {
+            bail!("Not your turn")
         }
-    }
 
-    async fn handle_fold(&mut self, player: Player) -> Result<()> {
-        let room_id = self.id().clone();
-        if let Some(game) = self.table.game_mut() {
-            if game.is_players_turn(&player) {
-                game.fold();
-                let game_update_msg = PokerMessage::game_update(room_id, game);
-                let _ = self.broadcast.send(game_update_msg);
-
-                if game.is_over() {
-                    // Try starting a new game
-                    // This fails if not enough players for the next game
-                    let _ = self.try_start_new_game().await;
-                } else {
-                    self.run_turn_timer(player).await;
-                }
+        game.act(action)?;
+        self.journal(JournalEntry::Acted { action }).await;
+        self.broadcast_game_events();
+        let game = self.table.game().expect("Game should exist");
+        let game_update_msg = PokerMessage::game_update(room_id, game);
+        let _ = self.publish(game_update_msg);
 
-                Ok(())
-            } else {
-                bail!("Not your turn")
-            }
+        if game.is_over() {
+            self.handle_game_over().await;
         } else {
-            bail!("Game is not active")
+            let next_player = game.current_player().clone();
+            self.run_turn_timer(next_player).await;
         }
+        Ok(())
     }
 
-    async fn send_to_player(&self, id: &PlayerId, msg: PokerMessage) -> Result<()> {
-        self.player_registry
-            .get(id.clone())
-            .await
-            .ok_or(eyre!("Player connection closed"))
-            .map(|p| p.send_message(msg))?
-    }
+    /// Stands the player up straight away if they aren't in the hand. Otherwise
+    /// they fold if still in it, and leave once the hand is over.
+    async fn handle_stand_up(&mut self, player: Player) -> Result<()> {
+        let seat = self
+            .table
+            .player_seat(&player)
+            .ok_or_else(|| eyre!("Not sitting at table"))?;
+        let showdown_pending = self.showdown.is_some();
+        let Some(game) = self
+            .table
+            .game_mut()
+            .filter(|game| !game.is_over() || showdown_pending)
+        else {
+            self.vacate_seat(seat).await;
+            // A winner who leaves has nothing left to show
+            if self
+                .fold_winner
+                .as_ref()
+                .is_some_and(|(winner, _)| winner.id == player.id)
+            {
+                self.end_fold_win().await;
+            }
+            return Ok(());
+        };
+        let Some(idx) = game.player_idx(&player) else {
+            self.vacate_seat(seat).await;
+            return Ok(());
+        };
 
-    async fn run_turn_timer(&mut self, player: Player) {
-        // Cancel previous timer if exists
-        if let Some(cancel) = self.turn_timer_cancel.clone() {
-            let _ = cancel.try_send(());
+        let in_hand = !game.is_over() && game.state.player_active.get(idx);
+        let was_current = game.current_player().clone();
+        if let Some(table_player) = &mut self.table.seats[seat] {
+            table_player.standing_up = true;
+        }
+        self.journal(JournalEntry::StandingUp { seat }).await;
+        if !in_hand {
+            // All-in, folded or at showdown, so they leave after the hand
+            return Ok(());
+        }
+        if was_current.id == player.id {
+            return self.handle_action(player, Action::Fold).await;
         }
-        let duration = Duration::from_secs(*TURN_TIMEOUT); // 30 seconds
-        let (cancel_tx, mut cancel_rx) = mpsc::channel::<()>(1);
-        self.turn_timer_cancel = Some(cancel_tx);
+
+        let game = self.table.game_mut().expect("Game should exist");
+        game.fold_player(idx)?;
+        self.journal(JournalEntry::Folded { player }).await;
+        self.broadcast_game_events();
+        let game = self.table.game().expect("Game should exist");
+        let game_update_msg = PokerMessage::game_update(self.id().clone(), game);
+        let _ = self.publish(game_update_msg);
+
+        if game.is_over() {
+            self.handle_game_over().await;
+        } else if game.current_player().id != was_current.id {
+            let next_player = game.current_player().clone();
+            self.run_turn_timer(next_player).await;
+        }
+        Ok(())
+    }
+
+    /// Tops up straight away between hands, or once the player's hand is over
+    async fn handle_top_up(
+        &mut self,
+        player: Player,
+        chips: Option<ChipInt>,
+        balance: ChipInt,
+    ) -> Result<ChipInt> {
+        if self.closing {
+            bail!("Table is closing")
+        }
+        let showdown_pending = self.showdown.is_some();
+        let in_hand = self.table.game().is_some_and(|game| {
+            game.player_idx(&player).is_some() && (!game.is_over() || showdown_pending)
+        });
+        let taken = self.table.top_up(&player, chips, balance, in_hand)?;
+        self.journal(JournalEntry::ToppedUp {
+            player: player.clone(),
+            chips: taken,
+            pending: in_hand,
+        })
+        .await;
+        if !in_hand {
+            let seat = self
+                .table
+                .player_seat(&player)
+                .expect("Player should be seated");
+            let stack = self.table.seats[seat].as_ref().unwrap().chips;
+            let topped_up_msg = PokerMessage::topped_up(self.id().clone(), player.id, seat, stack);
+            if let Err(e) = self.publish(topped_up_msg) {
+                error!(err = ?e, "Error broadcasting top-up");
+            }
+        }
+        Ok(taken)
+    }
+
+    async fn handle_setting(
+        &mut self,
+        player: Player,
+        setting: PlayerSetting,
+        value: bool,
+    ) -> Result<()> {
+        self.table.set_setting(&player, setting, value)?;
+        self.journal(JournalEntry::Setting {
+            player,
+            setting,
+            value,
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn handle_client_seed(&mut self, player: Player, seed: String) -> Result<()> {
+        self.table.set_client_seed(&player, seed.clone())?;
+        self.journal(JournalEntry::ClientSeed { player, seed })
+            .await;
+        Ok(())
+    }
+
+    /// Puts the table back as it was when the server stopped and carries on
+    /// with any hand in progress. A hand that can't be played back is called
+    /// off instead.
+    async fn handle_recover(
+        &mut self,
+        journal: TableJournal,
+        recovery: HandRecovery,
+    ) -> Result<Vec<Player>> {
+        let config = self.table.config.clone();
+        self.table = journal.restore(config.clone(), recovery).or_else(|e| {
+            if recovery == HandRecovery::Void {
+                return Err(e);
+            }
+            error!(err = ?e, "Couldn't play hand back, calling it off");
+            journal.restore(config, HandRecovery::Void)
+        })?;
+        let seated = self.table.players().map(|p| p.info.clone()).collect();
+
+        match self.table.game().map(Game::is_over) {
+            Some(true) => {
+                self.journaled = true;
+                let game = self.table.game().expect("Game should exist");
+                if game.went_to_showdown() && !game.was_run_out() {
+                    // Carries on from whoever hadn't shown or mucked yet
+                    self.showdown = Some(Showdown::resume(game, &journal.showdown()));
+                    self.advance_showdown().await;
+                } else {
+                    self.handle_game_over().await;
+                }
+            }
+            Some(false) => {
+                self.journaled = true;
+                let current = self.table.current_player().unwrap().clone();
+                self.run_turn_timer(current).await;
+            }
+            None => {
+                // The journal starts over without the hand
+                self.save_snapshot(TableSnapshot::new(&self.table)).await;
+                let _ = self.try_start_new_game().await;
+            }
+        }
+        Ok(seated)
+    }
+
+    async fn handle_close(&mut self, player: Option<Player>) -> Result<()> {
+        if let Some(player) = player {
+            if self.owner.as_ref() != Some(&player.id) {
+                bail!("Only the table's creator can close it")
+            }
+        }
+        if self.closing {
+            bail!("Table is already closing")
+        }
+        self.closing = true;
+
+        let hand_in_progress =
+            self.showdown.is_some() || self.table.game().is_some_and(|game| !game.is_over());
+        if !hand_in_progress {
+            self.close_now().await;
+        }
+        Ok(())
+    }
+
+    /// Cashes out everyone seated, tells subscribers and removes the room from
+    /// the lobby. The actor stops once the last handle to it is dropped.
+    async fn close_now(&mut self) {
+        self.cancel_timer().await;
+        for seat in 0..self.table.seats.len() {
+            self.vacate_seat(seat).await;
+        }
+        let closed_msg = PokerMessage::table_closed(self.id().clone());
+        if let Err(e) = self.publish(closed_msg) {
+            error!(err = ?e, "Error broadcasting table closed");
+        }
+        if let Err(e) = self.storage.delete_journal(self.id().clone()).await {
+            error!(err = ?e, "Error deleting table's journal");
+        }
+        self.journaled = false;
+        if let Err(e) = self.storage.delete_table(self.id().clone()).await {
+            error!(err = ?e, "Error deleting table from storage");
+        }
+        self.room_registry.delete(self.id().clone()).await;
+    }
+
+    /// Frees the seat, announces it and returns the stack to the player
+    async fn vacate_seat(&mut self, seat: usize) {
+        if let Some(table_player) = self.table.seats[seat].take() {
+            let entry = JournalChange::Entry(self.id().clone(), JournalEntry::Vacated { seat });
+            let credit = (table_player.info.id.clone(), table_player.stack());
+            self.settle(entry, None, vec![credit]).await;
+            self.announce_vacated(seat, table_player).await;
+        }
+    }
+
+    /// Tells the player their stack went back to their balance, and everyone
+    /// else that the seat is free
+    async fn announce_vacated(&self, seat: usize, table_player: TablePlayer) {
+        let stack = table_player.stack();
+        let returned_msg = PokerMessage::stack_returned(self.id().clone(), stack);
+        self.notify_credited(&table_player.info.id, returned_msg)
+            .await;
+        let vacated_msg =
+            PokerMessage::seat_vacated_broadcast(self.id().clone(), table_player.info, seat);
+        if let Err(e) = self.publish(vacated_msg) {
+            error!(err = ?e, "Error broadcasting seat vacated");
+        }
+    }
+
+    /// Announces streets dealt and all-in run outs since the last call
+    fn broadcast_game_events(&mut self) {
+        let room_id = self.id().clone();
+        let Some(events) = self.table.game_mut().map(Game::take_events) else {
+            return;
+        };
+        let game = self.table.game().expect("Game should exist");
+        let msgs: Vec<_> = events
+            .into_iter()
+            .map(|event| match event {
+                GameEvent::CommunityCards(board) => PokerMessage::community_cards(
+                    room_id.clone(),
+                    (board[0], board[1], board[2]),
+                    board.get(3).copied(),
+                    board.get(4).copied(),
+                ),
+                GameEvent::RunOut => PokerMessage::run_out(room_id.clone(), game),
+            })
+            .collect();
+        for msg in msgs {
+            if let Err(e) = self.publish(msg) {
+                error!(err = ?e, "Error broadcasting game event");
+            }
+        }
+    }
+
+    async fn handle_game_over(&mut self) {
+        self.cancel_timer().await;
+        self.fold_winner = None;
+
+        let game = self.table.game().expect("Game should exist");
+        if !game.went_to_showdown() {
+            let winner = game.contenders().ones().next().map(|idx| {
+                (
+                    game.players[idx].info.clone(),
+                    game.state.hands[idx].clone(),
+                )
+            });
+            // Holds up the next deal while the winner decides
+            if !self.closing {
+                self.fold_winner = winner.filter(|(player, _)| !self.auto_mucks(&player.id));
+            }
+            self.finish_game(vec![]).await;
+            if let Some((player, _)) = self.fold_winner.clone() {
+                self.prompt_show_or_muck(player).await;
+            }
+        } else if game.was_run_out() {
+            // Every hand was already turned up for the run out
+            let shown = game.showdown_order();
+            self.finish_game(shown).await;
+        } else {
+            self.showdown = Some(Showdown::resume(game, &[]));
+            self.advance_showdown().await;
+        }
+    }
+
+    /// Shows or mucks hands in showdown order until a player has to decide,
+    /// then finishes the game once every hand is settled
+    async fn advance_showdown(&mut self) {
+        while let Some(idx) = self
+            .showdown
+            .as_ref()
+            .and_then(|showdown| showdown.pending.front().copied())
+        {
+            let game = self.table.game().expect("Game should exist");
+            let player = game.players[idx].info.clone();
+            // The first hand and every winning hand must be shown
+            let must_show = self.showdown.as_ref().unwrap().shown.is_empty() || game.is_winner(idx);
+
+            if must_show {
+                self.show_hand(idx).await;
+                continue;
+            }
+            if self.auto_mucks(&player.id) {
+                self.muck_hand(idx).await;
+                continue;
+            }
+
+            self.prompt_show_or_muck(player).await;
+            return;
+        }
+
+        if let Some(showdown) = self.showdown.take() {
+            self.finish_game(showdown.shown).await;
+        }
+    }
+
+    /// Whether the player mucks without being asked, which anyone no longer
+    /// seated or leaving after the hand does
+    fn auto_mucks(&self, player: &PlayerId) -> bool {
+        self.table
+            .players()
+            .find(|p| &p.info.id == player)
+            .is_none_or(|p| p.auto_muck || p.standing_up)
+    }
+
+    /// Asks the player to show or muck, mucking for them once the showdown
+    /// timeout runs out
+    async fn prompt_show_or_muck(&mut self, player: Player) {
+        let prompt_msg =
+            PokerMessage::show_or_muck(self.id().clone(), player.id.clone(), *SHOWDOWN_TIMEOUT);
+        let _ = self.publish(prompt_msg);
+        self.run_timer(
+            Duration::from_secs(*SHOWDOWN_TIMEOUT),
+            move |room| async move {
+                let _ = room.muck_cards(player).await;
+            },
+        )
+        .await;
+    }
+
+    async fn show_hand(&mut self, idx: usize) {
+        let showdown = self.showdown.as_mut().expect("Showdown should exist");
+        showdown.pending.retain(|pending| *pending != idx);
+        showdown.shown.push(idx);
+
+        let player = self.table.game().expect("Game should exist").players[idx]
+            .info
+            .clone();
+        self.journal(JournalEntry::Showed { player }).await;
+        let game = self.table.game().expect("Game should exist");
+        let description = game
+            .ranks
+            .iter()
+            .find(|(_, ranked)| *ranked == idx)
+            .map(|(rank, _)| describe_rank(rank));
+        let shown_msg = PokerMessage::cards_shown(
+            self.id().clone(),
+            game.players[idx].info.id.clone(),
+            game.state.hands[idx][..].to_vec(),
+            description,
+        );
+        let _ = self.publish(shown_msg);
+    }
+
+    async fn muck_hand(&mut self, idx: usize) {
+        let showdown = self.showdown.as_mut().expect("Showdown should exist");
+        showdown.pending.retain(|pending| *pending != idx);
+
+        let game = self.table.game().expect("Game should exist");
+        let player = game.players[idx].info.clone();
+        let mucked_msg = PokerMessage::cards_mucked(self.id().clone(), player.id.clone());
+        self.journal(JournalEntry::Mucked { player }).await;
+        let _ = self.publish(mucked_msg);
+    }
+
+    async fn handle_show_cards(&mut self, player: Player, cards: Vec<Card>) -> Result<()> {
+        if let Some(showdown) = &self.showdown {
+            let game = self.table.game().expect("Game should exist");
+            let idx = game.player_idx(&player);
+            if idx.is_none() || showdown.pending.front() != idx.as_ref() {
+                bail!("Not your turn to show")
+            }
+            let idx = idx.unwrap();
+            let hand = &game.state.hands[idx];
+            if cards.len() != hand.len() || !cards.iter().all(|c| hand.iter().any(|h| h == c)) {
+                bail!("Must show your whole hand at showdown")
+            }
+
+            self.cancel_timer().await;
+            self.show_hand(idx).await;
+            self.advance_showdown().await;
+            return Ok(());
+        }
+
+        match &self.fold_winner {
+            Some((winner, hand)) if winner.id == player.id => {
+                let mut unique = cards.clone();
+                unique.sort();
+                unique.dedup();
+                if cards.is_empty()
+                    || unique.len() != cards.len()
+                    || !cards.iter().all(|c| hand.iter().any(|h| h == c))
+                {
+                    bail!("Can only show cards from your own hand")
+                }
+                let shown_msg =
+                    PokerMessage::cards_shown(self.id().clone(), player.id, cards, None);
+                let _ = self.publish(shown_msg);
+                self.end_fold_win().await;
+                Ok(())
+            }
+            _ => bail!("No cards to show"),
+        }
+    }
+
+    async fn handle_muck_cards(&mut self, player: Player) -> Result<()> {
+        if let Some((winner, _)) = &self.fold_winner {
+            if winner.id != player.id {
+                bail!("Not your turn to muck")
+            }
+            let mucked_msg = PokerMessage::cards_mucked(self.id().clone(), player.id);
+            let _ = self.publish(mucked_msg);
+            self.end_fold_win().await;
+            return Ok(());
+        }
+        let Some(showdown) = &self.showdown else {
+            bail!("No cards to muck")
+        };
+        let game = self.table.game().expect("Game should exist");
+        let idx = game.player_idx(&player);
+        if idx.is_none() || showdown.pending.front() != idx.as_ref() {
+            bail!("Not your turn to muck")
+        }
+
+        self.cancel_timer().await;
+        self.muck_hand(idx.unwrap()).await;
+        self.advance_showdown().await;
+        Ok(())
+    }
+
+    /// Lets the next hand be dealt once the winner of an uncalled pot has
+    /// shown or mucked
+    async fn end_fold_win(&mut self) {
+        self.cancel_timer().await;
+        self.fold_winner = None;
+        let _ = self.try_start_new_game().await;
+    }
+
+    /// Announces the result, settles stacks and tries to start the next game
+    async fn finish_game(&mut self, shown: Vec<usize>) {
+        let history = self.table.game().map(|game| {
+            let winner_msg = PokerMessage::declare_winner(self.id().clone(), game, &shown);
+            if let Err(e) = self.publish(winner_msg) {
+                error!(err = ?e, "Error broadcasting winners");
+            }
+            HandHistory::new(&self.table.config, game, &shown)
+        });
+
+        let completed = self.table.complete_game();
+        // The hand is recorded, stacks settled and chips leaving the table
+        // credited together, before anyone is told
+        let refunds = completed
+            .top_ups
+            .iter()
+            .filter(|top_up| top_up.refunded > 0)
+            .map(|top_up| (top_up.player.id.clone(), top_up.refunded));
+        let cashed_out = completed
+            .vacated
+            .iter()
+            .map(|(_, table_player)| (table_player.info.id.clone(), table_player.stack()));
+        let credits = refunds.chain(cashed_out).collect();
+        let snapshot = JournalChange::Snapshot(TableSnapshot::new(&self.table));
+        self.settle(snapshot, history, credits).await;
+        for top_up in completed.top_ups {
+            let topped_up_msg = PokerMessage::topped_up(
+                self.id().clone(),
+                top_up.player.id.clone(),
+                top_up.seat,
+                top_up.stack,
+            );
+            if let Err(e) = self.publish(topped_up_msg) {
+                error!(err = ?e, "Error broadcasting top-up");
+            }
+            if top_up.refunded > 0 {
+                let refund_msg = PokerMessage::top_up_refunded(self.id().clone(), top_up.refunded);
+                self.notify_credited(&top_up.player.id, refund_msg).await;
+            }
+        }
+        for (seat, table_player) in completed.vacated {
+            debug!(player = ?table_player.info, "Player stood up");
+            self.announce_vacated(seat, table_player).await;
+        }
+
+        if self.closing {
+            self.close_now().await;
+            return;
+        }
+
+        // Try starting a new game
+        // This fails if not enough players for the next game
+        let _ = self.try_start_new_game().await;
+    }
+
+    /// Adds a change already made to the table to its journal, or starts the
+    /// journal over from the table as it is now if it hasn't got one
+    async fn journal(&mut self, entry: JournalEntry) {
+        if !self.journaled {
+            self.save_snapshot(TableSnapshot::new(&self.table)).await;
+            return;
+        }
+        if let Err(e) = self.storage.append_journal(self.id().clone(), entry).await {
+            error!(err = ?e, "Error journaling table");
+        }
+    }
+
+    async fn save_snapshot(&mut self, snapshot: TableSnapshot) {
+        match self.storage.save_snapshot(snapshot).await {
+            Ok(()) => self.journaled = true,
+            Err(e) => error!(err = ?e, "Error saving table snapshot"),
+        }
+    }
+
+    /// Saves a journal change along with the chips it takes off the table,
+    /// so the chips are always either at the table or in their owners'
+    /// balances
+    async fn settle(
+        &mut self,
+        change: JournalChange,
+        history: Option<HandHistory>,
+        credits: Vec<(PlayerId, ChipInt)>,
+    ) {
+        // Without a journal to add to, it starts over from the table as it is
+        let change = match change {
+            JournalChange::Entry(..) if !self.journaled => {
+                JournalChange::Snapshot(TableSnapshot::new(&self.table))
+            }
+            change => change,
+        };
+        let snapshot = matches!(change, JournalChange::Snapshot(_));
+        let settlement = Settlement {
+            change,
+            history,
+            credits,
+        };
+        match self.storage.settle(settlement).await {
+            Ok(()) if snapshot => self.journaled = true,
+            Ok(()) => {}
+            Err(e) => error!(err = ?e, "Error settling chips leaving the table"),
+        }
+    }
+
+    /// Tells the player about chips already added to their stored balance.
+    /// The chips aren't lost if the message never arrives.
+    async fn notify_credited(&self, id: &PlayerId, msg: PokerMessage) {
+        if let Err(e) = self.send_to_player(id, msg).await {
+            debug!(err = ?e, "Player wasn't told chips were returned");
+        }
+    }
+
+    async fn send_to_player(&self, id: &PlayerId, msg: PokerMessage) -> Result<()> {
+        self.player_registry
+            .get(id.clone())
+            .await
+            .ok_or(eyre!("Player connection closed"))
+            .map(|p| p.send_message(msg))?
+    }
+
+    async fn run_turn_timer(&mut self, player: Player) {
+        self.start_turn_timer(player, TurnTimer::Turn, *TURN_TIMEOUT)
+            .await;
+    }
+
+    /// Times the player's turn, telling everyone when the timer runs out
+    async fn start_turn_timer(&mut self, player: Player, timer: TurnTimer, secs: u64) {
+        let expires_at = SystemTime::now() + Duration::from_secs(secs);
+        let expires_at = expires_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let timer_msg = PokerMessage::timer_started(
+            self.id().clone(),
+            player.id.clone(),
+            timer,
+            secs,
+            expires_at,
+        );
+        let _ = self.publish(timer_msg);
+
+        let expired = player.clone();
+        self.run_timer(Duration::from_secs(secs), move |room| async move {
+            let _ = room.timer_expired(expired).await;
+        })
+        .await;
+        self.turn_clock = Some(TurnClock {
+            player,
+            timer,
+            started: Instant::now(),
+        });
+    }
+
+    /// Moves a turn on to the player's time bank, then to some grace if they
+    /// are disconnected, and finally checks or folds for them
+    async fn handle_timer_expired(&mut self, player: Player) -> Result<()> {
+        let Some(clock) = self
+            .turn_clock
+            .as_ref()
+            .filter(|clock| clock.player.id == player.id)
+        else {
+            bail!("Timer was cancelled")
+        };
+        // The player acted and started a new turn as the old timer went off
+        if self
+            .timer_deadline
+            .is_none_or(|deadline| Instant::now() < deadline)
+        {
+            bail!("Timer was replaced")
+        }
+
+        let timer = clock.timer;
+        let expired_msg = PokerMessage::timer_expired(self.id().clone(), player.id.clone(), timer);
+        let _ = self.publish(expired_msg);
+
+        let (time_bank, connected) = self
+            .table
+            .players()
+            .find(|p| p.info.id == player.id)
+            .map_or((0, true), |p| (p.time_bank, p.connected));
+        match timer {
+            TurnTimer::Turn if time_bank > 0 => {
+                self.start_turn_timer(player, TurnTimer::TimeBank, time_bank)
+                    .await;
+                return Ok(());
+            }
+            TurnTimer::Turn | TurnTimer::TimeBank if !connected && *DISCONNECT_GRACE > 0 => {
+                self.start_turn_timer(player, TurnTimer::DisconnectGrace, *DISCONNECT_GRACE)
+                    .await;
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let action = self
+            .table
+            .game()
+            .filter(|game| !game.is_over())
+            .map(Game::timeout_action)
+            .ok_or_else(|| eyre!("Game is not active"))?;
+        self.handle_action(player, action).await
+    }
+
+    /// Runs `on_expiry` against this room's handle unless the timer is cancelled
+    /// or replaced first
+    async fn run_timer<F, Fut>(&mut self, duration: Duration, on_expiry: F)
+    where
+        F: FnOnce(RoomHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        // Cancel previous timer if exists
+        self.cancel_timer().await;
+        let (cancel_tx, mut cancel_rx) = mpsc::channel::<()>(1);
+        self.turn_timer_cancel = Some(cancel_tx);
+        self.timer_deadline = Some(Instant::now() + duration);
 
         // Get self-referential handle for the timer callback
         let self_handle = self
@@ -426,16 +1582,51 @@ impl Room {
         tokio::spawn(async move {
             debug!("Timer running!");
             tokio::select! {
-                _ = sleep(duration) => {
-                    // Time's up. Send 'fold' message to Room actor.
-                    let _ = self_handle.fold(player).await;
-                },
+                _ = sleep(duration) => on_expiry(self_handle).await,
                 _ = cancel_rx.recv() => {
                     // Timer was cancelled, do nothing.
                 },
             }
         });
     }
+
+    async fn cancel_timer(&mut self) {
+        if let Some(cancel) = self.turn_timer_cancel.take() {
+            let _ = cancel.try_send(());
+        }
+        self.timer_deadline = None;
+        // Time taken after the turn timer ran out comes out of the time bank
+        if let Some(clock) = self.turn_clock.take() {
+            if clock.timer == TurnTimer::TimeBank {
+                let secs = clock.started.elapsed().as_secs_f64().ceil() as u64;
+                if self.table.use_time_bank(&clock.player, secs).is_ok() {
+                    self.journal(JournalEntry::UsedTimeBank {
+                        player: clock.player,
+                        secs,
+                    })
+                    .await;
+                }
+            }
+        }
+    }
+}
+
+/// Sends spectators their broadcasts once they are due, in order. Anyone who
+/// sat down in the meantime is left out, as they are already following the
+/// table live.
+async fn delay_broadcasts(
+    mut delayed: mpsc::UnboundedReceiver<(Instant, RoomBroadcast)>,
+    broadcast: broadcast::Sender<RoomBroadcast>,
+    seated: watch::Receiver<Arc<HashSet<PlayerId>>>,
+) {
+    while let Some((due, mut msg)) = delayed.recv().await {
+        sleep_until(due).await;
+        if let Audience::Except(players) = &msg.audience {
+            let players = players.union(&seated.borrow()).cloned().collect();
+            msg.audience = Audience::Except(Arc::new(players));
+        }
+        let _ = broadcast.send(msg);
+    }
 }
 
 async fn run(mut room: Room) {
@@ -443,3 +1634,705 @@ async fn run(mut room: Room) {
         room.handle_message(msg).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use test_log::test;
+
+    struct TestRoom {
+        room: RoomHandle,
+        players: RegistryHandle<PlayerId, PlayerHandle>,
+        rooms: RegistryHandle<RoomId, RoomHandle>,
+        storage: StorageHandle,
+    }
+
+    impl TestRoom {
+        async fn open(config: TableConfig) -> Self {
+            Self::with_storage(config, StorageHandle::in_memory()).await
+        }
+
+        async fn with_storage(config: TableConfig, storage: StorageHandle) -> Self {
+            Self::with_table(Table::from_config(config), storage).await
+        }
+
+        async fn with_table(table: Table, storage: StorageHandle) -> Self {
+            let players = RegistryHandle::new();
+            let rooms = RegistryHandle::new();
+            let room =
+                RoomHandle::new(table, None, players.clone(), rooms.clone(), storage.clone());
+            rooms.set(room.id.clone(), room.clone()).await;
+            Self {
+                room,
+                players,
+                rooms,
+                storage,
+            }
+        }
+
+        fn player_handle(&self, player: &Player) -> PlayerHandle {
+            PlayerHandle::new(
+                player.clone(),
+                self.players.clone(),
+                self.rooms.clone(),
+                self.storage.clone(),
+            )
+        }
+
+        /// Connects a player, returning the socket they are sent messages on
+        async fn connect(&self, id: &str) -> (Player, mpsc::Receiver<PokerMessage>) {
+            let player = Player::new(id.to_owned(), id.to_owned());
+            let handle = self.player_handle(&player);
+            let (socket, received) = mpsc::channel(64);
+            handle.connect(socket).await.unwrap();
+            self.players.set(player.id.clone(), handle).await;
+            (player, received)
+        }
+
+        /// Collects what `player` would be sent until `count` hands are won
+        async fn watch(
+            &self,
+            player: &str,
+            count: usize,
+        ) -> tokio::task::JoinHandle<Vec<PokerMessage>> {
+            let player = player.to_owned();
+            let mut feed = self.room.subscribe(player.clone()).await;
+            tokio::spawn(async move {
+                let mut received = vec![];
+                let mut won = 0;
+                while won < count {
+                    let broadcast = feed.recv().await.expect("Feed should keep up");
+                    if !broadcast.is_for(&player) {
+                        continue;
+                    }
+                    if let PokerMessage::Server(Either::Room(RoomMessage {
+                        payload: ServerRoomPayload::DeclareWinner { .. },
+                        ..
+                    })) = &broadcast.msg
+                    {
+                        won += 1;
+                    }
+                    received.push(broadcast.msg);
+                }
+                received
+            })
+        }
+
+        /// Has whoever's turn it is check, or call if they can't
+        async fn check_or_call(&self, players: &[Player]) {
+            for player in players {
+                if self.room.check(player.clone()).await.is_ok()
+                    || self.room.call(player.clone()).await.is_ok()
+                {
+                    return;
+                }
+            }
+            panic!("Nobody could check or call");
+        }
+
+        async fn fold(&self, players: &[Player]) {
+            for player in players {
+                if self.room.fold(player.clone()).await.is_ok() {
+                    return;
+                }
+            }
+            panic!("Nobody could fold");
+        }
+    }
+
+    async fn receive_hand(socket: &mut mpsc::Receiver<PokerMessage>) -> Hand {
+        loop {
+            if let PokerMessage::Server(Either::Room(RoomMessage {
+                payload: ServerRoomPayload::DealHand(hand),
+                ..
+            })) = socket.recv().await.unwrap()
+            {
+                return hand;
+            }
+        }
+    }
+
+    fn contains(value: &Value, needle: &Value) -> bool {
+        value == needle
+            || match value {
+                Value::Array(values) => values.iter().any(|v| contains(v, needle)),
+                Value::Object(fields) => fields.values().any(|v| contains(v, needle)),
+                _ => false,
+            }
+    }
+
+    fn payload(msg: &PokerMessage) -> &ServerRoomPayload {
+        match msg {
+            PokerMessage::Server(Either::Room(RoomMessage { payload, .. })) => payload,
+            msg => panic!("Expected a room message, received {:?}", msg),
+        }
+    }
+
+    #[test(tokio::test)]
+    async fn test_spectators_never_see_unshown_hole_cards() {
+        let test = TestRoom::open(Table::default().config).await;
+        let spectator = test.watch("spectator", 2).await;
+        let (alice, mut alice_socket) = test.connect("alice").await;
+        let (bob, mut bob_socket) = test.connect("bob").await;
+        let players = [alice.clone(), bob.clone()];
+        test.room.sit_table(alice, 100, None).await.unwrap();
+        test.room.sit_table(bob, 100, None).await.unwrap();
+
+        // Checked down to showdown, where the loser mucks
+        let first = [
+            ("alice", receive_hand(&mut alice_socket).await),
+            ("bob", receive_hand(&mut bob_socket).await),
+        ];
+        for _ in 0..8 {
+            test.check_or_call(&players).await;
+        }
+        // Folded before the flop, so nobody shows
+        let second = [
+            ("alice", receive_hand(&mut alice_socket).await),
+            ("bob", receive_hand(&mut bob_socket).await),
+        ];
+        test.fold(&players).await;
+
+        let received = spectator.await.unwrap();
+        let hands: Vec<_> = received
+            .split_inclusive(|msg| matches!(payload(msg), ServerRoomPayload::DeclareWinner { .. }))
+            .collect();
+        assert_eq!(hands.len(), 2);
+        for (msgs, dealt) in hands.iter().zip([first, second]) {
+            let mut shown = HashSet::new();
+            for msg in msgs.iter() {
+                if let ServerRoomPayload::CardsShown { player, .. } = payload(msg) {
+                    shown.insert(player.clone());
+                }
+                let value = serde_json::to_value(msg).unwrap();
+                for (player, hand) in &dealt {
+                    if shown.contains(*player) {
+                        continue;
+                    }
+                    for card in hand.iter() {
+                        let card = serde_json::to_value(card).unwrap();
+                        assert!(
+                            !contains(&value, &card),
+                            "{}'s unshown cards were broadcast in {:?}",
+                            player,
+                            msg
+                        );
+                    }
+                }
+            }
+        }
+        assert!(!hands[0].is_empty());
+        assert!(hands[1]
+            .iter()
+            .all(|msg| !matches!(payload(msg), ServerRoomPayload::CardsShown { .. })));
+    }
+
+    /// Waits for the next hand shown or mucked, player asked to show or
+    /// muck, or the winner being declared
+    async fn next_showdown_event(
+        feed: &mut broadcast::Receiver<RoomBroadcast>,
+    ) -> ServerRoomPayload {
+        loop {
+            let broadcast = feed.recv().await.expect("Feed should keep up");
+            let payload = payload(&broadcast.msg);
+            if matches!(
+                payload,
+                ServerRoomPayload::CardsShown { .. }
+                    | ServerRoomPayload::CardsMucked { .. }
+                    | ServerRoomPayload::ShowOrMuck { .. }
+                    | ServerRoomPayload::DeclareWinner { .. }
+            ) {
+                return payload.clone();
+            }
+        }
+    }
+
+    /// Plays a stacked hand between three players who don't auto-muck to
+    /// the end of the river, where alice bets and is called
+    async fn river_showdown() -> (TestRoom, broadcast::Receiver<RoomBroadcast>, [Player; 3]) {
+        let mut table = Table::default();
+        // Alice is dealt aces, and the board pairs kings for everyone else
+        let stacked = [
+            "As", "7c", "8c", "Ah", "2d", "3d", "Ks", "Kh", "9d", "5c", "4s",
+        ]
+        .map(|card| Card::try_from(card).unwrap());
+        table
+            .decks
+            .push_back(DeckSource::stacked(stacked.to_vec()).unwrap());
+        let [alice, bob, carol] =
+            ["alice", "bob", "carol"].map(|name| Player::new(name.to_owned(), name.to_owned()));
+        for (seat, player) in [&alice, &bob].into_iter().enumerate() {
+            table.sit_player(player.clone(), 100, Some(seat)).unwrap();
+            table.set_auto_muck(player, false).unwrap();
+        }
+        let test = TestRoom::with_table(table, StorageHandle::in_memory()).await;
+        let feed = test.room.subscribe("spectator".to_owned()).await;
+        test.room
+            .sit_table(carol.clone(), 100, Some(2))
+            .await
+            .unwrap();
+        test.room.auto_muck(carol.clone(), false).await.unwrap();
+
+        // Checked to the river, where alice on the button bets last
+        for _ in 0..9 {
+            test.check_or_call(&[alice.clone(), bob.clone(), carol.clone()])
+                .await;
+        }
+        let blinds = [bob.clone(), carol.clone()];
+        for _ in 0..2 {
+            test.check_or_call(&blinds).await;
+        }
+        test.room.bet(alice.clone(), 10).await.unwrap();
+        for _ in 0..2 {
+            test.check_or_call(&blinds).await;
+        }
+        (test, feed, [alice, bob, carol])
+    }
+
+    #[test(tokio::test)]
+    async fn test_show_or_muck_in_showdown_order() {
+        let (test, mut feed, [_, bob, carol]) = river_showdown().await;
+
+        // The last aggressor shows first, then the others are asked in turn
+        let shown = next_showdown_event(&mut feed).await;
+        assert!(matches!(shown, ServerRoomPayload::CardsShown { player, .. } if player == "alice"));
+        let asked = next_showdown_event(&mut feed).await;
+        assert!(matches!(asked, ServerRoomPayload::ShowOrMuck { player, .. } if player == "bob"));
+        assert!(test.room.muck_cards(carol.clone()).await.is_err());
+        test.room.muck_cards(bob.clone()).await.unwrap();
+        let mucked = next_showdown_event(&mut feed).await;
+        assert!(matches!(mucked, ServerRoomPayload::CardsMucked { player } if player == "bob"));
+        let asked = next_showdown_event(&mut feed).await;
+        assert!(matches!(asked, ServerRoomPayload::ShowOrMuck { player, .. } if player == "carol"));
+
+        // Carol lets the showdown timer run out, which mucks for her
+        tokio::time::pause();
+        tokio::time::advance(Duration::from_secs(*SHOWDOWN_TIMEOUT + 1)).await;
+        tokio::time::resume();
+        let mucked = next_showdown_event(&mut feed).await;
+        assert!(matches!(mucked, ServerRoomPayload::CardsMucked { player } if player == "carol"));
+        let declared = next_showdown_event(&mut feed).await;
+        assert!(
+            matches!(declared, ServerRoomPayload::DeclareWinner { hands, .. } if hands.len() == 1)
+        );
+        assert!(test.room.muck_cards(carol).await.is_err());
+    }
+
+    #[test(tokio::test)]
+    async fn test_recovering_mid_showdown() {
+        let (test, mut feed, [_, bob, carol]) = river_showdown().await;
+        let shown = next_showdown_event(&mut feed).await;
+        assert!(matches!(shown, ServerRoomPayload::CardsShown { player, .. } if player == "alice"));
+        next_showdown_event(&mut feed).await;
+        test.room.muck_cards(bob).await.unwrap();
+        let mucked = next_showdown_event(&mut feed).await;
+        assert!(matches!(mucked, ServerRoomPayload::CardsMucked { player } if player == "bob"));
+        next_showdown_event(&mut feed).await;
+
+        // The server stops while carol decides, and the new room asks her again
+        let mut journals = test.storage.load_journals().await.unwrap();
+        let restarted = TestRoom::with_storage(Table::default().config, test.storage.clone()).await;
+        let mut feed = restarted.room.subscribe("spectator".to_owned()).await;
+        restarted
+            .room
+            .recover(journals.remove(0), HandRecovery::Resume)
+            .await
+            .unwrap();
+        let asked = next_showdown_event(&mut feed).await;
+        assert!(matches!(asked, ServerRoomPayload::ShowOrMuck { player, .. } if player == "carol"));
+
+        let hand = table_state(&restarted.room, &carol).await.hand.unwrap();
+        restarted
+            .room
+            .show_cards(carol, hand[..].to_vec())
+            .await
+            .unwrap();
+        let shown = next_showdown_event(&mut feed).await;
+        assert!(matches!(shown, ServerRoomPayload::CardsShown { player, .. } if player == "carol"));
+        // Alice's hand from before the restart is still among those shown
+        let declared = next_showdown_event(&mut feed).await;
+        assert!(
+            matches!(declared, ServerRoomPayload::DeclareWinner { hands, .. } if hands.len() == 2)
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn test_fold_winner_shows_before_the_next_deal() {
+        let test = TestRoom::open(Table::default().config).await;
+        let mut feed = test.room.subscribe("spectator".to_owned()).await;
+        let (alice, mut alice_socket) = test.connect("alice").await;
+        let (bob, mut bob_socket) = test.connect("bob").await;
+        let players = [alice.clone(), bob.clone()];
+        test.room.sit_table(alice.clone(), 100, None).await.unwrap();
+        test.room.sit_table(bob.clone(), 100, None).await.unwrap();
+        for player in &players {
+            test.room.auto_muck(player.clone(), false).await.unwrap();
+        }
+        receive_hand(&mut alice_socket).await;
+        receive_hand(&mut bob_socket).await;
+        test.fold(&players).await;
+
+        // Nothing is dealt until the winner decides
+        let declared = next_showdown_event(&mut feed).await;
+        let ServerRoomPayload::DeclareWinner { pots, .. } = declared else {
+            panic!(
+                "Expected the winner to be declared, received {:?}",
+                declared
+            );
+        };
+        let winner = pots[0].winners[0].player.clone();
+        let asked = next_showdown_event(&mut feed).await;
+        assert!(matches!(asked, ServerRoomPayload::ShowOrMuck { player, .. } if player == winner));
+        let (winner, loser) = if winner == alice.id {
+            (alice, bob)
+        } else {
+            (bob, alice)
+        };
+        assert!(test.room.muck_cards(loser).await.is_err());
+        assert!(alice_socket.try_recv().is_err() && bob_socket.try_recv().is_err());
+
+        test.room.muck_cards(winner.clone()).await.unwrap();
+        let mucked = next_showdown_event(&mut feed).await;
+        assert!(matches!(mucked, ServerRoomPayload::CardsMucked { player } if player == winner.id));
+        let hand = receive_hand(&mut alice_socket).await;
+        receive_hand(&mut bob_socket).await;
+        // The last hand's cards can't be shown once the next is dealt
+        assert!(test
+            .room
+            .show_cards(winner, hand[..1].to_vec())
+            .await
+            .is_err());
+    }
+
+    #[test(tokio::test)]
+    async fn test_fold_winner_leaving_does_not_hold_the_deal() {
+        let mut table = Table::default();
+        let [alice, bob, carol] =
+            ["alice", "bob", "carol"].map(|name| Player::new(name.to_owned(), name.to_owned()));
+        for (seat, player) in [&alice, &bob].into_iter().enumerate() {
+            table.sit_player(player.clone(), 100, Some(seat)).unwrap();
+            table.set_auto_muck(player, false).unwrap();
+        }
+        let test = TestRoom::with_table(table, StorageHandle::in_memory()).await;
+        let mut feed = test.room.subscribe("spectator".to_owned()).await;
+        test.room
+            .sit_table(carol.clone(), 100, Some(2))
+            .await
+            .unwrap();
+        test.room.auto_muck(carol.clone(), false).await.unwrap();
+
+        let players = [alice, bob, carol];
+        for _ in 0..2 {
+            test.fold(&players).await;
+        }
+        let declared = next_showdown_event(&mut feed).await;
+        let ServerRoomPayload::DeclareWinner { pots, .. } = declared else {
+            panic!(
+                "Expected the winner to be declared, received {:?}",
+                declared
+            );
+        };
+        let winner = pots[0].winners[0].player.clone();
+        let asked = next_showdown_event(&mut feed).await;
+        assert!(matches!(asked, ServerRoomPayload::ShowOrMuck { player, .. } if player == winner));
+
+        // The winner leaves instead of deciding
+        let winner = players.into_iter().find(|p| p.id == winner).unwrap();
+        test.room.stand_up(winner).await.unwrap();
+        // The next hand is dealt without waiting out the showdown timer
+        let next_deal = async {
+            loop {
+                let broadcast = feed.recv().await.expect("Feed should keep up");
+                if matches!(payload(&broadcast.msg), ServerRoomPayload::NewGame(_)) {
+                    return;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), next_deal)
+            .await
+            .expect("Next hand should be dealt straight away");
+    }
+
+    #[test(tokio::test)]
+    async fn test_stacked_deck_decides_the_showdown() {
+        let mut table = Table::default();
+        let stacked = ["Qh", "As", "Jh", "Kd", "Ah", "7h", "Kc", "2d", "3h"]
+            .map(|card| Card::try_from(card).unwrap());
+        table
+            .decks
+            .push_back(DeckSource::stacked(stacked.to_vec()).unwrap());
+        let test = TestRoom::with_table(table, StorageHandle::in_memory()).await;
+        let spectator = test.watch("spectator", 1).await;
+        let (alice, mut alice_socket) = test.connect("alice").await;
+        let (bob, _bob_socket) = test.connect("bob").await;
+        let players = [alice.clone(), bob.clone()];
+        test.room.sit_table(alice, 100, None).await.unwrap();
+        test.room.sit_table(bob, 100, None).await.unwrap();
+
+        // Whoever is dealt the queen and jack of hearts makes a flush
+        let alice_hand = receive_hand(&mut alice_socket).await;
+        let winner = if alice_hand.iter().any(|card| card == &stacked[0]) {
+            "alice"
+        } else {
+            "bob"
+        };
+        for _ in 0..8 {
+            test.check_or_call(&players).await;
+        }
+
+        let received = spectator.await.unwrap();
+        let Some(ServerRoomPayload::DeclareWinner {
+            pots,
+            hands,
+            shuffle,
+        }) = received.last().map(payload)
+        else {
+            panic!("Expected the winner to be declared");
+        };
+        assert_eq!(pots[0].winners.len(), 1);
+        assert_eq!(pots[0].winners[0].player, winner);
+        let shown = hands.iter().find(|hand| hand.player == winner).unwrap();
+        assert_eq!(shown.description, "Flush, Ace high");
+        assert!(shuffle.is_none());
+    }
+
+    #[test(tokio::test(start_paused = true))]
+    async fn test_spectator_delay() {
+        let test = TestRoom::open(TableConfig {
+            spectator_delay: 5,
+            ..Table::default().config
+        })
+        .await;
+        let alice = Player::new("alice".to_owned(), "alice".to_owned());
+        let bob = Player::new("bob".to_owned(), "bob".to_owned());
+        let spectator = Player::new("spectator".to_owned(), "spectator".to_owned());
+        let mut spectator_feed = test.room.subscribe(spectator.id.clone()).await;
+        let mut alice_feed = test.room.subscribe(alice.id.clone()).await;
+        test.room.sit_table(alice.clone(), 100, None).await.unwrap();
+        test.room.sit_table(bob, 100, None).await.unwrap();
+        assert_eq!(test.room.get_table().await.spectators, 1);
+
+        let start = Instant::now();
+        let is_new_game = |broadcast: &RoomBroadcast| {
+            matches!(payload(&broadcast.msg), ServerRoomPayload::NewGame(_))
+        };
+        loop {
+            let broadcast = alice_feed.recv().await.unwrap();
+            if broadcast.is_for(&alice.id) && is_new_game(&broadcast) {
+                break;
+            }
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // Spectators can't catch up with the game by resyncing either
+        let TableState { game, .. } = table_state(&test.room, &spectator).await;
+        assert!(game.is_none());
+        let TableState { game, .. } = table_state(&test.room, &alice).await;
+        assert!(game.is_some());
+
+        loop {
+            let broadcast = spectator_feed.recv().await.unwrap();
+            if broadcast.is_for(&spectator.id) && is_new_game(&broadcast) {
+                break;
+            }
+        }
+        assert!(start.elapsed() >= Duration::from_secs(5));
+    }
+
+    #[test(tokio::test(start_paused = true))]
+    async fn test_sitting_during_spectator_delay() {
+        let test = TestRoom::open(TableConfig {
+            spectator_delay: 5,
+            ..Table::default().config
+        })
+        .await;
+        let alice = Player::new("alice".to_owned(), "alice".to_owned());
+        let bob = Player::new("bob".to_owned(), "bob".to_owned());
+        let carol = Player::new("carol".to_owned(), "carol".to_owned());
+        let mut carol_feed = test.room.subscribe(carol.id.clone()).await;
+        test.room.sit_table(alice, 100, None).await.unwrap();
+        test.room.sit_table(bob, 100, None).await.unwrap();
+        // Carol sits down while the start of the hand is held back from her
+        test.room.sit_table(carol.clone(), 100, None).await.unwrap();
+        sleep(Duration::from_secs(6)).await;
+
+        let mut received = vec![];
+        while let Ok(broadcast) = carol_feed.try_recv() {
+            if broadcast.is_for(&carol.id) {
+                received.push(payload(&broadcast.msg).clone());
+            }
+        }
+        // Only what happened after she sat, once, and nothing from before
+        assert!(matches!(
+            received.as_slice(),
+            [ServerRoomPayload::SitTable { player, .. }] if player.id == carol.id
+        ));
+    }
+
+    #[test(tokio::test)]
+    async fn test_lagged_subscriber_is_resynced() {
+        let test = TestRoom::open(TableConfig {
+            broadcast_capacity: 2,
+            ..Table::default().config
+        })
+        .await;
+        let alice = Player::new("alice".to_owned(), "alice".to_owned());
+        let handle = test.player_handle(&alice);
+        // A socket that only holds one message falls behind while it is full
+        let (socket, mut received) = mpsc::channel(1);
+        handle.connect(socket).await.unwrap();
+        handle
+            .send_message(PokerMessage::subscribe_room(test.room.id.clone()))
+            .unwrap();
+        assert!(matches!(
+            payload(&received.recv().await.unwrap()),
+            ServerRoomPayload::TableState(_)
+        ));
+
+        for i in 0..20 {
+            test.room
+                .send_chat_message(i.to_string(), "bob".to_owned())
+                .await
+                .unwrap();
+        }
+        let mut chats = 0;
+        loop {
+            match payload(&received.recv().await.unwrap()) {
+                ServerRoomPayload::Chat { .. } => chats += 1,
+                ServerRoomPayload::TableState(_) => break,
+                payload => panic!("Unexpected {:?}", payload),
+            }
+        }
+        assert!(chats < 20);
+
+        // Still subscribed, picking up from where the room is now
+        test.room
+            .send_chat_message("caught up".to_owned(), "bob".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(
+            received.recv().await.unwrap(),
+            PokerMessage::chat_broadcast(
+                test.room.id.clone(),
+                "bob".to_owned(),
+                "caught up".to_owned()
+            )
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn test_finished_hands_are_recorded() {
+        let test = TestRoom::open(Table::default().config).await;
+        let (alice, mut alice_socket) = test.connect("alice").await;
+        let (bob, mut bob_socket) = test.connect("bob").await;
+        let players = [alice.clone(), bob.clone()];
+        test.room.sit_table(alice.clone(), 100, None).await.unwrap();
+        test.room.sit_table(bob.clone(), 100, None).await.unwrap();
+
+        let alice_hand = receive_hand(&mut alice_socket).await;
+        receive_hand(&mut bob_socket).await;
+        for _ in 0..8 {
+            test.check_or_call(&players).await;
+        }
+        receive_hand(&mut alice_socket).await;
+        receive_hand(&mut bob_socket).await;
+        test.fold(&players).await;
+
+        let hands = test
+            .storage
+            .player_hands(alice.id.clone(), 10)
+            .await
+            .unwrap();
+        let ids: Vec<_> = hands.iter().map(|history| history.id).collect();
+        assert_eq!(ids, [2, 1]);
+        let (folded, checked_down) = (&hands[0], &hands[1]);
+
+        assert_eq!(checked_down.board.len(), 5);
+        assert!(!checked_down.shown.is_empty());
+        assert_eq!(checked_down.shown.len() + checked_down.mucked.len(), 2);
+        let seen = checked_down.seen_by(&alice.id);
+        let alice_seat = seen.seats.iter().find(|s| s.player.id == alice.id).unwrap();
+        assert_eq!(alice_seat.cards, alice_hand[..]);
+        for seat in &seen.seats {
+            let shown = checked_down.shown.iter().any(|s| s.seat == seat.seat);
+            assert_eq!(seat.cards.is_empty(), seat.player.id != alice.id && !shown);
+        }
+
+        assert!(folded.board.is_empty() && folded.shown.is_empty());
+        assert!(folded
+            .actions
+            .iter()
+            .any(|action| action.kind == HandActionKind::Fold));
+        let seen = folded.seen_by(&bob.id);
+        let alice_seat = seen.seats.iter().find(|s| s.player.id == alice.id).unwrap();
+        assert!(alice_seat.cards.is_empty());
+        assert!(folded.to_pokerstars().contains("*** SUMMARY ***"));
+    }
+
+    #[test(tokio::test)]
+    async fn test_returned_chips_reach_the_balance() {
+        let test = TestRoom::open(Table::default().config).await;
+        // Nobody is connected to tell, so only the stored balance can take
+        // the chips
+        let alice = Player::new("alice".to_owned(), "alice".to_owned());
+        test.storage.save_player(alice.clone(), 0).await.unwrap();
+        test.room.sit_table(alice.clone(), 100, None).await.unwrap();
+        test.room.stand_up(alice.clone()).await.unwrap();
+        let balance = test.storage.load_balance(alice.id.clone()).await.unwrap();
+        assert_eq!(balance, Some(100));
+    }
+
+    #[test(tokio::test)]
+    async fn test_recovering_hand_from_journal() {
+        for recovery in [HandRecovery::Resume, HandRecovery::Void] {
+            let config = Table::default().config;
+            let test = TestRoom::open(config.clone()).await;
+            let (alice, mut alice_socket) = test.connect("alice").await;
+            let (bob, _bob_socket) = test.connect("bob").await;
+            let players = [alice.clone(), bob.clone()];
+            test.room.sit_table(alice.clone(), 100, None).await.unwrap();
+            test.room.sit_table(bob.clone(), 100, None).await.unwrap();
+            let alice_hand = receive_hand(&mut alice_socket).await;
+            test.check_or_call(&players).await;
+
+            // The server stops, and a new room picks the table up from storage
+            let mut journals = test.storage.load_journals().await.unwrap();
+            assert_eq!(journals.len(), 1);
+            let restarted = TestRoom::with_storage(config.clone(), test.storage.clone()).await;
+            let seated = restarted
+                .room
+                .recover(journals.remove(0), recovery)
+                .await
+                .unwrap();
+            assert_eq!(seated, players);
+
+            let state = table_state(&restarted.room, &alice).await;
+            let stacks: Vec<_> = state.seats.iter().map(|seat| seat.stack).collect();
+            assert_eq!(stacks, [100, 100]);
+            assert!(state.seats.iter().all(|seat| !seat.connected));
+            let game = state.game.unwrap();
+            match recovery {
+                HandRecovery::Resume => {
+                    assert_eq!(state.hand, Some(alice_hand));
+                    assert_eq!(game.pot, 4);
+                    restarted.check_or_call(&players).await;
+                    let game = table_state(&restarted.room, &alice).await.game.unwrap();
+                    assert_eq!(game.community_cards.len(), 3);
+                }
+                // Everyone got their chips back and a new hand was dealt
+                HandRecovery::Void => assert_eq!(game.pot, 3),
+            }
+        }
+    }
+
+    async fn table_state(room: &RoomHandle, player: &Player) -> TableState {
+        match room.table_state(player.clone()).await {
+            PokerMessage::Server(Either::Room(RoomMessage {
+                payload: ServerRoomPayload::TableState(state),
+                ..
+            })) => *state,
+            msg => panic!("Expected table state, received {:?}", msg),
+        }
+    }
+}
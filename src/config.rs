@@ -0,0 +1,243 @@
+use crate::*;
+use eyre::WrapErr;
+use std::{collections::HashSet, env, fs, net::SocketAddr, path::Path};
+
+/// Server settings and the tables to open at startup, read from a TOML or
+/// JSON file given by `--config <path>` or `POKER_CONFIG`. Anything the file
+/// leaves out keeps its default.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address the HTTP and WebSocket server listens on
+    pub addr: String,
+    /// Capacity of actor mailboxes
+    pub channel_size: usize,
+    /// Seconds a player has to act before they are folded
+    pub turn_timeout: u64,
+    /// Seconds a player has to show or muck at showdown
+    pub showdown_timeout: u64,
+    /// Seconds a disconnected player keeps their seats before being stood up
+    pub reconnect_timeout: u64,
+    /// Extra seconds a disconnected player gets to come back once their turn
+    /// and time bank run out
+    pub disconnect_grace: u64,
+    /// Balance a new player starts with
+    pub default_chips: ChipInt,
+    /// SQLite database keeping players, balances, tables opened at runtime and
+    /// hand histories. Everything is lost on restart without one.
+    pub database: Option<String>,
+    /// What happens to hands the server stopped in the middle of: `resume`
+    /// plays them on from where they were, `void` calls them off
+    pub hand_recovery: HandRecovery,
+    /// Tables opened at startup. Tables without an id are given one.
+    pub tables: Vec<TableConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            addr: "0.0.0.0:8080".to_owned(),
+            channel_size: 8,
+            turn_timeout: 30,
+            showdown_timeout: 10,
+            reconnect_timeout: 60,
+            disconnect_grace: 20,
+            default_chips: 100,
+            database: None,
+            hand_recovery: HandRecovery::Resume,
+            tables: vec![Table::default().config],
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file named on the command line or in `POKER_CONFIG`,
+    /// or the defaults when there is neither
+    pub fn load() -> Result<Self> {
+        let mut args = env::args().skip(1);
+        let mut path = None;
+        while let Some(arg) = args.next() {
+            if arg == "--config" {
+                path = Some(args.next().ok_or_else(|| eyre!("--config needs a path"))?);
+            } else if let Some(value) = arg.strip_prefix("--config=") {
+                path = Some(value.to_owned());
+            }
+        }
+
+        match path.or_else(|| env::var("POKER_CONFIG").ok()) {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Couldn't read config file {}", path.display()))?;
+        let is_json = path.extension().is_some_and(|ext| ext == "json");
+        Self::parse(&contents, is_json)
+            .wrap_err_with(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Parses and validates a config, filling in missing table ids
+    pub fn parse(contents: &str, is_json: bool) -> Result<Self> {
+        let mut config: Self = if is_json {
+            serde_json::from_str(contents)?
+        } else {
+            toml::from_str(contents)?
+        };
+        for table in config.tables.iter_mut().filter(|table| table.id.is_empty()) {
+            table.id = hex::encode(rand::random::<[u8; 8]>());
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.addr
+            .parse::<SocketAddr>()
+            .map_err(|_| eyre!("Invalid listen address {}", self.addr))?;
+        if self.channel_size == 0 {
+            bail!("Channel size must be at least 1")
+        }
+        if self.turn_timeout == 0 || self.showdown_timeout == 0 {
+            bail!("Timeouts must be at least 1 second")
+        }
+
+        let mut ids = HashSet::new();
+        for (i, table) in self.tables.iter().enumerate() {
+            table
+                .validate()
+                .wrap_err_with(|| format!("Invalid table {} ({})", i + 1, table.name))?;
+            if !ids.insert(&table.id) {
+                bail!("Table id {} is used more than once", table.id)
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_parse_toml() {
+        let config = Config::parse(
+            r#"
+            addr = "127.0.0.1:9000"
+            turn_timeout = 15
+
+            [[tables]]
+            id = "high-stakes"
+            name = "High Stakes"
+            small_blind = 50
+            big_blind = 100
+
+            [[tables]]
+            name = "Short Handed"
+            max_players = 6
+
+            [[tables]]
+            name = "Limit"
+            betting = { structure = "fixedLimit", small_bet = 2, big_bet = 4, raise_cap = 3 }
+            ante = { kind = "classic", amount = 1 }
+            "#,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(config.addr, "127.0.0.1:9000");
+        assert_eq!(config.turn_timeout, 15);
+        assert_eq!(config.channel_size, Config::default().channel_size);
+        assert_eq!(config.tables.len(), 3);
+        assert_eq!(config.tables[0].id, "high-stakes");
+        assert_eq!(config.tables[0].big_blind, 100);
+        assert_eq!(config.tables[0].max_players, 9);
+        assert!(!config.tables[1].id.is_empty());
+        assert_eq!(config.tables[1].max_players, 6);
+        assert_eq!(
+            config.tables[2].betting_structure(),
+            BettingStructure::FixedLimit {
+                small_bet: 2,
+                big_bet: 4,
+                raise_cap: 3
+            }
+        );
+        assert_eq!(
+            config.tables[2].ante,
+            Some(Ante {
+                kind: AnteKind::Classic,
+                amount: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_example_config() {
+        let config = Config::from_file("poker.example.toml").unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let config = Config::parse(r#"{ "defaultChips": 500 }"#, true);
+        assert!(config.is_err(), "Config keys are snake case");
+
+        let config = Config::parse(r#"{ "default_chips": 500 }"#, true).unwrap();
+        assert_eq!(config.default_chips, 500);
+        assert_eq!(config.tables, Config::default().tables);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let err = Config::parse(r#"addr = "localhost""#, false).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid listen address localhost");
+
+        let err = Config::parse(
+            r#"
+            [[tables]]
+            name = "Backwards"
+            small_blind = 10
+            big_blind = 5
+            "#,
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid table 1 (Backwards)");
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Big blind must be at least the small blind, which can't be 0"
+        );
+
+        let err = Config::parse(
+            r#"
+            [[tables]]
+            id = "a"
+            name = "One"
+
+            [[tables]]
+            id = "a"
+            name = "Two"
+            "#,
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Table id a is used more than once");
+
+        let err = Config::parse(
+            r#"
+            [[tables]]
+            name = "Typo"
+            big_blnd = 10
+            "#,
+            false,
+        )
+        .unwrap_err();
+        assert!(err
+            .root_cause()
+            .to_string()
+            .contains("unknown field `big_blnd`"));
+    }
+}
@@ -8,17 +8,21 @@ use std::env::var;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod actors;
+mod config;
 mod messages;
 mod models;
 mod server;
+mod storage;
 
 pub use actors::*;
-pub use alloy_primitives::Address;
+pub use alloy_primitives::{Address, B256};
+pub use config::*;
 pub use eyre::{bail, eyre, Result};
 pub use messages::*;
 pub use models::*;
 pub use serde::{Deserialize, Serialize};
 pub use server::*;
+pub use storage::*;
 pub use tracing::{debug, error, info, span};
 
 lazy_static! {
@@ -27,10 +31,17 @@ lazy_static! {
     pub static ref COOKIE_SECRET: String =
         var("POKER_SESSION_SECRET").expect("Missing POKER_SESSION_SECRET");
     pub static ref ENVIRONMENT: String = var("RUST_ENV").expect("Missing RUST_ENV");
-    pub static ref ADDR: &'static str = "0.0.0.0:8080";
-    pub static ref DEFAULT_CHIPS: ChipInt = 100;
-    pub static ref CHANNEL_SIZE: usize = 8;
-    pub static ref TURN_TIMEOUT: u64 = 30;
+    pub static ref CONFIG: Config =
+        Config::load().unwrap_or_else(|err| panic!("Invalid configuration: {err:?}"));
+    pub static ref ADDR: String = CONFIG.addr.clone();
+    pub static ref DEFAULT_CHIPS: ChipInt = CONFIG.default_chips;
+    pub static ref CHANNEL_SIZE: usize = CONFIG.channel_size;
+    pub static ref TURN_TIMEOUT: u64 = CONFIG.turn_timeout;
+    pub static ref SHOWDOWN_TIMEOUT: u64 = CONFIG.showdown_timeout;
+    pub static ref RECONNECT_TIMEOUT: u64 = CONFIG.reconnect_timeout;
+    pub static ref DISCONNECT_GRACE: u64 = CONFIG.disconnect_grace;
+    /// Bearer token for the admin HTTP routes, which are disabled without it
+    pub static ref ADMIN_TOKEN: Option<String> = var("POKER_ADMIN_TOKEN").ok();
 }
 
 #[tokio::main]
@@ -40,6 +51,7 @@ async fn main() {
     let _ = COOKIE_NAME.clone();
     let _ = COOKIE_SECRET.clone();
     let _ = ENVIRONMENT.clone();
+    let _ = CONFIG.clone();
 
     // Tracing initialization
     tracing_subscriber::registry()
@@ -57,12 +69,14 @@ async fn main() {
 mod tests {
     use super::*;
     use futures::{sink::SinkExt, stream::StreamExt};
+    use rs_poker::core::{Card, Hand};
     use std::assert_matches::assert_matches;
     use test_log::test;
     use tokio::net::TcpStream;
     use tokio::task::JoinHandle;
     use tokio::time::Duration;
     use tokio_tungstenite::tungstenite::handshake::client::{generate_key, Request};
+    use tokio_tungstenite::tungstenite::Error as WsError;
     use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
     use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
     use tracing::debug;
@@ -83,37 +97,158 @@ mod tests {
 
     struct ClientConnection {
         data: Player,
+        session: Session,
+        url: String,
         ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+        /// Whether to read turn timer broadcasts, which are skipped otherwise
+        timers: bool,
     }
 
     impl ClientConnection {
         async fn setup_conn() -> Self {
-            let url = "ws://localhost:8080/ws";
             // Dev environment cookie
-            let session = Session::default();
+            Self::connect(Session::default()).await
+        }
+
+        async fn connect(session: Session) -> Self {
+            Self::connect_to("ws://localhost:8080/ws", session).await
+        }
+
+        async fn connect_to(url: &str, session: Session) -> Self {
             let mut cookie = COOKIE_NAME.clone();
             cookie.push('=');
             cookie.push_str(&session.to_cookie(&COOKIE_SECRET));
 
-            let req = Request::builder()
-                .uri(url)
-                .method("GET")
-                .header("Host", url)
-                .header("cookie", cookie)
-                .header("Connection", "Upgrade")
-                .header("Upgrade", "websocket")
-                .header("Sec-WebSocket-Version", "13")
-                .header("Sec-WebSocket-Key", generate_key())
-                .body(())
-                .unwrap();
-            let (ws_stream, _) = connect_async(req).await.expect("Failed to connect");
+            let request = || {
+                Request::builder()
+                    .uri(url)
+                    .method("GET")
+                    .header("Host", url)
+                    .header("cookie", &cookie)
+                    .header("Connection", "Upgrade")
+                    .header("Upgrade", "websocket")
+                    .header("Sec-WebSocket-Version", "13")
+                    .header("Sec-WebSocket-Key", generate_key())
+                    .body(())
+                    .unwrap()
+            };
+            // The server may still be starting up
+            let mut attempts = 0;
+            let ws_stream = loop {
+                match connect_async(request()).await {
+                    Ok((ws_stream, _)) => break ws_stream,
+                    Err(e) if attempts < 50 => {
+                        debug!(err = ?e, "Server not up yet, retrying");
+                        attempts += 1;
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                    }
+                    Err(e) => panic!("Failed to connect: {e:?}"),
+                }
+            };
             Self {
                 data: Player::new(session.address.to_string(), session.address.to_string()),
+                session,
+                url: url.to_owned(),
                 ws_stream,
+                timers: false,
+            }
+        }
+
+        async fn next_frame(&mut self) -> Option<Result<Message, WsError>> {
+            loop {
+                let frame = self.ws_stream.next().await;
+                if let Some(Ok(Message::Text(text))) = &frame {
+                    let msg = serde_json::from_str::<PokerMessage>(text).unwrap();
+                    let is_timer = matches!(
+                        msg,
+                        PokerMessage::Server(Either::Room(RoomMessage {
+                            payload: ServerRoomPayload::TimerStarted { .. }
+                                | ServerRoomPayload::TimerExpired { .. },
+                            ..
+                        }))
+                    );
+                    if is_timer && !self.timers {
+                        continue;
+                    }
+                }
+                return frame;
             }
         }
 
-        async fn get_tables(&mut self) -> Vec<TableConfig> {
+        async fn receive_timer_started(
+            &mut self,
+            room_id: &RoomId,
+            player: &Player,
+            timer: TurnTimer,
+        ) -> u64 {
+            if let Some(Ok(Message::Text(text))) = self.next_frame().await {
+                let msg = serde_json::from_str::<PokerMessage>(&text).unwrap();
+                match msg {
+                    PokerMessage::Server(Either::Room(RoomMessage {
+                        room_id: received_room_id,
+                        payload:
+                            ServerRoomPayload::TimerStarted {
+                                player: received_player,
+                                timer: received_timer,
+                                timeout,
+                                ..
+                            },
+                    })) if *room_id == received_room_id
+                        && player.id == received_player
+                        && timer == received_timer =>
+                    {
+                        timeout
+                    }
+                    msg => panic!("Expected timer started, received {:?}", msg),
+                }
+            } else {
+                panic!("Did not receive a reply");
+            }
+        }
+
+        /// Drops the connection without closing it, then connects again as the
+        /// same player
+        async fn reconnect(&mut self) {
+            self.ws_stream = Self::connect_to(&self.url, self.session.clone())
+                .await
+                .ws_stream;
+        }
+
+        async fn create_table(&mut self, config: TableConfig) -> TableListing {
+            let create_msg = PokerMessage::create_table(config);
+            let create_msg = serde_json::to_string(&create_msg).unwrap();
+
+            debug!("Sending create table message from client");
+            self.ws_stream
+                .send(Message::Text(create_msg))
+                .await
+                .expect("Failed to send message");
+
+            if let Some(Ok(Message::Text(msg))) = self.next_frame().await {
+                let msg = serde_json::from_str::<PokerMessage>(&msg).unwrap();
+                if let PokerMessage::Server(Either::Lobby(ServerLobby::TableCreated(listing))) = msg
+                {
+                    listing
+                } else {
+                    panic!("Received invalid create table response");
+                }
+            } else {
+                panic!("Didn't receive create table response");
+            }
+        }
+
+        async fn close_table(&mut self, id: &TableId) {
+            let close_msg = PokerMessage::close_table(id.clone());
+            let close_msg = serde_json::to_string(&close_msg).unwrap();
+
+            debug!("Sending close table message from client");
+            self.ws_stream
+                .send(Message::Text(close_msg))
+                .await
+                .expect("Failed to send message");
+        }
+
+        async fn get_tables(&mut self) -> Vec<TableListing> {
             let get_tables_msg = PokerMessage::get_tables();
             let get_tables_msg = serde_json::to_string(&get_tables_msg).unwrap();
 
@@ -123,7 +258,7 @@ mod tests {
                 .await
                 .expect("Failed to send message");
 
-            if let Some(Ok(Message::Text(msg))) = self.ws_stream.next().await {
+            if let Some(Ok(Message::Text(msg))) = self.next_frame().await {
                 let msg = serde_json::from_str::<PokerMessage>(&msg).unwrap();
                 if let PokerMessage::Server(Either::Lobby(ServerLobby::TableList(tables))) = msg {
                     tables
@@ -135,7 +270,41 @@ mod tests {
             }
         }
 
-        async fn subscribe_room(&mut self, room_id: &RoomId) {
+        async fn get_hand_histories(&mut self, limit: Option<usize>) -> Vec<HandHistory> {
+            let get_msg = PokerMessage::get_hand_histories(limit);
+            let get_msg = serde_json::to_string(&get_msg).unwrap();
+
+            debug!("Sending get hand histories message from client");
+            self.ws_stream
+                .send(Message::Text(get_msg))
+                .await
+                .expect("Failed to send message");
+
+            if let Some(Ok(Message::Text(msg))) = self.next_frame().await {
+                let msg = serde_json::from_str::<PokerMessage>(&msg).unwrap();
+                if let PokerMessage::Server(Either::Lobby(ServerLobby::HandHistories(hands))) = msg
+                {
+                    hands
+                } else {
+                    panic!("Received invalid hand histories response");
+                }
+            } else {
+                panic!("Didn't receive hand histories response");
+            }
+        }
+
+        async fn export_hand_history(&mut self, id: HandId) {
+            let export_msg = PokerMessage::export_hand_history(id);
+            let export_msg = serde_json::to_string(&export_msg).unwrap();
+
+            debug!("Sending export hand history message from client");
+            self.ws_stream
+                .send(Message::Text(export_msg))
+                .await
+                .expect("Failed to send message");
+        }
+
+        async fn subscribe_room(&mut self, room_id: &RoomId) -> TableState {
             let subscribe_msg = PokerMessage::subscribe_room(room_id.clone());
             let subscribe_msg = serde_json::to_string(&subscribe_msg).unwrap();
 
@@ -144,6 +313,44 @@ mod tests {
                 .send(Message::Text(subscribe_msg))
                 .await
                 .expect("Failed to send message");
+            self.receive_table_state(room_id).await
+        }
+
+        async fn unsubscribe_room(&mut self, room_id: &RoomId) {
+            let unsubscribe_msg = PokerMessage::unsubscribe_room(room_id.clone());
+            let unsubscribe_msg = serde_json::to_string(&unsubscribe_msg).unwrap();
+
+            debug!("Sending unsubscribe message from client");
+            self.ws_stream
+                .send(Message::Text(unsubscribe_msg))
+                .await
+                .expect("Failed to send message");
+        }
+
+        async fn resync(&mut self, room_id: &RoomId) {
+            let resync_msg = PokerMessage::resync(room_id.clone());
+            let resync_msg = serde_json::to_string(&resync_msg).unwrap();
+
+            debug!("Sending resync message from client");
+            self.ws_stream
+                .send(Message::Text(resync_msg))
+                .await
+                .expect("Failed to send message");
+        }
+
+        async fn receive_table_state(&mut self, room_id: &RoomId) -> TableState {
+            if let Some(Ok(Message::Text(text))) = self.next_frame().await {
+                let msg = serde_json::from_str::<PokerMessage>(&text).unwrap();
+                match msg {
+                    PokerMessage::Server(Either::Room(RoomMessage {
+                        room_id: received_room_id,
+                        payload: ServerRoomPayload::TableState(state),
+                    })) if *room_id == received_room_id => *state,
+                    msg => panic!("Expected table state, received {:?}", msg),
+                }
+            } else {
+                panic!("Did not receive a reply");
+            }
         }
 
         async fn send_chat(&mut self, message: &str, room_id: &RoomId) {
@@ -158,7 +365,7 @@ mod tests {
         }
 
         async fn sit_table(&mut self, chips: ChipInt, room_id: &RoomId) {
-            let sit_msg = PokerMessage::sit_table(room_id.clone(), chips);
+            let sit_msg = PokerMessage::sit_table(room_id.clone(), chips, None);
             let sit_msg = serde_json::to_string(&sit_msg).unwrap();
 
             debug!("Sending sit table from client");
@@ -179,6 +386,39 @@ mod tests {
                 .expect("Failed to send message");
         }
 
+        async fn show_cards(&mut self, cards: Vec<Card>, room_id: &RoomId) {
+            let show_msg = PokerMessage::show_cards(room_id.clone(), cards);
+            let show_msg = serde_json::to_string(&show_msg).unwrap();
+
+            debug!("Sending show cards from client");
+            self.ws_stream
+                .send(Message::Text(show_msg))
+                .await
+                .expect("Failed to send message");
+        }
+
+        async fn auto_muck(&mut self, value: bool, room_id: &RoomId) {
+            let auto_muck_msg = PokerMessage::auto_muck(room_id.clone(), value);
+            let auto_muck_msg = serde_json::to_string(&auto_muck_msg).unwrap();
+
+            debug!("Sending auto muck from client");
+            self.ws_stream
+                .send(Message::Text(auto_muck_msg))
+                .await
+                .expect("Failed to send message");
+        }
+
+        async fn client_seed(&mut self, seed: &str, room_id: &RoomId) {
+            let seed_msg = PokerMessage::client_seed(room_id.clone(), seed.to_owned());
+            let seed_msg = serde_json::to_string(&seed_msg).unwrap();
+
+            debug!("Sending client seed from client");
+            self.ws_stream
+                .send(Message::Text(seed_msg))
+                .await
+                .expect("Failed to send message");
+        }
+
         async fn fold(&mut self, room_id: &RoomId) {
             let fold_msg = PokerMessage::fold(room_id.clone());
             let fold_msg = serde_json::to_string(&fold_msg).unwrap();
@@ -190,8 +430,41 @@ mod tests {
                 .expect("Failed to send message");
         }
 
+        async fn stand_up(&mut self, room_id: &RoomId) {
+            let stand_msg = PokerMessage::stand_up(room_id.clone());
+            let stand_msg = serde_json::to_string(&stand_msg).unwrap();
+
+            debug!("Sending stand up from client");
+            self.ws_stream
+                .send(Message::Text(stand_msg))
+                .await
+                .expect("Failed to send message");
+        }
+
+        /// Receives messages sent to the player directly and by broadcast,
+        /// which can arrive in either order
+        async fn receive_msgs_any_order(&mut self, mut expected_msgs: Vec<PokerMessage>) {
+            while !expected_msgs.is_empty() {
+                let msg = self
+                    .ws_stream
+                    .next()
+                    .await
+                    .expect("Did not receive a reply")
+                    .expect("Failed to read message");
+                let Message::Text(text) = msg else {
+                    panic!("Received unexpected message type");
+                };
+                let msg = serde_json::from_str::<PokerMessage>(&text).unwrap();
+                let idx = expected_msgs
+                    .iter()
+                    .position(|expected| *expected == msg)
+                    .unwrap_or_else(|| panic!("Received unexpected message {:?}", msg));
+                expected_msgs.remove(idx);
+            }
+        }
+
         async fn receive_msg(&mut self, expected_msg: PokerMessage) {
-            if let Some(msg) = self.ws_stream.next().await {
+            if let Some(msg) = self.next_frame().await {
                 let msg = msg.expect("Failed to read message");
                 match msg {
                     Message::Text(text) => {
@@ -212,8 +485,8 @@ mod tests {
             &mut self,
             expected_room_id: &RoomId,
             expected_dealer_idx: usize,
-        ) {
-            if let Some(msg) = self.ws_stream.next().await {
+        ) -> PublicGameState {
+            if let Some(msg) = self.next_frame().await {
                 let msg = msg.expect("Failed to read message");
                 match msg {
                     Message::Text(text) => {
@@ -221,28 +494,78 @@ mod tests {
                         println!("{}", pretty_text);
                         let msg = serde_json::from_str::<PokerMessage>(&text).unwrap();
                         debug!(msg = ?msg);
-                        assert_matches!(
-                            msg,
+                        match msg {
                             PokerMessage::Server(Either::Room(RoomMessage {
                                 room_id,
-                                payload: ServerRoomPayload::NewGame(
-                                    PublicGameState {
-                                        id,
-                                        players,
-                                        dealer_idx,
-                                        current_player_idx,
-                                        game_active_players,
-                                        round_active_players,
-                                        community_cards,
-                                        stacks,
-                                        bets,
-                                        min_raise,
-                                        to_call,
-                                        pot,
-                                    }
-                                )
-                            }))
-                        if *expected_room_id == room_id && expected_dealer_idx == dealer_idx);
+                                payload: ServerRoomPayload::NewGame(state),
+                            })) if *expected_room_id == room_id
+                                && expected_dealer_idx == state.dealer_idx =>
+                            {
+                                state
+                            }
+                            msg => panic!("Expected new game, received {:?}", msg),
+                        }
+                    }
+                    _ => panic!("Received unexpected message type"),
+                }
+            } else {
+                panic!("Did not receive a reply");
+            }
+        }
+
+        async fn receive_deal_hand(&mut self, room_id: &RoomId) -> Hand {
+            if let Some(msg) = self.next_frame().await {
+                let msg = msg.expect("Failed to read message");
+                match msg {
+                    Message::Text(text) => {
+                        let pretty_text = pretty_print_json(&text);
+                        println!("{}", pretty_text);
+                        let msg = serde_json::from_str::<PokerMessage>(&text).unwrap();
+                        debug!(msg = ?msg);
+                        match msg {
+                            PokerMessage::Server(Either::Room(RoomMessage {
+                                room_id: received_room_id,
+                                payload: ServerRoomPayload::DealHand(hand),
+                            })) if *room_id == received_room_id => hand,
+                            msg => panic!("Expected deal hand, received {:?}", msg),
+                        }
+                    }
+                    _ => panic!("Received unexpected message type"),
+                }
+            } else {
+                panic!("Did not receive a reply");
+            }
+        }
+
+        /// Returns the hand's shuffle, revealed with the winners
+        async fn receive_declare_winner(&mut self, room_id: &RoomId, winner: &PlayerId) -> Shuffle {
+            if let Some(msg) = self.next_frame().await {
+                let msg = msg.expect("Failed to read message");
+                match msg {
+                    Message::Text(text) => {
+                        let pretty_text = pretty_print_json(&text);
+                        println!("{}", pretty_text);
+                        let msg = serde_json::from_str::<PokerMessage>(&text).unwrap();
+                        debug!(msg = ?msg);
+                        match msg {
+                            PokerMessage::Server(Either::Room(RoomMessage {
+                                room_id: received_room_id,
+                                payload:
+                                    ServerRoomPayload::DeclareWinner {
+                                        pots,
+                                        hands,
+                                        shuffle: Some(shuffle),
+                                    },
+                            })) if *room_id == received_room_id
+                                && hands.is_empty()
+                                && pots
+                                    .iter()
+                                    .all(|pot| pot.winners.iter().all(|w| w.player == *winner)) =>
+                            {
+                                shuffle
+                            }
+                            msg => panic!("Expected winners, received {:?}", msg),
+                        }
                     }
                     _ => panic!("Received unexpected message type"),
                 }
@@ -251,8 +574,8 @@ mod tests {
             }
         }
 
-        async fn receive_deal_hand(&mut self, room_id: &RoomId) {
-            if let Some(msg) = self.ws_stream.next().await {
+        async fn receive_community_cards(&mut self, room_id: &RoomId, num_cards: usize) {
+            if let Some(msg) = self.next_frame().await {
                 let msg = msg.expect("Failed to read message");
                 match msg {
                     Message::Text(text) => {
@@ -264,9 +587,10 @@ mod tests {
                             msg,
                             PokerMessage::Server(Either::Room(RoomMessage {
                                 room_id: received_room_id,
-                                payload: ServerRoomPayload::DealHand(hand)
+                                payload: ServerRoomPayload::CommunityCards { flop, turn, river }
                             }))
-                        if *room_id == received_room_id);
+                        if *room_id == received_room_id
+                            && 3 + turn.iter().count() + river.iter().count() == num_cards);
                     }
                     _ => panic!("Received unexpected message type"),
                 }
@@ -276,7 +600,7 @@ mod tests {
         }
 
         async fn receive_game_update(&mut self, room_id: &RoomId) {
-            if let Some(msg) = self.ws_stream.next().await {
+            if let Some(msg) = self.next_frame().await {
                 let msg = msg.expect("Failed to read message");
                 match msg {
                     Message::Text(text) => {
@@ -293,7 +617,9 @@ mod tests {
                                     PublicGameState {
                                         id,
                                         players,
+                                        seats,
                                         dealer_idx,
+                                        button_seat,
                                         current_player_idx,
                                         game_active_players,
                                         round_active_players,
@@ -302,7 +628,12 @@ mod tests {
                                         bets,
                                         min_raise,
                                         to_call,
+                                        min_raise_to,
+                                        max_raise_to,
                                         pot,
+                                        commitment,
+                                        client_seeds,
+                                        next_commitment,
                                     }
                                )
                             }))
@@ -325,10 +656,12 @@ mod tests {
         let tables = player1.get_tables().await;
         assert_eq!(tables.len(), 1);
 
-        let table = tables.first().unwrap();
+        let table = &tables.first().unwrap().config;
         let room_id = table.id.clone();
 
-        player1.subscribe_room(&room_id).await;
+        let state = player1.subscribe_room(&room_id).await;
+        assert_eq!(state.table, *table);
+        assert!(state.seats.is_empty() && state.game.is_none() && state.seat.is_none());
         player1.send_chat("Hello, World!", &room_id).await;
         player1
             .receive_msg(PokerMessage::chat_broadcast(
@@ -338,6 +671,9 @@ mod tests {
             ))
             .await;
 
+        // Subscribing again only resyncs, so broadcasts aren't doubled up
+        player1.subscribe_room(&room_id).await;
+
         let mut player2 = ClientConnection::setup_conn().await;
 
         // Chatting
@@ -367,8 +703,12 @@ mod tests {
             ))
             .await;
 
-        let expected_msg =
-            PokerMessage::sit_table_broadcast(room_id.clone(), player1.data.clone(), 0);
+        let expected_msg = PokerMessage::sit_table_broadcast(
+            room_id.clone(),
+            player1.data.clone(),
+            0,
+            *DEFAULT_CHIPS,
+        );
         player1.sit_table(*DEFAULT_CHIPS, &room_id).await;
         player2.receive_msg(expected_msg.clone()).await;
         player1.receive_msg(expected_msg).await;
@@ -378,32 +718,41 @@ mod tests {
         //     .receive_msg(PokerMessage::error("Insufficient Chips".to_owned()))
         //     .await;
 
-        let expected_msg =
-            PokerMessage::sit_table_broadcast(room_id.clone(), player2.data.clone(), 1);
+        let expected_msg = PokerMessage::sit_table_broadcast(
+            room_id.clone(),
+            player2.data.clone(),
+            1,
+            *DEFAULT_CHIPS,
+        );
         player2.sit_table(*DEFAULT_CHIPS, &room_id).await;
         player2.receive_msg(expected_msg.clone()).await;
         player1.receive_msg(expected_msg).await;
 
-        player1.receive_new_game(&room_id, 0).await;
+        let first_game = player1.receive_new_game(&room_id, 0).await;
         player2.receive_new_game(&room_id, 0).await;
+        // The small blind opens, from a full raise up to all-in
+        assert_eq!(first_game.min_raise_to, Some(4));
+        assert_eq!(first_game.max_raise_to, Some(*DEFAULT_CHIPS));
 
-        player1.receive_deal_hand(&room_id).await;
-        player2.receive_deal_hand(&room_id).await;
+        let player1_hand = player1.receive_deal_hand(&room_id).await;
+        let player2_hand = player2.receive_deal_hand(&room_id).await;
 
-        player1.bet(10, &room_id).await;
-        player1
+        player2.bet(10, &room_id).await;
+        player2
             .receive_msg(PokerMessage::error_room(
                 room_id.clone(),
                 "Not your turn".to_owned(),
             ))
             .await;
 
-        // Preflop
-        player2.bet(2, &room_id).await;
+        // Preflop, heads-up the button posts the small blind and acts first
+        player1.bet(2, &room_id).await;
         player2.receive_game_update(&room_id).await;
         player1.receive_game_update(&room_id).await;
 
-        player1.bet(2, &room_id).await;
+        player2.bet(2, &room_id).await;
+        player2.receive_community_cards(&room_id, 3).await;
+        player1.receive_community_cards(&room_id, 3).await;
         player2.receive_game_update(&room_id).await;
         player1.receive_game_update(&room_id).await;
 
@@ -413,6 +762,8 @@ mod tests {
         player1.receive_game_update(&room_id).await;
 
         player1.bet(0, &room_id).await;
+        player2.receive_community_cards(&room_id, 4).await;
+        player1.receive_community_cards(&room_id, 4).await;
         player2.receive_game_update(&room_id).await;
         player1.receive_game_update(&room_id).await;
 
@@ -422,62 +773,325 @@ mod tests {
         player1.receive_game_update(&room_id).await;
 
         player1.bet(0, &room_id).await;
+        player2.receive_community_cards(&room_id, 5).await;
+        player1.receive_community_cards(&room_id, 5).await;
         player2.receive_game_update(&room_id).await;
         player1.receive_game_update(&room_id).await;
 
-        // River
+        // River, where player 2 turns auto-muck off to choose whether to show
+        player2.auto_muck(false, &room_id).await;
         player2.bet(2, &room_id).await;
         player2.receive_game_update(&room_id).await;
         player1.receive_game_update(&room_id).await;
 
+        // Player 1 adds their own entropy to the next hand's shuffle, which
+        // the server committed to when this hand was dealt
+        let client_seed = "player 1's lucky seed".to_owned();
+        player1.client_seed(&client_seed, &room_id).await;
+
         // Game ends
+        let winner_id = player2.data.id.clone();
         player1.fold(&room_id).await;
         player2.receive_game_update(&room_id).await;
         player1.receive_game_update(&room_id).await;
+        player2.receive_declare_winner(&room_id, &winner_id).await;
+        let shuffle = player1.receive_declare_winner(&room_id, &winner_id).await;
+
+        // The revealed seed is the one committed to, and dealt player 1's hand
+        let commitment = first_game.commitment.unwrap();
+        shuffle
+            .verify(&commitment, 2, &[player1_hand[..].to_vec(), vec![]], &[])
+            .unwrap();
+
+        // The next deal waits for the winner of the folded hand, who shows
+        // one card
+        let prompt_msg =
+            PokerMessage::show_or_muck(room_id.clone(), winner_id.clone(), *SHOWDOWN_TIMEOUT);
+        player2.receive_msg(prompt_msg.clone()).await;
+        player1.receive_msg(prompt_msg).await;
+        player2.show_cards(vec![player2_hand[0]], &room_id).await;
+        let shown_msg = PokerMessage::cards_shown(
+            room_id.clone(),
+            winner_id.clone(),
+            vec![player2_hand[0]],
+            None,
+        );
+        player2.receive_msg(shown_msg.clone()).await;
+        player1.receive_msg(shown_msg).await;
+        player2.auto_muck(true, &room_id).await;
 
         // New game starts with dealer idx progressed
         player2.receive_new_game(&room_id, 1).await;
-        player1.receive_new_game(&room_id, 1).await;
+        let second_game = player1.receive_new_game(&room_id, 1).await;
+        assert_eq!(second_game.commitment, first_game.next_commitment);
+        assert_eq!(second_game.client_seeds, [client_seed, String::new()]);
 
         player1.receive_deal_hand(&room_id).await;
         player2.receive_deal_hand(&room_id).await;
 
-        player2.bet(10, &room_id).await;
-        player2
+        player1.bet(10, &room_id).await;
+        player1
             .receive_msg(PokerMessage::error_room(
                 room_id.clone(),
                 "Not your turn".to_owned(),
             ))
             .await;
 
-        // Turn timer folds player 1
+        // Turn timer runs out, then player 2's time bank, and they fold
+        player1.timers = true;
+        player2.timers = true;
         tokio::time::pause();
         tokio::time::advance(Duration::from_secs(*TURN_TIMEOUT + 1)).await;
         tokio::time::resume();
+        let expired_msg =
+            PokerMessage::timer_expired(room_id.clone(), player2.data.id.clone(), TurnTimer::Turn);
+        player1.receive_msg(expired_msg.clone()).await;
+        player2.receive_msg(expired_msg).await;
+        let player2_data = player2.data.clone();
+        let time_bank = player1
+            .receive_timer_started(&room_id, &player2_data, TurnTimer::TimeBank)
+            .await;
+        assert_eq!(time_bank, table.time_bank);
+        player2
+            .receive_timer_started(&room_id, &player2_data, TurnTimer::TimeBank)
+            .await;
+
+        tokio::time::pause();
+        tokio::time::advance(Duration::from_secs(time_bank + 1)).await;
+        tokio::time::resume();
+        let expired_msg = PokerMessage::timer_expired(
+            room_id.clone(),
+            player2.data.id.clone(),
+            TurnTimer::TimeBank,
+        );
+        player1.receive_msg(expired_msg.clone()).await;
+        player2.receive_msg(expired_msg).await;
+        player1.timers = false;
+        player2.timers = false;
 
-        // Receive fold of player 1
+        // Receive fold of player 2
+        let winner_id = player1.data.id.clone();
         player1.receive_game_update(&room_id).await;
         player2.receive_game_update(&room_id).await;
+        player1.receive_declare_winner(&room_id, &winner_id).await;
+        player2.receive_declare_winner(&room_id, &winner_id).await;
 
         // New game starts
         player2.receive_new_game(&room_id, 0).await;
         player1.receive_new_game(&room_id, 0).await;
 
         // Cards dealt
-        player1.receive_deal_hand(&room_id).await;
+        let player1_hand = player1.receive_deal_hand(&room_id).await;
         player2.receive_deal_hand(&room_id).await;
 
-        player1.bet(10, &room_id).await;
-        player1
+        player2.bet(10, &room_id).await;
+        player2
             .receive_msg(PokerMessage::error_room(
                 room_id.clone(),
                 "Not your turn".to_owned(),
             ))
             .await;
 
+        // Player 1 drops mid-hand and is caught up on reconnecting
+        player1.reconnect().await;
+        let state = player1.receive_table_state(&room_id).await;
+        assert_eq!(state.hand, Some(player1_hand));
+        assert_eq!(state.seats.len(), 2);
+        assert_eq!(state.seat.map(|seat| seat.index), Some(0));
+        assert_matches!(
+            state.game,
+            Some(PublicGameState {
+                current_player_idx: 0,
+                ..
+            })
+        );
+        assert_matches!(state.timer, Some(1..=30));
+
+        player1.resync(&room_id).await;
+        assert_eq!(player1.receive_table_state(&room_id).await.hand, state.hand);
+
+        // Player 2 stands up out of turn, folding the big blind
+        let winner_id = player1.data.id.clone();
+        player2.stand_up(&room_id).await;
+        player1.receive_game_update(&room_id).await;
+        player2.receive_game_update(&room_id).await;
+        player1.receive_declare_winner(&room_id, &winner_id).await;
+        player2.receive_declare_winner(&room_id, &winner_id).await;
+
+        let vacated_msg =
+            PokerMessage::seat_vacated_broadcast(room_id.clone(), player2.data.clone(), 1);
+        player1.receive_msg(vacated_msg.clone()).await;
+        player2
+            .receive_msgs_any_order(vec![
+                PokerMessage::stack_returned(room_id.clone(), 99),
+                vacated_msg,
+            ])
+            .await;
+
+        player2.stand_up(&room_id).await;
+        player2
+            .receive_msg(PokerMessage::error_room(
+                room_id.clone(),
+                "Not sitting at table".to_owned(),
+            ))
+            .await;
+
+        // Player 1 won the last hand without showing, so player 2 can't see their cards
+        let hands = player2.get_hand_histories(Some(2)).await;
+        assert_eq!(hands.len(), 2);
+        assert!(hands[0].id > hands[1].id);
+        let last = &hands[0];
+        assert!(last.shown.is_empty());
+        for seat in &last.seats {
+            assert_eq!(seat.cards.is_empty(), seat.player.id == player1.data.id);
+        }
+        player2.export_hand_history(last.id).await;
+        player2
+            .receive_msg(PokerMessage::hand_history_export(
+                last.id,
+                last.to_pokerstars(),
+            ))
+            .await;
+        player2.export_hand_history(last.id + 100).await;
+        player2
+            .receive_msg(PokerMessage::error_lobby(
+                "Not a hand you played".to_owned(),
+            ))
+            .await;
+
+        // Player 1 opens a table of their own, which player 2 sits at
+        let listing = player1
+            .create_table(TableConfig {
+                name: "Heads Up Hangout".to_owned(),
+                ..Table::default().config
+            })
+            .await;
+        assert_ne!(listing.config.id, room_id);
+        assert_eq!(listing.owner, Some(player1.data.id.clone()));
+        assert_eq!(player1.get_tables().await.len(), 2);
+        player2.sit_table(60, &listing.config.id).await;
+
+        player2.close_table(&listing.config.id).await;
+        player2
+            .receive_msg(PokerMessage::error_lobby(
+                "Only the table's creator can close it".to_owned(),
+            ))
+            .await;
+
+        // Closing cashes player 2 out and removes the table from the lobby
+        player1.close_table(&listing.config.id).await;
+        player1
+            .receive_msg(PokerMessage::table_closing(listing.config.id.clone()))
+            .await;
+        player2
+            .receive_msg(PokerMessage::stack_returned(listing.config.id.clone(), 60))
+            .await;
+        let tables = player1.get_tables().await;
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].config.id, room_id);
+        // Player 2 is left watching the first table until they unsubscribe
+        assert_eq!(tables[0].spectators, 1);
+        player2.unsubscribe_room(&room_id).await;
+        assert_eq!(player2.get_tables().await[0].spectators, 0);
+        player1.send_chat("bye", &room_id).await;
+        player1
+            .receive_msg(PokerMessage::chat_broadcast(
+                room_id.clone(),
+                player1.data.id.clone(),
+                "bye".to_owned(),
+            ))
+            .await;
+        assert_eq!(player2.get_tables().await.len(), 1);
+
         // TODO: Test:
         // - SitOutNextHand, SitOutNextBigBlind, WaitForBigBlind,
         // - CheckFold, CallAny
         server_handle.abort();
     }
+
+    #[test]
+    fn test_hand_survives_server_restart() {
+        dotenv().ok();
+        let path = std::env::temp_dir().join(format!(
+            "poker-restart-{}.db",
+            hex::encode(rand::random::<[u8; 8]>())
+        ));
+        let addr: std::net::SocketAddr = "127.0.0.1:8081".parse().unwrap();
+        let url = format!("ws://{addr}/ws");
+        let runtime = || {
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+        };
+        let start_server = || {
+            let storage = SqliteStorage::open(&path).unwrap();
+            tokio::spawn(server::serve(addr, Box::new(storage)))
+        };
+
+        // Dropping the runtime kills the server and everything it spawned
+        // without any chance to clean up, as if the process had died
+        let (session1, session2, room_id, hand1, hand2) = runtime().block_on(async {
+            start_server();
+            let mut player1 = ClientConnection::connect_to(&url, Session::default()).await;
+            let mut player2 = ClientConnection::connect_to(&url, Session::default()).await;
+            let room_id = player1.get_tables().await[0].config.id.clone();
+            player1.subscribe_room(&room_id).await;
+            player2.subscribe_room(&room_id).await;
+
+            for (seat, player) in [player1.data.clone(), player2.data.clone()]
+                .into_iter()
+                .enumerate()
+            {
+                let sit_msg = PokerMessage::sit_table_broadcast(
+                    room_id.clone(),
+                    player.clone(),
+                    seat,
+                    *DEFAULT_CHIPS,
+                );
+                let sitting = if seat == 0 {
+                    &mut player1
+                } else {
+                    &mut player2
+                };
+                sitting.sit_table(*DEFAULT_CHIPS, &room_id).await;
+                player1.receive_msg(sit_msg.clone()).await;
+                player2.receive_msg(sit_msg).await;
+            }
+            player1.receive_new_game(&room_id, 0).await;
+            player2.receive_new_game(&room_id, 0).await;
+            let hand1 = player1.receive_deal_hand(&room_id).await;
+            let hand2 = player2.receive_deal_hand(&room_id).await;
+
+            // The button calls, leaving the big blind to act
+            player1.bet(2, &room_id).await;
+            player1.receive_game_update(&room_id).await;
+            player2.receive_game_update(&room_id).await;
+            (player1.session, player2.session, room_id, hand1, hand2)
+        });
+
+        runtime().block_on(async {
+            start_server();
+            // Both players are still seated, and are caught up on reconnecting
+            let mut player1 = ClientConnection::connect_to(&url, session1).await;
+            let state = player1.receive_table_state(&room_id).await;
+            assert_eq!(state.hand, Some(hand1));
+            assert_eq!(state.seat.map(|seat| seat.index), Some(0));
+            let game = state.game.expect("Hand should be resumed");
+            assert_eq!(game.pot, 4);
+            assert_eq!(game.current_player_idx, 1);
+
+            let mut player2 = ClientConnection::connect_to(&url, session2).await;
+            let state = player2.receive_table_state(&room_id).await;
+            assert_eq!(state.hand, Some(hand2));
+
+            // The hand carries on where it was
+            player2.bet(2, &room_id).await;
+            player1.receive_community_cards(&room_id, 3).await;
+            player2.receive_community_cards(&room_id, 3).await;
+            player1.receive_game_update(&room_id).await;
+            player2.receive_game_update(&room_id).await;
+        });
+        let _ = std::fs::remove_file(path);
+    }
 }
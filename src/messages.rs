@@ -13,6 +13,8 @@ pub enum Either<C, S> {
     Room(S),
 }
 
+// Server messages carry whole game states, client messages are much smaller
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(untagged)]
 pub enum PokerMessage {
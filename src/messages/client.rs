@@ -1,23 +1,69 @@
 use crate::*;
+use rs_poker::core::Card;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(tag = "messageType", content = "payload", rename_all = "camelCase")]
 pub enum ClientLobby {
     GetTables,
+    /// Open a new table, the server picks its id
+    CreateTable {
+        config: TableConfig,
+    },
+    /// Close a table you created once its current hand is over
+    CloseTable {
+        id: TableId,
+    },
+    /// Your latest hands, newest first
+    GetHandHistories {
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// One of your hands in PokerStars' text format
+    ExportHandHistory {
+        id: HandId,
+    },
 }
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(tag = "messageType", content = "payload", rename_all = "camelCase")]
 pub enum ClientRoomPayload {
+    /// Follow the room's broadcasts, doing nothing more than a resync if
+    /// already subscribed
     Subscribe,
+    Unsubscribe,
+    /// Ask for the table's current state, as sent on subscribing
+    Resync,
     Chat(String),
-    SitTable { chips: ChipInt },
+    /// Sit in `seat` if given and empty, otherwise the first empty seat
+    SitTable {
+        chips: ChipInt,
+        #[serde(default)]
+        seat: Option<usize>,
+    },
+    /// Leave the seat, folding first if in a hand
+    StandUp,
+    /// Add chips to your stack, or as many as allowed up to the maximum buy-in
+    TopUp {
+        #[serde(default)]
+        chips: Option<ChipInt>,
+    },
     Bet(ChipInt),
+    Check,
+    Call,
+    Raise {
+        to: ChipInt,
+    },
+    AllIn,
     Fold,
     SitOutNextHand(bool),
     SitOutNextBigBlind(bool),
     WaitForBigBlind(bool),
     CheckFold(bool),
     CallAny(bool),
+    ShowCards(Vec<Card>),
+    MuckCards,
+    AutoMuck(bool),
+    /// Add your own entropy to the shuffle of each hand you're dealt into
+    ClientSeed(String),
 }
 
 impl PokerMessage {
@@ -25,6 +71,22 @@ impl PokerMessage {
         Self::Client(Either::Lobby(ClientLobby::GetTables))
     }
 
+    pub fn create_table(config: TableConfig) -> Self {
+        Self::Client(Either::Lobby(ClientLobby::CreateTable { config }))
+    }
+
+    pub fn get_hand_histories(limit: Option<usize>) -> Self {
+        Self::Client(Either::Lobby(ClientLobby::GetHandHistories { limit }))
+    }
+
+    pub fn export_hand_history(id: HandId) -> Self {
+        Self::Client(Either::Lobby(ClientLobby::ExportHandHistory { id }))
+    }
+
+    pub fn close_table(id: TableId) -> Self {
+        Self::Client(Either::Lobby(ClientLobby::CloseTable { id }))
+    }
+
     pub fn subscribe_room(room_id: RoomId) -> Self {
         Self::Client(Either::Room(RoomMessage {
             room_id,
@@ -32,6 +94,20 @@ impl PokerMessage {
         }))
     }
 
+    pub fn unsubscribe_room(room_id: RoomId) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::Unsubscribe,
+        }))
+    }
+
+    pub fn resync(room_id: RoomId) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::Resync,
+        }))
+    }
+
     pub fn chat(room_id: RoomId, message: String) -> Self {
         Self::Client(Either::Room(RoomMessage {
             room_id,
@@ -39,10 +115,10 @@ impl PokerMessage {
         }))
     }
 
-    pub fn sit_table(room_id: RoomId, chips: ChipInt) -> Self {
+    pub fn sit_table(room_id: RoomId, chips: ChipInt, seat: Option<usize>) -> Self {
         Self::Client(Either::Room(RoomMessage {
             room_id,
-            payload: ClientRoomPayload::SitTable { chips },
+            payload: ClientRoomPayload::SitTable { chips, seat },
         }))
     }
 
@@ -53,10 +129,73 @@ impl PokerMessage {
         }))
     }
 
+    pub fn check(room_id: RoomId) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::Check,
+        }))
+    }
+
+    pub fn call(room_id: RoomId) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::Call,
+        }))
+    }
+
+    pub fn raise(room_id: RoomId, to: ChipInt) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::Raise { to },
+        }))
+    }
+
+    pub fn all_in(room_id: RoomId) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::AllIn,
+        }))
+    }
+
     pub fn fold(room_id: RoomId) -> Self {
         Self::Client(Either::Room(RoomMessage {
             room_id,
             payload: ClientRoomPayload::Fold,
         }))
     }
+
+    pub fn stand_up(room_id: RoomId) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::StandUp,
+        }))
+    }
+
+    pub fn top_up(room_id: RoomId, chips: Option<ChipInt>) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::TopUp { chips },
+        }))
+    }
+
+    pub fn client_seed(room_id: RoomId, seed: String) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::ClientSeed(seed),
+        }))
+    }
+
+    pub fn show_cards(room_id: RoomId, cards: Vec<Card>) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::ShowCards(cards),
+        }))
+    }
+
+    pub fn auto_muck(room_id: RoomId, value: bool) -> Self {
+        Self::Client(Either::Room(RoomMessage {
+            room_id,
+            payload: ClientRoomPayload::AutoMuck(value),
+        }))
+    }
 }
@@ -4,7 +4,16 @@ use rs_poker::core::{Card, Hand};
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(tag = "messageType", content = "payload", rename_all = "camelCase")]
 pub enum ServerLobby {
-    TableList(Vec<TableConfig>),
+    TableList(Vec<TableListing>),
+    TableCreated(TableListing),
+    /// The table closes once its current hand is over
+    TableClosing(TableId),
+    /// Hands you played, without anyone else's hole cards unless shown
+    HandHistories(Vec<HandHistory>),
+    HandHistoryExport {
+        id: HandId,
+        text: String,
+    },
     LobbyError(String),
 }
 
@@ -18,8 +27,30 @@ pub enum ServerRoomPayload {
     SitTable {
         player: Player,
         index: usize,
+        stack: ChipInt,
+    },
+    SeatVacated {
+        player: Player,
+        index: usize,
+    },
+    /// The table closed and everyone seated was cashed out
+    TableClosed,
+    /// Sent only to the player who left, with the stack going back to their balance
+    StackReturned {
+        stack: ChipInt,
+    },
+    ToppedUp {
+        player: PlayerId,
+        index: usize,
+        stack: ChipInt,
+    },
+    /// Sent only to the player, with top-up chips that went over the maximum buy-in
+    TopUpRefunded {
+        chips: ChipInt,
     },
     RoomError(String),
+    /// Sent only to the player, on subscribing, reconnecting or asking to resync
+    TableState(Box<TableState>),
     NewGame(PublicGameState),
     GameUpdate(PublicGameState),
     DealHand(Hand),
@@ -28,18 +59,74 @@ pub enum ServerRoomPayload {
         turn: Option<Card>,
         river: Option<Card>,
     },
+    RunOut {
+        hands: Vec<ShownHand>,
+    },
+    /// A player's turn timer started, counting down `timeout` seconds until
+    /// `expires_at`, in milliseconds since the Unix epoch
+    TimerStarted {
+        player: PlayerId,
+        timer: TurnTimer,
+        timeout: u64,
+        expires_at: u64,
+    },
+    TimerExpired {
+        player: PlayerId,
+        timer: TurnTimer,
+    },
+    ShowOrMuck {
+        player: PlayerId,
+        timeout: u64,
+    },
+    CardsShown {
+        player: PlayerId,
+        cards: Vec<Card>,
+        description: Option<String>,
+    },
+    CardsMucked {
+        player: PlayerId,
+    },
     DeclareWinner {
-        winner: PlayerId,
-        hand: Hand,
+        pots: Vec<PotResult>,
+        hands: Vec<ShownHand>,
+        /// Reveals the server seed, so anyone can check the deal against the
+        /// commitment made before it. This deals mucked hands again too.
+        shuffle: Option<Shuffle>,
     },
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PotResult {
+    pub amount: ChipInt,
+    pub winners: Vec<PotWinner>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PotWinner {
+    pub player: PlayerId,
+    pub amount: ChipInt,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShownHand {
+    pub player: PlayerId,
+    pub hand: Hand,
+    pub description: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PublicGameState {
     pub id: GameId,
     pub players: Vec<Player>,
+    /// Seat of each player in `players`
+    pub seats: Vec<usize>,
     pub dealer_idx: usize,
+    /// Seat of the dealer button, which may be empty under dead button rules
+    pub button_seat: usize,
     pub game_active_players: Vec<usize>,
     pub round_active_players: Vec<usize>,
     pub current_player_idx: usize,
@@ -48,13 +135,84 @@ pub struct PublicGameState {
     pub bets: Vec<i32>,
     pub min_raise: i32,
     pub to_call: i32,
+    /// Smallest and largest total bets the current player can bet or raise
+    /// to under the table's betting structure, `None` when they can only
+    /// check, call or fold
+    pub min_raise_to: Option<ChipInt>,
+    pub max_raise_to: Option<ChipInt>,
     pub pot: i32,
+    /// Hash of the server seed the hand was shuffled with, committed to
+    /// before the deal
+    pub commitment: Option<B256>,
+    /// What each of `players` added to the shuffle
+    pub client_seeds: Vec<String>,
+    /// Hash of the server seed the table's next hand will be shuffled with
+    pub next_commitment: Option<B256>,
+}
+
+/// Stages of a turn. When the turn timer runs out the player's time bank
+/// starts, then disconnected players get some grace, before they check or fold.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TurnTimer {
+    Turn,
+    TimeBank,
+    DisconnectGrace,
+}
+
+/// Everything a player needs to catch up with a table
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TableState {
+    pub table: TableConfig,
+    pub seats: Vec<PublicSeat>,
+    pub game: Option<PublicGameState>,
+    /// The player's own hole cards in the current hand
+    pub hand: Option<Hand>,
+    /// The player's own seat, when sitting at the table
+    pub seat: Option<SeatSettings>,
+    /// Seconds left on the turn or showdown timer, when one is running
+    pub timer: Option<u64>,
+    /// Hash of the server seed the next hand will be shuffled with
+    pub next_commitment: B256,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicSeat {
+    pub index: usize,
+    pub player: Player,
+    /// Stack at the start of the current hand
+    pub stack: ChipInt,
+    pub sitting_out: bool,
+    /// Seconds left in the player's time bank
+    pub time_bank: u64,
+    pub connected: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SeatSettings {
+    pub index: usize,
+    pub wait_for_big_blind: bool,
+    pub sit_out_next_hand: bool,
+    pub sit_out_next_big_blind: bool,
+    pub standing_up: bool,
+    /// Chips waiting to be added once the current hand is over
+    pub top_up: ChipInt,
+    pub auto_muck: bool,
+    pub check_fold: bool,
+    pub call_any: bool,
+    pub client_seed: String,
 }
 
 impl PokerMessage {
-    fn public_game_state_from_game(game: &Game) -> PublicGameState {
+    pub fn public_game_state_from_game(game: &Game) -> PublicGameState {
         let game_state = game.state.clone();
         let current_round = game_state.current_round_data();
+        let raise_range = (!game.is_over())
+            .then(|| game.raise_range(game.current_player_idx()))
+            .flatten();
         PublicGameState {
             id: game.id.clone(),
             players: game
@@ -63,16 +221,26 @@ impl PokerMessage {
                 .into_iter()
                 .map(GamePlayer::into)
                 .collect(),
+            seats: game.players.iter().map(|p| p.seat).collect(),
             dealer_idx: game_state.dealer_idx,
+            button_seat: game.button_seat,
             community_cards: game_state.board.clone(),
             min_raise: current_round.min_raise,
             to_call: game.current_bet() as i32,
+            min_raise_to: raise_range.map(|(min, _)| min),
+            max_raise_to: raise_range.map(|(_, max)| max),
             current_player_idx: game.current_player_idx(),
             pot: game_state.total_pot,
             stacks: game_state.stacks.clone(),
             bets: current_round.player_bet.clone(),
             game_active_players: game_state.player_active.ones().collect(),
             round_active_players: current_round.player_active.ones().collect(),
+            commitment: game.shuffle().map(Shuffle::commitment),
+            client_seeds: game
+                .shuffle()
+                .map(|shuffle| shuffle.client_seeds.clone())
+                .unwrap_or_default(),
+            next_commitment: game.next_commitment,
         }
     }
 
@@ -81,7 +249,7 @@ impl PokerMessage {
         Self::Server(Either::Lobby(ServerLobby::LobbyError(err)))
     }
 
-    pub fn table_list(tables: Vec<TableConfig>) -> Self {
+    pub fn table_list(tables: Vec<TableListing>) -> Self {
         Self::Server(Either::Lobby(ServerLobby::TableList(tables)))
     }
 
@@ -93,6 +261,65 @@ impl PokerMessage {
         }))
     }
 
+    pub fn table_state(
+        room_id: RoomId,
+        table: &Table,
+        player: &Player,
+        timer: Option<u64>,
+        live: bool,
+    ) -> Self {
+        let seats = table
+            .seats
+            .iter()
+            .enumerate()
+            .filter_map(|(index, seat)| {
+                seat.as_ref().map(|p| PublicSeat {
+                    index,
+                    player: p.info.clone(),
+                    stack: p.chips,
+                    sitting_out: p.sit_out_next_hand,
+                    time_bank: p.time_bank,
+                    connected: p.connected,
+                })
+            })
+            .collect();
+        let game = table.game();
+        let game_player = game.and_then(|game| {
+            game.players
+                .iter()
+                .position(|p| p.info.id == player.id)
+                .map(|idx| (&game.players[idx], &game.state.hands[idx]))
+        });
+        let seat = table.player_seat(player).map(|index| {
+            let p = table.seats[index].as_ref().unwrap();
+            SeatSettings {
+                index,
+                wait_for_big_blind: p.wait_for_big_blind,
+                sit_out_next_hand: p.sit_out_next_hand,
+                sit_out_next_big_blind: p.sit_out_next_big_blind,
+                standing_up: p.standing_up,
+                top_up: p.top_up,
+                auto_muck: p.auto_muck,
+                check_fold: game_player.is_some_and(|(p, _)| p.check_fold),
+                call_any: game_player.is_some_and(|(p, _)| p.call_any),
+                client_seed: p.client_seed.clone(),
+            }
+        });
+
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::TableState(Box::new(TableState {
+                table: table.config.clone(),
+                seats,
+                game: game.filter(|_| live).map(Self::public_game_state_from_game),
+                hand: game_player.map(|(_, hand)| hand.clone()),
+                seat,
+                timer,
+                next_commitment: table.commitment(),
+            })),
+        }))
+    }
+
     pub fn chat_broadcast(room_id: RoomId, from: PlayerId, message: String) -> Self {
         Self::Server(Either::Room(RoomMessage {
             room_id,
@@ -100,10 +327,74 @@ impl PokerMessage {
         }))
     }
 
-    pub fn sit_table_broadcast(room_id: RoomId, player: Player, index: usize) -> Self {
+    pub fn sit_table_broadcast(
+        room_id: RoomId,
+        player: Player,
+        index: usize,
+        stack: ChipInt,
+    ) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::SitTable {
+                player,
+                index,
+                stack,
+            },
+        }))
+    }
+
+    pub fn seat_vacated_broadcast(room_id: RoomId, player: Player, index: usize) -> Self {
         Self::Server(Either::Room(RoomMessage {
             room_id,
-            payload: ServerRoomPayload::SitTable { player, index },
+            payload: ServerRoomPayload::SeatVacated { player, index },
+        }))
+    }
+
+    pub fn table_created(listing: TableListing) -> Self {
+        Self::Server(Either::Lobby(ServerLobby::TableCreated(listing)))
+    }
+
+    pub fn hand_histories(hands: Vec<HandHistory>) -> Self {
+        Self::Server(Either::Lobby(ServerLobby::HandHistories(hands)))
+    }
+
+    pub fn hand_history_export(id: HandId, text: String) -> Self {
+        Self::Server(Either::Lobby(ServerLobby::HandHistoryExport { id, text }))
+    }
+
+    pub fn table_closing(id: TableId) -> Self {
+        Self::Server(Either::Lobby(ServerLobby::TableClosing(id)))
+    }
+
+    pub fn table_closed(room_id: RoomId) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::TableClosed,
+        }))
+    }
+
+    pub fn stack_returned(room_id: RoomId, stack: ChipInt) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::StackReturned { stack },
+        }))
+    }
+
+    pub fn topped_up(room_id: RoomId, player: PlayerId, index: usize, stack: ChipInt) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::ToppedUp {
+                player,
+                index,
+                stack,
+            },
+        }))
+    }
+
+    pub fn top_up_refunded(room_id: RoomId, chips: ChipInt) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::TopUpRefunded { chips },
         }))
     }
 
@@ -134,6 +425,116 @@ impl PokerMessage {
         }))
     }
 
+    pub fn declare_winner(room_id: RoomId, game: &Game, shown: &[usize]) -> Self {
+        let player_id = |idx: usize| game.players[idx].info.id.clone();
+        let pots = game
+            .pots
+            .iter()
+            .map(|pot| PotResult {
+                amount: pot.amount,
+                winners: pot
+                    .winners
+                    .iter()
+                    .map(|(idx, amount)| PotWinner {
+                        player: player_id(*idx),
+                        amount: *amount,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let hands = game
+            .ranks
+            .iter()
+            .filter(|(_, idx)| shown.contains(idx))
+            .map(|(rank, idx)| ShownHand {
+                player: player_id(*idx),
+                hand: game.state.hands[*idx].clone(),
+                description: describe_rank(rank),
+            })
+            .collect();
+
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::DeclareWinner {
+                pots,
+                hands,
+                shuffle: game.shuffle().cloned(),
+            },
+        }))
+    }
+
+    pub fn run_out(room_id: RoomId, game: &Game) -> Self {
+        let hands = game
+            .contenders()
+            .ones()
+            .map(|idx| ShownHand {
+                player: game.players[idx].info.id.clone(),
+                hand: game.state.hands[idx].clone(),
+                description: describe_rank(&game.rank_hand(idx)),
+            })
+            .collect();
+
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::RunOut { hands },
+        }))
+    }
+
+    pub fn timer_started(
+        room_id: RoomId,
+        player: PlayerId,
+        timer: TurnTimer,
+        timeout: u64,
+        expires_at: u64,
+    ) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::TimerStarted {
+                player,
+                timer,
+                timeout,
+                expires_at,
+            },
+        }))
+    }
+
+    pub fn timer_expired(room_id: RoomId, player: PlayerId, timer: TurnTimer) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::TimerExpired { player, timer },
+        }))
+    }
+
+    pub fn show_or_muck(room_id: RoomId, player: PlayerId, timeout: u64) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::ShowOrMuck { player, timeout },
+        }))
+    }
+
+    pub fn cards_shown(
+        room_id: RoomId,
+        player: PlayerId,
+        cards: Vec<Card>,
+        description: Option<String>,
+    ) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::CardsShown {
+                player,
+                cards,
+                description,
+            },
+        }))
+    }
+
+    pub fn cards_mucked(room_id: RoomId, player: PlayerId) -> Self {
+        Self::Server(Either::Room(RoomMessage {
+            room_id,
+            payload: ServerRoomPayload::CardsMucked { player },
+        }))
+    }
+
     pub fn game_update(room_id: RoomId, game: &Game) -> Self {
         let state_update = Self::public_game_state_from_game(game);
         Self::Server(Either::Room(RoomMessage {
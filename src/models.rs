@@ -1,7 +1,19 @@
+mod betting;
 mod game;
+mod history;
+mod journal;
 mod player;
+mod shuffle;
 mod table;
+#[cfg(test)]
+mod test_util;
+mod variant;
 
+pub use betting::*;
 pub use game::*;
+pub use history::*;
+pub use journal::*;
 pub use player::*;
+pub use shuffle::*;
 pub use table::*;
+pub use variant::*;
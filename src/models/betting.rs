@@ -0,0 +1,73 @@
+use crate::*;
+
+/// How much players can bet and raise
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(tag = "structure", rename_all = "camelCase")]
+pub enum BettingStructure {
+    /// Anything from a full raise up to all-in
+    #[default]
+    NoLimit,
+    /// Up to a raise the size of the pot once the player has called
+    PotLimit,
+    /// Bets and raises of exactly `small_bet` preflop and on the flop, and
+    /// `big_bet` on the turn and river
+    FixedLimit {
+        small_bet: ChipInt,
+        big_bet: ChipInt,
+        /// Raises allowed on each street after the first bet, which preflop
+        /// is the big blind
+        raise_cap: u32,
+    },
+}
+
+impl BettingStructure {
+    /// What every bet and raise on `street` must be in fixed-limit games
+    pub fn bet_size(self, street: Street) -> Option<ChipInt> {
+        match (self, street) {
+            (Self::FixedLimit { small_bet, .. }, Street::Preflop | Street::Flop) => Some(small_bet),
+            (Self::FixedLimit { big_bet, .. }, Street::Turn | Street::River) => Some(big_bet),
+            _ => None,
+        }
+    }
+
+    /// The limit as PokerStars names it in hand histories
+    pub fn pokerstars_name(self) -> &'static str {
+        match self {
+            Self::NoLimit => "No Limit",
+            Self::PotLimit => "Pot Limit",
+            Self::FixedLimit { .. } => "Limit",
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Self::FixedLimit {
+            small_bet, big_bet, ..
+        } = *self
+        {
+            if small_bet == 0 || big_bet < small_bet {
+                bail!("Big bet must be at least the small bet, which can't be 0")
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Who pays the ante
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum AnteKind {
+    /// Every player dealt in
+    Classic,
+    /// The big blind, once for the whole table
+    BigBlind,
+    /// The player on the button, once for the whole table
+    Button,
+}
+
+/// Chips put into the pot before the deal. Antes are dead, so unlike blinds
+/// they don't count towards calling.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+pub struct Ante {
+    pub kind: AnteKind,
+    pub amount: ChipInt,
+}
@@ -1,43 +1,135 @@
 use crate::*;
-use rs_poker::arena::{game_state::Round, GameState};
-use rs_poker::core::{FlatDeck, Hand, Rank, Rankable};
+use rs_poker::arena::{
+    game_state::{Round, RoundData},
+    GameState,
+};
+use rs_poker::core::{Card, FlatDeck, Hand, PlayerBitSet, Rank, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub type GameId = TableId;
 
 #[derive(Debug)]
 pub struct Game {
     pub id: GameId,
+    pub variant: GameVariant,
+    /// The variant's usual structure unless the table sets its own
+    pub betting: BettingStructure,
     pub players: Vec<GamePlayer>,
     pub state: GameState,
     pub deck: FlatDeck,
+    pub pots: Vec<Pot>,
+    /// Hand ranks of the players who went to showdown, best first
+    pub ranks: Vec<(Rank, usize)>,
+    /// Last player to bet or raise on the current street
+    pub last_aggressor: Option<usize>,
+    /// Seat of the dealer button, which may be empty under dead button rules
+    pub button_seat: usize,
+    /// Unix time in milliseconds the hand was dealt
+    pub started_at: u64,
+    /// Every blind posted and action taken, in order
+    pub actions: Vec<HandAction>,
+    /// Where the cards came from, unless the deck was given as it is
+    pub source: Option<DeckSource>,
+    /// Commitment to the server seed of the table's next hand, announced with
+    /// this one so client seeds changed during it can't be played against
+    pub next_commitment: Option<B256>,
+    events: Vec<GameEvent>,
+}
+
+/// Who posts the blinds, as indexes into the game's players, and the ante
+/// collected before them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blinds {
+    /// Acts last after the flop, on the button or just before a dead button
+    pub dealer: usize,
+    /// `None` when the small blind is dead
+    pub small_blind: Option<usize>,
+    pub big_blind: usize,
+    pub ante: Option<Ante>,
+}
+
+impl Blinds {
+    /// Blinds to the left of the dealer, or heads-up the dealer posts the small blind
+    pub fn from_dealer(dealer: usize, num_players: usize) -> Self {
+        let small_blind = if num_players == 2 {
+            dealer
+        } else {
+            (dealer + 1) % num_players
+        };
+        Self {
+            dealer,
+            small_blind: Some(small_blind),
+            big_blind: (small_blind + 1) % num_players,
+            ante: None,
+        }
+    }
+}
+
+/// Something that happened during the game which the room should announce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    /// A street was dealt, holds the whole board so far
+    CommunityCards(Vec<Card>),
+    /// Everyone left is all-in, so the rest of the board is dealt without betting
+    RunOut,
+}
+
+/// A betting action taken by the player whose turn it is.
+/// `Bet`, `Raise` and `BetTo` amounts are the player's total bet for the round.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(tag = "action", content = "amount", rename_all = "camelCase")]
+pub enum Action {
+    Check,
+    Call,
+    /// Opens the betting, which fails if there is already a bet
+    Bet(ChipInt),
+    Raise(ChipInt),
+    AllIn,
+    Fold,
+    /// Checks, calls, bets or raises, whichever puts the player's total bet
+    /// at the amount
+    BetTo(ChipInt),
+}
+
+/// A main or side pot, built from each player's total contribution to the hand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pot {
+    pub amount: ChipInt,
+    /// Indexes of the players who can win this pot
+    pub eligible: Vec<usize>,
+    /// Indexes of the players who won this pot and the amount each was awarded
+    pub winners: Vec<(usize, ChipInt)>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct GamePlayer {
     pub info: Player,
+    /// Stack at the start of the hand
+    pub chips: ChipInt,
     pub check_fold: bool,
     pub call_any: bool,
+    /// Posts a big blind to be dealt in out of position
+    pub posts_big_blind: bool,
+    /// Seat at the table
+    pub seat: usize,
 }
 
 impl GamePlayer {
-    fn new(player: Player) -> Self {
+    fn new(player: Player, chips: ChipInt) -> Self {
         Self {
             info: player,
+            chips,
             check_fold: false,
             call_any: false,
+            posts_big_blind: false,
+            seat: 0,
         }
     }
 }
 
-impl From<Player> for GamePlayer {
-    fn from(player: Player) -> Self {
-        Self::new(player)
-    }
-}
-
 impl From<TablePlayer> for GamePlayer {
     fn from(player: TablePlayer) -> Self {
-        Self::new(player.info)
+        Self::new(player.info, player.chips)
     }
 }
 
@@ -45,16 +137,46 @@ impl Game {
     pub fn new(
         id: GameId,
         players: Vec<GamePlayer>,
-        dealer_idx: usize,
+        blinds: Blinds,
+        small_blind: ChipInt,
+        big_blind: ChipInt,
+        variant: GameVariant,
+    ) -> Self {
+        let shuffle = Shuffle::new(random_seed(), vec![String::new(); players.len()]);
+        let source = DeckSource::Shuffle(shuffle);
+        Self::with_source(id, players, blinds, small_blind, big_blind, variant, source)
+    }
+
+    /// Deals the game from the deck `source` gives
+    pub fn with_source(
+        id: GameId,
+        players: Vec<GamePlayer>,
+        blinds: Blinds,
         small_blind: ChipInt,
         big_blind: ChipInt,
+        variant: GameVariant,
+        source: DeckSource,
+    ) -> Self {
+        let deck = source.deck();
+        let mut game = Self::with_deck(id, players, blinds, small_blind, big_blind, variant, deck);
+        game.source = Some(source);
+        game
+    }
+
+    /// Deals the game from `deck`, taking cards off the end
+    pub fn with_deck(
+        id: GameId,
+        players: Vec<GamePlayer>,
+        blinds: Blinds,
+        small_blind: ChipInt,
+        big_blind: ChipInt,
+        variant: GameVariant,
+        mut deck: FlatDeck,
     ) -> Self {
-        // shuffled deck of 52 cards
-        let mut deck = FlatDeck::default();
         let mut hands: Vec<Hand> = (0..players.len()).map(|_| Hand::default()).collect();
 
-        // Deal 2 cards to each player
-        for _ in 0..2 {
+        // Deal the variant's hole cards one at a time around the table
+        for _ in 0..variant.hole_cards() {
             for hand in &mut hands {
                 let next_card = deck.deal().expect("Deck should not be empty");
                 hand.push(next_card);
@@ -63,26 +185,47 @@ impl Game {
         debug!("Players hands: {:?}", &hands);
 
         let mut game_state = GameState::new(
-            (0..players.len()).map(|_| *DEFAULT_CHIPS as i32).collect(),
+            players.iter().map(|p| p.chips as i32).collect(),
             big_blind as i32,
             small_blind as i32,
-            dealer_idx,
+            blinds.dealer,
         );
 
         game_state.hands = hands;
+        let players_seat = players[blinds.dealer].seat;
 
         let mut new_game = Self {
             id,
+            variant,
+            betting: variant.betting_structure(),
             players,
             deck,
             state: game_state,
+            pots: vec![],
+            ranks: vec![],
+            last_aggressor: None,
+            button_seat: players_seat,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64),
+            actions: vec![],
+            source: None,
+            next_commitment: None,
+            events: vec![],
         };
 
-        // Advance to preflop and take blinds
-        new_game.advance_round();
+        new_game.post_blinds(blinds);
+        new_game.post_big_blinds();
+        // Blinds may have put enough players all-in that there is no betting left
+        new_game.advance();
         new_game
     }
 
+    /// The provably fair shuffle the cards came from, if they did
+    pub fn shuffle(&self) -> Option<&Shuffle> {
+        self.source.as_ref().and_then(DeckSource::shuffle)
+    }
+
     pub fn players_hands(&self) -> Vec<(&Player, &Hand)> {
         self.players
             .iter()
@@ -115,43 +258,287 @@ impl Game {
         self.state.current_round_data().player_bet[player_idx] as ChipInt
     }
 
-    pub fn bet(&mut self, amount: ChipInt) -> Result<i32, rs_poker::arena::errors::GameStateError> {
-        let bet = self.state.do_bet(amount as i32, false)?;
+    pub fn players_stack(&self, player_idx: usize) -> ChipInt {
+        self.state.stacks[player_idx] as ChipInt
+    }
+
+    /// Chips the player still needs to put in to match the current bet
+    pub fn to_call(&self, player_idx: usize) -> ChipInt {
+        self.current_bet()
+            .saturating_sub(self.players_bet(player_idx))
+    }
+
+    /// Smallest total bet that is a full raise this round. In fixed-limit
+    /// games that's the only size a bet or raise can be.
+    pub fn min_raise_to(&self) -> ChipInt {
+        let raise = match self.betting.bet_size(Street::from(self.state.round)) {
+            Some(bet_size) => bet_size,
+            None => self.state.current_round_data().min_raise as ChipInt,
+        };
+        self.current_bet() + raise
+    }
+
+    /// Largest total bet the player can make this round, i.e. going all-in
+    pub fn max_bet(&self, player_idx: usize) -> ChipInt {
+        self.players_bet(player_idx) + self.players_stack(player_idx)
+    }
+
+    /// Smallest and largest total bets the player can bet or raise to this
+    /// round, which is all-in when they can't cover a full raise. `None` when
+    /// they can only check, call or fold.
+    pub fn raise_range(&self, player_idx: usize) -> Option<(ChipInt, ChipInt)> {
+        let limit = match self.betting {
+            BettingStructure::NoLimit => ChipInt::MAX,
+            // A raise the size of the pot once the player has called
+            BettingStructure::PotLimit => {
+                let pot = self.state.total_pot as ChipInt + self.to_call(player_idx);
+                self.current_bet() + pot
+            }
+            BettingStructure::FixedLimit { raise_cap, .. } => {
+                let street = Street::from(self.state.round);
+                let raises = self
+                    .actions
+                    .iter()
+                    .filter(|action| action.street == street)
+                    .filter(|action| action.kind == HandActionKind::Raise)
+                    .count();
+                if raises >= raise_cap as usize {
+                    return None;
+                }
+                self.min_raise_to()
+            }
+        };
+        let max = limit.max(self.min_raise_to()).min(self.max_bet(player_idx));
+        (max > self.current_bet()).then(|| (self.min_raise_to().min(max), max))
+    }
+
+    /// Validates and applies an action for the current player.
+    /// Returns the chips the player added to the pot.
+    pub fn act(&mut self, action: Action) -> Result<ChipInt> {
+        if self.is_over() {
+            bail!("Game is not active");
+        }
+        let idx = self.current_player_idx();
+        let to_call = self.to_call(idx);
+        let max_bet = self.max_bet(idx);
+
+        let total_bet = match action {
+            Action::BetTo(amount) => return self.bet(amount),
+            Action::Fold => {
+                self.fold();
+                return Ok(0);
+            }
+            Action::Check => {
+                if to_call > 0 {
+                    bail!("Cannot check, {} to call", to_call);
+                }
+                self.players_bet(idx)
+            }
+            Action::Call => {
+                if to_call == 0 {
+                    bail!("Nothing to call, check instead");
+                }
+                // Calling for less than the full amount puts the player all-in
+                self.current_bet().min(max_bet)
+            }
+            Action::Bet(amount) | Action::Raise(amount) => {
+                if matches!(action, Action::Bet(_)) && self.current_bet() > 0 {
+                    bail!(
+                        "Cannot bet, there is already a bet of {}",
+                        self.current_bet()
+                    );
+                }
+                if matches!(action, Action::Raise(_)) && self.current_bet() == 0 {
+                    bail!("Cannot raise, there is no bet to raise");
+                }
+                if amount > max_bet {
+                    bail!("Not enough chips, the most you can bet is {}", max_bet);
+                }
+                if amount <= self.current_bet() {
+                    bail!(
+                        "Bet must be more than the current bet of {}",
+                        self.current_bet()
+                    );
+                }
+                let Some((min, max)) = self.raise_range(idx) else {
+                    bail!("Betting is capped, you can only call or fold");
+                };
+                if amount > max {
+                    bail!("Over the limit, the most you can bet is {}", max);
+                }
+                if amount < min {
+                    bail!(
+                        "Minimum is {} unless going all-in for {}",
+                        self.min_raise_to(),
+                        max_bet
+                    );
+                }
+                amount
+            }
+            Action::AllIn => {
+                if max_bet == self.players_bet(idx) {
+                    bail!("No chips left to go all-in with");
+                }
+                if max_bet > self.current_bet() {
+                    match self.raise_range(idx) {
+                        None => bail!("Betting is capped, you can only call or fold"),
+                        Some((_, max)) if max < max_bet => {
+                            bail!("Over the limit, the most you can bet is {}", max)
+                        }
+                        Some(_) => {}
+                    }
+                }
+                max_bet
+            }
+        };
+
+        let prev_bet = self.state.current_round_data().bet;
+        let prev_min_raise = self.state.current_round_data().min_raise;
+        let added = self.state.do_bet(total_bet as i32, false)?;
+
+        // rs_poker keeps the largest amount put in at once as the min raise,
+        // but the next raise only has to match the size of the last full raise
+        let raise = self.state.current_round_data().bet - prev_bet;
+        self.state.mut_current_round_data().min_raise = prev_min_raise.max(raise);
+        if raise > 0 {
+            self.last_aggressor = Some(idx);
+        }
+
+        let kind = if added == 0 {
+            HandActionKind::Check
+        } else if raise == 0 {
+            HandActionKind::Call
+        } else if prev_bet == 0 {
+            HandActionKind::Bet
+        } else {
+            HandActionKind::Raise
+        };
+        self.record_action(idx, kind, added as ChipInt);
+
         self.advance();
-        Ok(bet)
+        Ok(added as ChipInt)
+    }
+
+    /// Puts the current player's total bet for the round at `amount`.
+    /// A bet of the current amount is a check or call.
+    pub fn bet(&mut self, amount: ChipInt) -> Result<ChipInt> {
+        let idx = self.current_player_idx();
+        if amount == self.players_bet(idx) && self.to_call(idx) == 0 {
+            self.act(Action::Check)
+        } else if amount == self.current_bet() {
+            self.act(Action::Call)
+        } else if amount > self.current_bet() && self.current_bet() == 0 {
+            self.act(Action::Bet(amount))
+        } else if amount > self.current_bet() {
+            self.act(Action::Raise(amount))
+        } else if amount >= self.max_bet(idx) {
+            // Calling all-in for less than the current bet
+            self.act(Action::Call)
+        } else {
+            bail!(
+                "Bet of {} does not call the current bet of {}",
+                amount,
+                self.current_bet()
+            )
+        }
     }
 
     pub fn fold(&mut self) {
+        self.record_action(self.current_player_idx(), HandActionKind::Fold, 0);
         self.state.fold();
         self.advance();
     }
 
+    /// What the current player does when they run out of time: check if it's
+    /// free, otherwise fold
+    pub fn timeout_action(&self) -> Action {
+        if self.to_call(self.current_player_idx()) == 0 {
+            Action::Check
+        } else {
+            Action::Fold
+        }
+    }
+
+    /// Folds a player whether or not it is their turn, e.g. when they leave the table
+    pub fn fold_player(&mut self, player_idx: usize) -> Result<()> {
+        if self.is_over() {
+            bail!("Game is not active");
+        }
+        if !self.state.player_active.get(player_idx) {
+            bail!("Player is not in the hand");
+        }
+        if player_idx == self.current_player_idx() {
+            self.fold();
+            return Ok(());
+        }
+        self.record_action(player_idx, HandActionKind::Fold, 0);
+        self.state.player_active.disable(player_idx);
+        self.state
+            .mut_current_round_data()
+            .player_active
+            .disable(player_idx);
+        self.advance();
+        Ok(())
+    }
+
     pub fn is_over(&self) -> bool {
         self.state.round == Round::Complete
     }
 
+    /// Drains the events that happened since the last call
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Players who have not folded, including those who are all-in
+    pub fn contenders(&self) -> PlayerBitSet {
+        self.state.player_active | self.state.player_all_in
+    }
+
     fn check_fold(&mut self) {
-        if self.players_bet(self.current_player_idx()) == self.current_bet() {
-            self.bet(self.current_bet())
-                .expect("Check / Fold should work");
+        if self.to_call(self.current_player_idx()) == 0 {
+            self.act(Action::Check).expect("Check / Fold should work");
         } else {
             self.fold();
         }
     }
 
     fn call_any(&mut self) {
-        let current_bet = self.current_bet() as i32;
-        self.bet(current_bet as ChipInt)
-            .expect("Call any should be valid");
+        let action = if self.to_call(self.current_player_idx()) == 0 {
+            Action::Check
+        } else {
+            Action::Call
+        };
+        self.act(action).expect("Call any should be valid");
     }
 
     fn advance(&mut self) {
+        if self.is_over() {
+            return;
+        }
+
         // If last action was a fold to end the game, just complete
         if self.is_complete() {
             self.complete();
             return;
         }
 
+        // If last action ended the betting round, advance then check complete.
+        // When everyone left is all-in, keep dealing until showdown.
+        while self.is_betting_closed() {
+            if self.state.num_active_players() <= 1
+                && self.state.board.len() < 5
+                && !self.events.contains(&GameEvent::RunOut)
+            {
+                self.events.push(GameEvent::RunOut);
+            }
+            self.advance_round();
+            if self.is_complete() {
+                self.complete();
+                return;
+            }
+        }
+
         // Check if next player has an auto-action and execute it
         // This will make a recursive call back to this advance() function, therefore we return
         let current_player_idx = self.current_player_idx();
@@ -172,16 +559,129 @@ impl Game {
             .call_any
         {
             self.call_any();
+        }
+    }
+
+    /// Betting is closed when nobody is left to act, or when at most one player
+    /// is not all-in and they have already matched the current bet
+    fn is_betting_closed(&self) -> bool {
+        let round = self.state.current_round_data();
+        if round.player_active.empty() {
+            return true;
+        }
+        match self.state.num_active_players() {
+            0 => true,
+            1 => self
+                .state
+                .player_active
+                .ones()
+                .all(|idx| round.player_bet[idx] >= round.bet),
+            _ => false,
+        }
+    }
+
+    /// Starts preflop betting with the blinds where the table put them, rather
+    /// than always left of the dealer, so a small blind can be dead
+    fn post_blinds(&mut self, blinds: Blinds) {
+        let num_players = self.players.len();
+        self.state.round = Round::Preflop;
+        self.state.round_data.push(RoundData {
+            player_active: self.state.player_active,
+            min_raise: self.state.big_blind,
+            bet: 0,
+            player_bet: vec![0; num_players],
+            bet_count: vec![0; num_players],
+            raise_count: vec![0; num_players],
+            to_act_idx: blinds.big_blind,
+        });
+        if let Some(idx) = blinds.small_blind {
+            self.state.mut_current_round_data().to_act_idx = idx;
+            let small_blind = self.state.small_blind;
+            let posted = self
+                .state
+                .do_bet(small_blind, true)
+                .expect("Forced bets are always valid");
+            self.record_action(idx, HandActionKind::SmallBlind, posted as ChipInt);
+        }
+        // Action starts left of the big blind
+        self.state.mut_current_round_data().to_act_idx = blinds.big_blind;
+        let big_blind = self.state.big_blind;
+        let posted = self
+            .state
+            .do_bet(big_blind, true)
+            .expect("Forced bets are always valid");
+        self.record_action(
+            blinds.big_blind,
+            HandActionKind::BigBlind,
+            posted as ChipInt,
+        );
+        self.post_antes(blinds);
+        // Still left of the big blind, past anyone the ante put all-in
+        let round = self.state.mut_current_round_data();
+        round.to_act_idx = blinds.big_blind;
+        round.advance();
+    }
+
+    /// Collects the ante from whoever pays it, out of what is left once the
+    /// blinds are in. It goes into the pot without counting towards anyone's
+    /// bet for the round.
+    fn post_antes(&mut self, blinds: Blinds) {
+        let Some(ante) = blinds.ante else {
             return;
+        };
+        let paying = match ante.kind {
+            AnteKind::Classic => (0..self.players.len()).collect(),
+            AnteKind::BigBlind => vec![blinds.big_blind],
+            AnteKind::Button => vec![blinds.dealer],
+        };
+        for idx in paying {
+            let amount = self.take_chips(idx, ante.amount as i32);
+            self.record_action(idx, HandActionKind::Ante, amount as ChipInt);
         }
+    }
 
-        // If last action ended the betting round, advance then check complete
-        if self.state.current_round_data().player_active.empty() {
-            self.advance_round();
-            if self.is_complete() {
-                self.complete();
+    /// Takes a big blind from each player entering out of position. The post is
+    /// live, so it counts towards calling and they still get to check.
+    fn post_big_blinds(&mut self) {
+        let big_blind = self.state.big_blind;
+        for idx in 0..self.players.len() {
+            if !self.players[idx].posts_big_blind {
+                continue;
             }
+            let amount = self.take_chips(idx, big_blind);
+            self.state.mut_current_round_data().player_bet[idx] += amount;
+            self.record_action(idx, HandActionKind::BigBlind, amount as ChipInt);
+        }
+    }
+
+    /// Puts up to `amount` of the player's stack into the pot outside of
+    /// betting, returning what they put in
+    fn take_chips(&mut self, idx: usize, amount: i32) -> i32 {
+        let amount = amount.min(self.state.stacks[idx]);
+        self.state.stacks[idx] -= amount;
+        self.state.player_bet[idx] += amount;
+        self.state.total_pot += amount;
+        if self.state.stacks[idx] == 0 {
+            self.state.player_active.disable(idx);
+            self.state.player_all_in.enable(idx);
+            self.state
+                .mut_current_round_data()
+                .player_active
+                .disable(idx);
         }
+        amount
+    }
+
+    fn record_action(&mut self, idx: usize, kind: HandActionKind, amount: ChipInt) {
+        let all_in = kind != HandActionKind::Fold && self.players_stack(idx) == 0;
+        self.actions.push(HandAction {
+            street: Street::from(self.state.round),
+            seat: self.players[idx].seat,
+            kind,
+            amount,
+            total: self.players_bet(idx),
+            all_in,
+        });
     }
 
     fn advance_round(&mut self) {
@@ -197,54 +697,164 @@ impl Game {
                 .state
                 .board
                 .push(self.deck.deal().expect("Deck should not be empty")),
-            _ => (),
+            _ => return,
         }
+        self.last_aggressor = None;
+        self.events
+            .push(GameEvent::CommunityCards(self.state.board.clone()));
     }
 
     fn is_complete(&self) -> bool {
-        self.state.is_complete()
+        self.contenders().count() == 1 || self.state.round == Round::Showdown
     }
 
     fn complete(&mut self) {
         self.state.complete();
-        match self.state.num_active_players() {
-            0 => panic!("No active players when game is complete"),
-            1 => {
-                if let Some(winner_idx) = self.state.player_active.ones().next() {
-                    self.state.award(winner_idx, self.state.total_pot)
+        if self.contenders().empty() {
+            panic!("No active players when game is complete");
+        }
+
+        debug!("Community Cards {:?}", self.state.board);
+        let ranks = if self.contenders().count() > 1 {
+            self.rank_active_players()
+        } else {
+            vec![]
+        };
+        debug!("Players Ranks: {:?}", ranks);
+
+        let mut pots = self.build_pots();
+        for pot in &mut pots {
+            let mut winners: Vec<usize> = match pot.eligible.as_slice() {
+                [only] => vec![*only],
+                eligible => {
+                    let best = ranks
+                        .iter()
+                        .filter(|(_, idx)| eligible.contains(idx))
+                        .map(|(rank, _)| rank)
+                        .max()
+                        .expect("Pot should have eligible players");
+                    ranks
+                        .iter()
+                        .filter(|(rank, idx)| eligible.contains(idx) && rank == best)
+                        .map(|(_, idx)| *idx)
+                        .collect()
                 }
+            };
+
+            // Odd chips go to the winners closest to the left of the dealer
+            winners.sort_by_key(|idx| self.seat_order(*idx));
+            let share = pot.amount / winners.len() as ChipInt;
+            let odd_chips = pot.amount % winners.len() as ChipInt;
+            for (i, winner_idx) in winners.into_iter().enumerate() {
+                let won = share + if (i as ChipInt) < odd_chips { 1 } else { 0 };
+                self.state.award(winner_idx, won as i32);
+                pot.winners.push((winner_idx, won));
             }
-            _ => {
-                let ranks = self.rank_active_players();
-                debug!("Community Cards {:?}", self.state.board);
-                debug!("Players Ranks: {:?}", ranks);
-                // TODO: Handle ties + side pots
-                self.state.award(
-                    ranks.first().expect("Should be at least 2 players").1,
-                    self.state.total_pot,
-                )
+        }
+        debug!("Pots: {:?}", pots);
+        self.pots = pots;
+        self.ranks = ranks;
+    }
+
+    /// Whether the hand was decided by comparing cards rather than everyone folding
+    pub fn went_to_showdown(&self) -> bool {
+        !self.ranks.is_empty()
+    }
+
+    /// Whether all the hands were turned up because everyone left was all-in
+    pub fn was_run_out(&self) -> bool {
+        self.went_to_showdown() && self.state.player_active.count() <= 1
+    }
+
+    /// Order in which hands are shown: the last aggressor on the river first,
+    /// or the first player left of the dealer if it was checked down, then clockwise
+    pub fn showdown_order(&self) -> Vec<usize> {
+        let num_players = self.players.len();
+        let contenders = self.contenders();
+        let first = self
+            .last_aggressor
+            .filter(|idx| contenders.get(*idx))
+            .unwrap_or((self.state.dealer_idx + 1) % num_players);
+        (0..num_players)
+            .map(|i| (first + i) % num_players)
+            .filter(|idx| contenders.get(*idx))
+            .collect()
+    }
+
+    /// Whether the player was awarded chips from any pot
+    pub fn is_winner(&self, player_idx: usize) -> bool {
+        self.pots
+            .iter()
+            .any(|pot| pot.winners.iter().any(|(idx, _)| *idx == player_idx))
+    }
+
+    pub fn player_idx(&self, player: &Player) -> Option<usize> {
+        self.players.iter().position(|p| p.info.id == player.id)
+    }
+
+    /// Splits every player's total contribution into a main pot and side pots.
+    /// Each pot is capped at the contribution of an all-in contender, and only
+    /// contenders who covered that cap are eligible to win it.
+    fn build_pots(&self) -> Vec<Pot> {
+        let contributions = &self.state.player_bet;
+        let contenders = self.contenders();
+
+        let mut levels: Vec<i32> = contenders.ones().map(|idx| contributions[idx]).collect();
+        levels.sort_unstable();
+        levels.dedup();
+
+        let mut pots: Vec<Pot> = vec![];
+        let mut prev_level = 0;
+        for level in levels {
+            let amount: i32 = contributions
+                .iter()
+                .map(|c| (*c).clamp(prev_level, level) - prev_level)
+                .sum();
+            if amount > 0 {
+                pots.push(Pot {
+                    amount: amount as ChipInt,
+                    eligible: contenders
+                        .ones()
+                        .filter(|idx| contributions[*idx] >= level)
+                        .collect(),
+                    winners: vec![],
+                });
             }
+            prev_level = level;
+        }
+
+        // Folded players can't have put in more than the largest contender, but
+        // make sure no chips are ever left behind
+        let remainder: i32 = contributions.iter().map(|c| (c - prev_level).max(0)).sum();
+        if let Some(last) = pots.last_mut() {
+            last.amount += remainder as ChipInt;
         }
+
+        pots
+    }
+
+    /// Position of a player counting clockwise from the seat left of the dealer
+    fn seat_order(&self, player_idx: usize) -> usize {
+        let num_players = self.players.len();
+        (player_idx + num_players - self.state.dealer_idx - 1) % num_players
+    }
+
+    /// The best hand the player makes with the board dealt so far
+    pub fn rank_hand(&self, player_idx: usize) -> Rank {
+        let hand = self
+            .state
+            .hands
+            .get(player_idx)
+            .expect("Player should have a hand");
+        self.variant.rank(&hand[..], &self.state.board)
     }
 
     fn rank_active_players(&self) -> Vec<(Rank, usize)> {
         let mut ranks = self
-            .state
-            .player_active
+            .contenders()
             .ones()
-            .map(|idx| {
-                let mut hand = self
-                    .state
-                    .hands
-                    .get(idx)
-                    .expect("Player should have a hand")
-                    .clone();
-
-                // Add community cards
-                hand.extend(self.state.board.clone());
-                hand.rank()
-            })
-            .zip(self.state.player_active.ones())
+            .map(|idx| self.rank_hand(idx))
+            .zip(self.contenders().ones())
             .collect::<Vec<_>>();
         ranks.sort_unstable_by(|r1, r2| r2.0.cmp(&r1.0));
 
@@ -252,32 +862,216 @@ impl Game {
     }
 }
 
+/// Human readable hand category, e.g. "Flush, Ace high"
+pub fn describe_rank(rank: &Rank) -> String {
+    // Ranks hold bitsets of card values, with the major values shifted above the kickers
+    fn highest(bits: u32) -> Value {
+        Value::from_u8((31 - bits.leading_zeros()) as u8)
+    }
+    fn name(value: Value) -> &'static str {
+        match value {
+            Value::Two => "Two",
+            Value::Three => "Three",
+            Value::Four => "Four",
+            Value::Five => "Five",
+            Value::Six => "Six",
+            Value::Seven => "Seven",
+            Value::Eight => "Eight",
+            Value::Nine => "Nine",
+            Value::Ten => "Ten",
+            Value::Jack => "Jack",
+            Value::Queen => "Queen",
+            Value::King => "King",
+            Value::Ace => "Ace",
+        }
+    }
+    fn plural(value: Value) -> String {
+        match value {
+            Value::Six => "Sixes".to_string(),
+            value => format!("{}s", name(value)),
+        }
+    }
+
+    match *rank {
+        Rank::HighCard(bits) => format!("High card, {}", name(highest(bits))),
+        Rank::OnePair(bits) => format!("Pair of {}", plural(highest(bits >> 13))),
+        Rank::TwoPair(bits) => {
+            let high = highest(bits >> 13);
+            let low = highest((bits >> 13) ^ (1 << high as u32));
+            format!("Two pair, {} and {}", plural(high), plural(low))
+        }
+        Rank::ThreeOfAKind(bits) => format!("Three of a kind, {}", plural(highest(bits >> 13))),
+        // Straights are ranked by their lowest card, with the wheel as 0
+        Rank::Straight(low) => format!("Straight, {} high", name(Value::from_u8(low as u8 + 3))),
+        Rank::Flush(bits) => format!("Flush, {} high", name(highest(bits))),
+        Rank::FullHouse(bits) => format!(
+            "Full house, {} full of {}",
+            plural(highest(bits >> 13)),
+            plural(highest(bits & 0x1FFF))
+        ),
+        Rank::FourOfAKind(bits) => format!("Four of a kind, {}", plural(highest(bits >> 13))),
+        Rank::StraightFlush(9) => "Royal flush".to_string(),
+        Rank::StraightFlush(low) => {
+            format!(
+                "Straight flush, {} high",
+                name(Value::from_u8(low as u8 + 3))
+            )
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::test_util::*;
+    use rs_poker::core::Rankable;
     use test_log::test;
 
+    /// Players with 100 chips each, sitting in the seats matching their indexes
+    fn test_players(num_players: usize) -> Vec<GamePlayer> {
+        (0..num_players)
+            .map(|i| {
+                let mut player: GamePlayer =
+                    TablePlayer::new(Player::new(i.to_string(), i.to_string()), 100).into();
+                player.seat = i;
+                player
+            })
+            .collect()
+    }
+
+    /// Builds a game that has reached showdown with the given hole cards, board
+    /// and total contributions. A `None` hand means the player folded.
+    fn showdown_game(hands: &[Option<&str>], board: &str, contributions: &[i32]) -> Game {
+        let players = test_players(hands.len());
+        let mut game = Game::new(
+            "test".to_string(),
+            players,
+            Blinds::from_dealer(0, hands.len()),
+            1,
+            2,
+            GameVariant::Holdem,
+        );
+        let mut state = GameState::new(vec![0; hands.len()], 2, 1, 0);
+        state.round = Round::Showdown;
+        state.player_active = PlayerBitSet::default();
+        state.player_bet = contributions.to_vec();
+        state.total_pot = contributions.iter().sum();
+        for (idx, hand) in hands.iter().enumerate() {
+            if let Some(hand) = hand {
+                state.player_all_in.enable(idx);
+                state.hands[idx] = Hand::new_from_str(hand).unwrap();
+            }
+        }
+        state.board = Hand::new_from_str(board).unwrap()[..].to_vec();
+        game.state = state;
+        game
+    }
+
+    #[test]
+    fn test_short_stack_only_wins_main_pot() {
+        // Player 0 is all-in for 20 with the best hand, 1 and 2 play a side pot
+        let mut game = showdown_game(
+            &[Some("AsAd"), Some("KsKd"), Some("QsQd")],
+            "2c7h9dTc3s",
+            &[20, 100, 100],
+        );
+        game.complete();
+
+        assert_eq!(game.pots.len(), 2);
+        assert_eq!(game.pots[0].amount, 60);
+        assert_eq!(game.pots[0].winners, vec![(0, 60)]);
+        assert_eq!(game.pots[1].amount, 160);
+        assert_eq!(game.pots[1].eligible, vec![1, 2]);
+        assert_eq!(game.pots[1].winners, vec![(1, 160)]);
+        assert_eq!(game.state.stacks, vec![60, 160, 0]);
+    }
+
+    #[test]
+    fn test_folded_chips_go_to_pots() {
+        // Player 1 folded after putting in 50
+        let mut game = showdown_game(
+            &[Some("AsAd"), None, Some("QsQd")],
+            "2c7h9dTc3s",
+            &[30, 50, 80],
+        );
+        game.complete();
+
+        assert_eq!(game.pots.len(), 2);
+        assert_eq!(game.pots[0].amount, 90);
+        assert_eq!(game.pots[0].winners, vec![(0, 90)]);
+        // Uncalled chips go back to the only eligible player
+        assert_eq!(game.pots[1].amount, 70);
+        assert_eq!(game.pots[1].winners, vec![(2, 70)]);
+        assert_eq!(game.state.stacks.iter().sum::<i32>(), 160);
+    }
+
+    #[test]
+    fn test_split_pot_odd_chip_by_seat_order() {
+        // Both players play the board, dealer is seat 0 so seat 1 gets the odd chip
+        let mut game = showdown_game(
+            &[Some("2s3d"), Some("2h3c"), None],
+            "AcKhQdJcTs",
+            &[10, 10, 1],
+        );
+        game.complete();
+
+        assert_eq!(game.pots.len(), 1);
+        assert_eq!(game.pots[0].winners, vec![(1, 11), (0, 10)]);
+        assert_eq!(game.state.stacks, vec![10, 11, 0]);
+    }
+
+    #[test]
+    fn test_all_in_runs_out_board() {
+        let mut game = Game::new(
+            "test".to_string(),
+            test_players(2),
+            Blinds::from_dealer(0, 2),
+            1,
+            2,
+            GameVariant::Holdem,
+        );
+
+        // Small blind shoves, big blind calls
+        game.bet(100).unwrap();
+        assert!(!game.is_over());
+        game.bet(100).unwrap();
+
+        assert!(game.is_over());
+        assert_eq!(game.state.board.len(), 5);
+        let board = game.state.board.clone();
+        assert_eq!(
+            game.take_events(),
+            vec![
+                GameEvent::RunOut,
+                GameEvent::CommunityCards(board[..3].to_vec()),
+                GameEvent::CommunityCards(board[..4].to_vec()),
+                GameEvent::CommunityCards(board.clone()),
+            ]
+        );
+        let awarded: ChipInt = game
+            .pots
+            .iter()
+            .flat_map(|pot| pot.winners.iter().map(|(_, won)| won))
+            .sum();
+        assert_eq!(awarded, 200);
+    }
+
     #[test]
     fn test_game() -> Result<()> {
         let mut table = Table::default();
         let player_id = Address::default().to_string();
         let username = player_id.clone();
         let player = Player::new(player_id, username);
-        table.players = (0..table.max_players())
-            .map(|_| player.clone().into())
+        table.seats = (0..table.max_players())
+            .map(|_| Some(TablePlayer::new(player.clone(), *DEFAULT_CHIPS)))
             .collect();
-        let dealer_idx = 0;
         let mut game = Game::new(
             table.id().clone(),
-            table
-                .players
-                .clone()
-                .into_iter()
-                .map(GamePlayer::from)
-                .collect(),
-            dealer_idx,
+            table.players().cloned().map(GamePlayer::from).collect(),
+            Blinds::from_dealer(0, table.max_players()),
             table.small_blind(),
             table.big_blind(),
+            GameVariant::Holdem,
         );
 
         // Advance from start -> preflop state and take the blinds
@@ -351,4 +1145,388 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_action_validation() {
+        // Dealer 0, small blind 1, big blind 2, player 0 is first to act
+        let mut game = Game::new(
+            "test".to_string(),
+            test_players(3),
+            Blinds::from_dealer(0, 3),
+            1,
+            2,
+            GameVariant::Holdem,
+        );
+        assert_eq!(game.current_player_idx(), 0);
+        assert_eq!(game.to_call(0), 2);
+
+        let err = game.act(Action::Check).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot check, 2 to call");
+        let err = game.act(Action::Bet(6)).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot bet, there is already a bet of 2");
+        let err = game.act(Action::Raise(3)).unwrap_err();
+        assert_eq!(err.to_string(), "Minimum is 4 unless going all-in for 100");
+        let err = game.act(Action::Raise(101)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Not enough chips, the most you can bet is 100"
+        );
+
+        assert_eq!(game.act(Action::Raise(6)).unwrap(), 6);
+        assert_eq!(game.min_raise_to(), 10);
+        // Small blind calls the raise by putting their total bet at it, which
+        // as a `Bet` would fail
+        assert_eq!(game.act(Action::BetTo(6)).unwrap(), 5);
+        // Big blind goes all-in
+        assert_eq!(game.act(Action::AllIn).unwrap(), 98);
+        assert_eq!(game.to_call(0), 94);
+        assert_eq!(game.act(Action::Call).unwrap(), 94);
+        game.act(Action::Fold).unwrap();
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn test_fold_player_out_of_turn() {
+        let mut game = Game::new(
+            "test".to_string(),
+            test_players(3),
+            Blinds::from_dealer(0, 3),
+            1,
+            2,
+            GameVariant::Holdem,
+        );
+        // Big blind leaves while player 0 is still to act
+        game.fold_player(2).unwrap();
+        assert_eq!(game.current_player_idx(), 0);
+        let err = game.fold_player(2).unwrap_err();
+        assert_eq!(err.to_string(), "Player is not in the hand");
+
+        game.act(Action::Call).unwrap();
+        game.act(Action::Call).unwrap();
+        assert_eq!(game.state.round, Round::Flop);
+        assert_eq!(game.current_player_idx(), 1);
+
+        game.fold_player(0).unwrap();
+        assert!(game.is_over());
+        assert!(game.is_winner(1));
+        assert_eq!(game.state.stacks, vec![98, 104, 98]);
+    }
+
+    #[test]
+    fn test_timeout_action() {
+        let mut game = Game::new(
+            "test".to_string(),
+            test_players(3),
+            Blinds::from_dealer(0, 3),
+            1,
+            2,
+            GameVariant::Holdem,
+        );
+        assert_eq!(game.timeout_action(), Action::Fold);
+        game.act(Action::Call).unwrap();
+        game.act(Action::Call).unwrap();
+        // The big blind has the option to check
+        assert_eq!(game.current_player_idx(), 2);
+        assert_eq!(game.timeout_action(), Action::Check);
+    }
+
+    #[test]
+    fn test_check_and_call_after_flop() {
+        let mut game = Game::new(
+            "test".to_string(),
+            test_players(2),
+            Blinds::from_dealer(0, 2),
+            1,
+            2,
+            GameVariant::Holdem,
+        );
+        game.act(Action::Call).unwrap();
+        let err = game.act(Action::Call).unwrap_err();
+        assert_eq!(err.to_string(), "Nothing to call, check instead");
+        game.act(Action::Check).unwrap();
+        assert_eq!(game.state.round, Round::Flop);
+
+        let err = game.act(Action::Raise(4)).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot raise, there is no bet to raise");
+        game.act(Action::Bet(4)).unwrap();
+        game.act(Action::Call).unwrap();
+        assert_eq!(game.state.round, Round::Turn);
+        assert_eq!(game.state.total_pot, 12);
+    }
+
+    #[test]
+    fn test_describe_rank() {
+        let describe = |cards: &str| describe_rank(&Hand::new_from_str(cards).unwrap().rank());
+        assert_eq!(describe("As9d7c4h2s3dJh"), "High card, Ace");
+        assert_eq!(describe("KsKd7c4h2s3dJh"), "Pair of Kings");
+        assert_eq!(describe("KsKd5c5h2s3dJh"), "Two pair, Kings and Fives");
+        assert_eq!(describe("6s6d6c4h2s9dJh"), "Three of a kind, Sixes");
+        assert_eq!(describe("As2d3c4h5s9dJh"), "Straight, Five high");
+        assert_eq!(describe("9s8d7c6h5s2dJh"), "Straight, Nine high");
+        assert_eq!(describe("AhKh9h4h2h3dJs"), "Flush, Ace high");
+        assert_eq!(
+            describe("KsKdKc5h5s3dJh"),
+            "Full house, Kings full of Fives"
+        );
+        assert_eq!(describe("AsAdAcAh5s3dJh"), "Four of a kind, Aces");
+        assert_eq!(describe("9h8h7h6h5h2dJs"), "Straight flush, Nine high");
+        assert_eq!(describe("AsKsQsJsTs2d3h"), "Royal flush");
+    }
+
+    #[test]
+    fn test_showdown_order() {
+        let mut game = Game::new(
+            "test".to_string(),
+            test_players(4),
+            Blinds::from_dealer(0, 4),
+            1,
+            2,
+            GameVariant::Holdem,
+        );
+        // Preflop: 3 calls, dealer folds, blinds complete and check
+        game.act(Action::Call).unwrap();
+        game.act(Action::Fold).unwrap();
+        game.act(Action::Call).unwrap();
+        game.act(Action::Check).unwrap();
+
+        // Flop is checked down, showdown would start left of the dealer
+        for _ in 0..3 {
+            game.act(Action::Check).unwrap();
+        }
+        assert_eq!(game.last_aggressor, None);
+        assert_eq!(game.showdown_order(), vec![1, 2, 3]);
+
+        // Turn: big blind bets and is called
+        game.act(Action::Check).unwrap();
+        game.act(Action::Bet(10)).unwrap();
+        game.act(Action::Call).unwrap();
+        game.act(Action::Call).unwrap();
+        assert_eq!(game.state.round, Round::River);
+
+        // River: small blind checks, big blind bets, everyone calls
+        game.act(Action::Check).unwrap();
+        game.act(Action::Bet(10)).unwrap();
+        game.act(Action::Call).unwrap();
+        game.act(Action::Call).unwrap();
+        assert!(game.is_over());
+        assert_eq!(game.last_aggressor, Some(2));
+        assert_eq!(game.showdown_order(), vec![2, 3, 1]);
+        assert!(!game.was_run_out());
+    }
+
+    /// Deals the cards in order: hole cards around the table twice, then the board
+    fn scripted_game(num_players: usize, variant: GameVariant, stacked: &str) -> Game {
+        Game::with_source(
+            "test".to_string(),
+            test_players(num_players),
+            Blinds::from_dealer(0, num_players),
+            1,
+            2,
+            variant,
+            DeckSource::stacked(cards(stacked)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_flush_on_the_river_beats_two_pair() {
+        // Player 0 has two hearts, player 1 flops two pair
+        let mut game = scripted_game(2, GameVariant::Holdem, "QhAsJhKdAh7hKc2d3h");
+        game.act(Action::Call).unwrap();
+        game.act(Action::Check).unwrap();
+        game.act(Action::Check).unwrap();
+        game.act(Action::Check).unwrap();
+        assert_eq!(game.state.board, cards("Ah7hKc2d"));
+
+        game.act(Action::Check).unwrap();
+        game.act(Action::Check).unwrap();
+        game.act(Action::Bet(10)).unwrap();
+        game.act(Action::Call).unwrap();
+        assert!(game.is_over());
+        assert_eq!(game.state.board, cards("Ah7hKc2d3h"));
+        let described: Vec<_> = game
+            .ranks
+            .iter()
+            .map(|(rank, idx)| (*idx, describe_rank(rank)))
+            .collect();
+        assert_eq!(
+            described,
+            [
+                (0, "Flush, Ace high".to_owned()),
+                (1, "Two pair, Aces and Kings".to_owned())
+            ]
+        );
+        assert_eq!(game.pots[0].winners, vec![(0, 24)]);
+        assert_eq!(game.state.stacks, vec![112, 88]);
+    }
+
+    #[test]
+    fn test_split_pot_on_a_board_straight() {
+        // Neither hand plays, so the pot is chopped
+        let mut game = scripted_game(3, GameVariant::Holdem, "2s3d4h2c3s4d9hTdJcQsKh");
+        game.act(Action::Call).unwrap();
+        game.act(Action::Fold).unwrap();
+        game.act(Action::Check).unwrap();
+        while !game.is_over() {
+            game.act(Action::Check).unwrap();
+        }
+        assert_eq!(game.pots[0].amount, 5);
+        // The small blind folded, so the big blind is the first winner left
+        // of the dealer and gets the odd chip
+        assert_eq!(game.pots[0].winners, vec![(2, 3), (0, 2)]);
+        assert_eq!(describe_rank(&game.ranks[0].0), "Straight, King high");
+    }
+
+    #[test]
+    fn test_omaha_needs_two_hole_cards() {
+        // Alice holds the only heart in the deal, which would make a flush in
+        // Hold'em, and Bob a pair of queens
+        let mut game = scripted_game(2, GameVariant::Plo, "AhQsKcQd7d3c8s3dJh9h5h4h2s");
+        assert!(game.state.hands.iter().all(|hand| hand.len() == 4));
+        game.act(Action::Call).unwrap();
+        while !game.is_over() {
+            game.act(Action::Check).unwrap();
+        }
+        assert_eq!(game.pots[0].winners, vec![(1, 4)]);
+        let ranks: Vec<_> = game
+            .ranks
+            .iter()
+            .map(|(rank, idx)| (*idx, describe_rank(rank)))
+            .collect();
+        assert_eq!(
+            ranks,
+            [
+                (1, "Pair of Queens".to_string()),
+                (0, "High card, Ace".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pot_limit() {
+        let mut game = scripted_game(3, GameVariant::Plo5, "");
+        assert!(game.state.hands.iter().all(|hand| hand.len() == 5));
+
+        // Calling the big blind makes the pot 5, so the most is a raise to 7
+        assert_eq!(game.raise_range(0), Some((4, 7)));
+        assert!(game.act(Action::Raise(8)).is_err());
+        assert!(game.act(Action::AllIn).is_err());
+        game.act(Action::Raise(7)).unwrap();
+        // The small blind calls 6 into a pot of 10, then can raise 16 more
+        assert_eq!(game.raise_range(1), Some((12, 23)));
+        game.act(Action::Call).unwrap();
+        game.act(Action::Call).unwrap();
+
+        assert_eq!(game.raise_range(1), Some((2, 21)));
+        assert!(game.act(Action::Bet(22)).is_err());
+        game.act(Action::Bet(21)).unwrap();
+        assert_eq!(game.raise_range(2), Some((42, 84)));
+        game.act(Action::Raise(84)).unwrap();
+        // Raising all-in is allowed within the limit
+        assert_eq!(game.raise_range(0), Some((93, 93)));
+        game.act(Action::AllIn).unwrap();
+    }
+
+    #[test]
+    fn test_fixed_limit() {
+        let mut game = scripted_game(3, GameVariant::Holdem, "");
+        game.betting = BettingStructure::FixedLimit {
+            small_bet: 2,
+            big_bet: 4,
+            raise_cap: 2,
+        };
+
+        // Preflop the big blind is the bet, so two raises cap it at 6
+        assert_eq!(game.raise_range(0), Some((4, 4)));
+        assert!(game.act(Action::Raise(5)).is_err());
+        game.act(Action::Raise(4)).unwrap();
+        game.act(Action::Raise(6)).unwrap();
+        assert_eq!(game.raise_range(2), None);
+        assert!(game.act(Action::Raise(8)).is_err());
+        assert!(game.act(Action::AllIn).is_err());
+        game.act(Action::Call).unwrap();
+        game.act(Action::Call).unwrap();
+
+        // Small bets on the flop
+        assert_eq!(game.state.board.len(), 3);
+        assert!(game.act(Action::Bet(4)).is_err());
+        game.act(Action::Bet(2)).unwrap();
+        game.act(Action::Raise(4)).unwrap();
+        game.act(Action::Raise(6)).unwrap();
+        assert_eq!(game.raise_range(1), None);
+        game.act(Action::Call).unwrap();
+        game.act(Action::Call).unwrap();
+
+        // Big bets on the turn and river
+        assert_eq!(game.state.board.len(), 4);
+        assert_eq!(game.raise_range(1), Some((4, 4)));
+        assert!(game.act(Action::Bet(2)).is_err());
+        game.act(Action::Bet(4)).unwrap();
+        assert_eq!(game.raise_range(2), Some((8, 8)));
+    }
+
+    #[test]
+    fn test_antes() {
+        let game_with_ante = |kind, amount, players: Vec<GamePlayer>| {
+            let blinds = Blinds {
+                ante: Some(Ante { kind, amount }),
+                ..Blinds::from_dealer(0, players.len())
+            };
+            Game::with_source(
+                "test".to_string(),
+                players,
+                blinds,
+                1,
+                2,
+                GameVariant::Holdem,
+                DeckSource::stacked(vec![]).unwrap(),
+            )
+        };
+        let stacks = |game: &Game| {
+            (0..3)
+                .map(|idx| game.players_stack(idx))
+                .collect::<Vec<_>>()
+        };
+
+        let game = game_with_ante(AnteKind::Classic, 1, test_players(3));
+        assert_eq!(stacks(&game), [99, 98, 97]);
+        assert_eq!(game.state.total_pot, 6);
+        // Antes are dead, so the blinds are still all there is to call
+        assert_eq!(game.to_call(0), 2);
+        let kinds: Vec<_> = game.actions.iter().map(|action| action.kind).collect();
+        assert_eq!(
+            kinds[..2],
+            [HandActionKind::SmallBlind, HandActionKind::BigBlind]
+        );
+        assert_eq!(kinds[2..], [HandActionKind::Ante; 3]);
+
+        let game = game_with_ante(AnteKind::BigBlind, 3, test_players(3));
+        assert_eq!(stacks(&game), [100, 99, 95]);
+        assert_eq!(game.state.total_pot, 6);
+        let game = game_with_ante(AnteKind::Button, 3, test_players(3));
+        assert_eq!(stacks(&game), [97, 99, 98]);
+
+        // An ante can put a short stack all-in, so they only win what they
+        // matched from everyone else
+        let mut players = test_players(3);
+        players[0].chips = 1;
+        let mut game = game_with_ante(AnteKind::Classic, 2, players);
+        assert_eq!(game.current_player_idx(), 1);
+        game.act(Action::Fold).unwrap();
+        assert!(game.is_over());
+        let pots: Vec<_> = game.pots.iter().map(|pot| pot.amount).collect();
+        assert_eq!(pots, [3, 5]);
+        assert_eq!(game.pots[1].winners, vec![(2, 5)]);
+
+        // A big blind too short for blind and ante posts the whole blind and
+        // antes what is left
+        let mut players = test_players(3);
+        players[2].chips = 3;
+        let game = game_with_ante(AnteKind::BigBlind, 2, players);
+        assert_eq!(stacks(&game), [100, 99, 0]);
+        assert_eq!(game.state.total_pot, 4);
+        assert_eq!(game.to_call(0), 2);
+        assert_eq!(game.state.current_round_data().min_raise, 2);
+        let ante = game.actions.last().unwrap();
+        assert_eq!((ante.kind, ante.amount), (HandActionKind::Ante, 1));
+    }
 }
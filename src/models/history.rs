@@ -0,0 +1,1024 @@
+use crate::*;
+use rs_poker::arena::game_state::Round;
+use rs_poker::core::Card;
+use std::fmt::Write;
+
+pub type HandId = u64;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Street {
+    Preflop,
+    Flop,
+    Turn,
+    River,
+}
+
+impl From<Round> for Street {
+    fn from(round: Round) -> Self {
+        match round {
+            Round::Starting | Round::Preflop => Street::Preflop,
+            Round::Flop => Street::Flop,
+            Round::Turn => Street::Turn,
+            Round::River | Round::Showdown | Round::Complete => Street::River,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HandActionKind {
+    Ante,
+    SmallBlind,
+    BigBlind,
+    Check,
+    Call,
+    Bet,
+    Raise,
+    Fold,
+}
+
+/// A blind posted or an action taken during a hand
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HandAction {
+    pub street: Street,
+    pub seat: usize,
+    #[serde(rename = "action")]
+    pub kind: HandActionKind,
+    /// Chips put in by the action
+    pub amount: ChipInt,
+    /// The player's total bet on the street afterwards
+    pub total: ChipInt,
+    pub all_in: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistorySeat {
+    pub seat: usize,
+    pub player: Player,
+    /// Stack at the start of the hand
+    pub stack: ChipInt,
+    /// Hole cards, left out of other players' copies unless shown
+    pub cards: Vec<Card>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShowdownHand {
+    pub seat: usize,
+    pub cards: Vec<Card>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPot {
+    /// Includes any bet nobody called
+    pub amount: ChipInt,
+    pub winners: Vec<SeatWinnings>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SeatWinnings {
+    pub seat: usize,
+    pub amount: ChipInt,
+}
+
+/// Everything that happened in a finished hand, for players to look back on
+/// and for settling disputes
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HandHistory {
+    /// Given when the hand is recorded
+    pub id: HandId,
+    pub table_id: TableId,
+    pub table_name: String,
+    pub max_players: usize,
+    /// Hands recorded before Omaha was dealt are all Hold'em
+    #[serde(default)]
+    pub variant: GameVariant,
+    #[serde(default)]
+    pub betting: BettingStructure,
+    /// Unix time in milliseconds the hand was dealt
+    pub started_at: u64,
+    pub small_blind: ChipInt,
+    pub big_blind: ChipInt,
+    #[serde(default)]
+    pub ante: Option<Ante>,
+    pub button_seat: usize,
+    pub seats: Vec<HistorySeat>,
+    pub actions: Vec<HandAction>,
+    pub board: Vec<Card>,
+    /// Hands turned up at showdown, in the order they were shown
+    pub shown: Vec<ShowdownHand>,
+    /// Seats that went to showdown and mucked
+    pub mucked: Vec<usize>,
+    pub pots: Vec<HistoryPot>,
+    /// How the deck was shuffled, with the server seed revealed
+    pub shuffle: Option<Shuffle>,
+}
+
+impl HandHistory {
+    /// Records a finished game, where `shown` are the indexes of the players
+    /// whose hands were turned up at showdown
+    pub fn new(config: &TableConfig, game: &Game, shown: &[usize]) -> Self {
+        let seat = |idx: usize| game.players[idx].seat;
+        let shown_hands = shown
+            .iter()
+            .map(|idx| ShowdownHand {
+                seat: seat(*idx),
+                cards: game.state.hands[*idx][..].to_vec(),
+                description: game
+                    .ranks
+                    .iter()
+                    .find(|(_, ranked)| ranked == idx)
+                    .map(|(rank, _)| describe_rank(rank)),
+            })
+            .collect();
+        let mucked = game
+            .ranks
+            .iter()
+            .map(|(_, idx)| *idx)
+            .filter(|idx| !shown.contains(idx))
+            .map(seat)
+            .collect();
+
+        Self {
+            id: 0,
+            table_id: config.id.clone(),
+            table_name: config.name.clone(),
+            max_players: config.max_players,
+            variant: game.variant,
+            betting: game.betting,
+            started_at: game.started_at,
+            small_blind: config.small_blind,
+            big_blind: config.big_blind,
+            ante: config.ante,
+            button_seat: game.button_seat,
+            seats: game
+                .players
+                .iter()
+                .zip(&game.state.hands)
+                .map(|(player, hand)| HistorySeat {
+                    seat: player.seat,
+                    player: player.info.clone(),
+                    stack: player.chips,
+                    cards: hand[..].to_vec(),
+                })
+                .collect(),
+            actions: game.actions.clone(),
+            board: game.state.board.clone(),
+            shown: shown_hands,
+            mucked,
+            pots: game
+                .pots
+                .iter()
+                .map(|pot| HistoryPot {
+                    amount: pot.amount,
+                    winners: pot
+                        .winners
+                        .iter()
+                        .map(|(idx, amount)| SeatWinnings {
+                            seat: seat(*idx),
+                            amount: *amount,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            shuffle: game.shuffle().cloned(),
+        }
+    }
+
+    /// Checks the hand was dealt from the shuffle committed to as
+    /// `commitment` before the deal, as far as the cards in the history show
+    pub fn verify_shuffle(&self, commitment: &B256) -> Result<()> {
+        let shuffle = self
+            .shuffle
+            .as_ref()
+            .ok_or_else(|| eyre!("Hand wasn't shuffled from seeds"))?;
+        let hands: Vec<_> = self.seats.iter().map(|seat| seat.cards.clone()).collect();
+        shuffle.verify(commitment, self.variant.hole_cards(), &hands, &self.board)
+    }
+
+    /// Deals the hand again and plays back every action, checking it comes
+    /// out exactly as recorded. The deck comes from the shuffle, or without
+    /// one from everyone's hole cards and the board. `step` sees the game
+    /// once dealt and after each action.
+    pub fn replay(&self, mut step: impl FnMut(&Game)) -> Result<Game> {
+        use HandActionKind::*;
+
+        let source = match &self.shuffle {
+            Some(shuffle) => DeckSource::Shuffle(shuffle.clone()),
+            None => {
+                let num_cards = self.variant.hole_cards();
+                if let Some(seat) = self.seats.iter().find(|seat| seat.cards.len() != num_cards) {
+                    bail!("Hole cards for seat {} weren't recorded", seat.seat)
+                }
+                let hole_cards =
+                    (0..num_cards).flat_map(|i| self.seats.iter().map(move |seat| seat.cards[i]));
+                DeckSource::stacked(hole_cards.chain(self.board.iter().copied()).collect())?
+            }
+        };
+        let idx = |seat: usize| {
+            self.seats
+                .iter()
+                .position(|s| s.seat == seat)
+                .ok_or_else(|| eyre!("Seat {} wasn't dealt in", seat))
+        };
+
+        // Antes and blinds are posted first, the big blind ahead of anyone
+        // posting to come in out of position
+        let num_blinds = self
+            .actions
+            .iter()
+            .take_while(|action| matches!(action.kind, Ante | SmallBlind | BigBlind))
+            .count();
+        let (blinds, actions) = self.actions.split_at(num_blinds);
+        let mut big_blinds = blinds.iter().filter(|action| action.kind == BigBlind);
+        let big_blind = big_blinds
+            .next()
+            .ok_or_else(|| eyre!("No big blind was posted"))?;
+        let posting: Vec<_> = big_blinds.map(|action| action.seat).collect();
+        let small_blind = blinds
+            .iter()
+            .find(|action| action.kind == SmallBlind)
+            .map(|action| idx(action.seat))
+            .transpose()?;
+        // With a dead button, the last player before it acts last after the flop
+        let dealer = (0..self.max_players)
+            .map(|offset| (self.button_seat + self.max_players - offset) % self.max_players)
+            .find_map(|seat| idx(seat).ok())
+            .ok_or_else(|| eyre!("Nobody was dealt in"))?;
+
+        let players = self
+            .seats
+            .iter()
+            .map(|seat| {
+                let table_player = TablePlayer::new(seat.player.clone(), seat.stack);
+                let mut player = GamePlayer::from(table_player);
+                player.seat = seat.seat;
+                player.posts_big_blind = posting.contains(&seat.seat);
+                player
+            })
+            .collect();
+        let blinds = Blinds {
+            dealer,
+            small_blind,
+            big_blind: idx(big_blind.seat)?,
+            ante: self.ante,
+        };
+        let mut game = Game::with_source(
+            self.table_id.clone(),
+            players,
+            blinds,
+            self.small_blind,
+            self.big_blind,
+            self.variant,
+            source,
+        );
+        game.button_seat = self.button_seat;
+        game.betting = self.betting;
+        game.started_at = self.started_at;
+        step(&game);
+
+        for action in actions {
+            let idx = idx(action.seat)?;
+            if action.kind == Fold && idx != game.current_player_idx() {
+                // Players who stand up fold whenever they leave
+                game.fold_player(idx)?;
+            } else if idx != game.current_player_idx() {
+                bail!("Seat {} acted out of turn", action.seat)
+            } else {
+                game.act(match action.kind {
+                    Check => Action::Check,
+                    Call => Action::Call,
+                    Bet => Action::Bet(action.total),
+                    Raise => Action::Raise(action.total),
+                    Fold => Action::Fold,
+                    Ante | SmallBlind | BigBlind => {
+                        bail!("Antes and blinds are only posted before the deal")
+                    }
+                })?;
+            }
+            step(&game);
+        }
+        if !game.is_over() {
+            bail!("Hand was still being played")
+        }
+
+        let config = TableConfig {
+            id: self.table_id.clone(),
+            name: self.table_name.clone(),
+            max_players: self.max_players,
+            variant: self.variant,
+            betting: Some(self.betting),
+            small_blind: self.small_blind,
+            big_blind: self.big_blind,
+            ante: self.ante,
+            ..Default::default()
+        };
+        let shown = self
+            .shown
+            .iter()
+            .map(|shown| idx(shown.seat))
+            .collect::<Result<Vec<_>>>()?;
+        let mut replayed = Self::new(&config, &game, &shown);
+        replayed.id = self.id;
+        // Players' own copies leave out hole cards they didn't see
+        for (seat, recorded) in replayed.seats.iter_mut().zip(&self.seats) {
+            if recorded.cards.is_empty() {
+                seat.cards.clear();
+            }
+        }
+        if &replayed != self {
+            bail!("Replay doesn't match the recorded hand")
+        }
+        Ok(game)
+    }
+
+    pub fn was_dealt(&self, player: &PlayerId) -> bool {
+        self.seats.iter().any(|seat| &seat.player.id == player)
+    }
+
+    /// The history as `player` may see it, without anyone else's hole cards
+    /// unless they were shown. The shuffle stays, so the hidden cards can
+    /// still be dealt again from it.
+    pub fn seen_by(&self, player: &PlayerId) -> Self {
+        let mut history = self.clone();
+        for seat in &mut history.seats {
+            let shown = self.shown.iter().any(|shown| shown.seat == seat.seat);
+            if &seat.player.id != player && !shown {
+                seat.cards.clear();
+            }
+        }
+        history
+    }
+
+    fn name(&self, seat: usize) -> &str {
+        self.seats
+            .iter()
+            .find(|s| s.seat == seat)
+            .map_or("", |s| s.player.username.as_str())
+    }
+
+    /// Chips each seat bet over the hand, leaving out antes, which are dead
+    fn contributions(&self) -> Vec<(usize, ChipInt)> {
+        self.seats
+            .iter()
+            .map(|seat| {
+                let put_in = self
+                    .actions
+                    .iter()
+                    .filter(|action| action.seat == seat.seat)
+                    .filter(|action| action.kind != HandActionKind::Ante)
+                    .map(|action| action.amount)
+                    .sum();
+                (seat.seat, put_in)
+            })
+            .collect()
+    }
+
+    /// The seat and size of a bet nobody called, which goes back to the bettor
+    fn uncalled_bet(&self) -> Option<(usize, ChipInt)> {
+        let mut contributions = self.contributions();
+        contributions.sort_by_key(|(_, put_in)| std::cmp::Reverse(*put_in));
+        match contributions.as_slice() {
+            [(seat, most), (_, next), ..] if most > next => Some((*seat, most - next)),
+            _ => None,
+        }
+    }
+
+    /// Writes the hand in the text format PokerStars uses, which most hand
+    /// history tools can import
+    pub fn to_pokerstars(&self) -> String {
+        let mut out = String::new();
+        // Writing to a String can't fail
+        let _ = self.write_pokerstars(&mut out);
+        out
+    }
+
+    fn write_pokerstars(&self, out: &mut String) -> std::fmt::Result {
+        let uncalled = self.uncalled_bet();
+        // The uncalled bet is taken out of the last pot its bettor won
+        let mut pots = self.pots.clone();
+        if let Some((seat, amount)) = uncalled {
+            if let Some(pot) = pots
+                .iter_mut()
+                .rev()
+                .find(|pot| pot.winners.iter().any(|w| w.seat == seat))
+            {
+                pot.amount -= amount;
+                for winner in pot.winners.iter_mut().filter(|w| w.seat == seat) {
+                    winner.amount -= amount;
+                }
+            }
+            pots.retain(|pot| pot.amount > 0);
+        }
+        // Limit games go by their bet sizes rather than the blinds
+        let stakes = match self.betting {
+            BettingStructure::FixedLimit {
+                small_bet, big_bet, ..
+            } => (small_bet, big_bet),
+            _ => (self.small_blind, self.big_blind),
+        };
+        let pot_name = |i: usize| match (i, pots.len()) {
+            (_, 1) => "pot".to_owned(),
+            (0, _) => "main pot".to_owned(),
+            (i, _) => format!("side pot-{i}"),
+        };
+
+        writeln!(
+            out,
+            "PokerStars Hand #{}:  {} {} ({}/{}) - {} UTC",
+            self.id,
+            self.variant.pokerstars_name(),
+            self.betting.pokerstars_name(),
+            stakes.0,
+            stakes.1,
+            format_utc(self.started_at)
+        )?;
+        writeln!(
+            out,
+            "Table '{}' {}-max Seat #{} is the button",
+            self.table_name,
+            self.max_players,
+            self.button_seat + 1
+        )?;
+        for seat in &self.seats {
+            writeln!(
+                out,
+                "Seat {}: {} ({} in chips)",
+                seat.seat + 1,
+                seat.player.username,
+                seat.stack
+            )?;
+        }
+
+        let is_blind = |action: &&HandAction| {
+            matches!(
+                action.kind,
+                HandActionKind::Ante | HandActionKind::SmallBlind | HandActionKind::BigBlind
+            )
+        };
+        let mut street_bet = 0;
+        for action in self.actions.iter().filter(is_blind) {
+            self.write_action(out, action, &mut street_bet)?;
+        }
+        writeln!(out, "*** HOLE CARDS ***")?;
+        for seat in self.seats.iter().filter(|seat| !seat.cards.is_empty()) {
+            writeln!(
+                out,
+                "Dealt to {} [{}]",
+                seat.player.username,
+                format_cards(&seat.cards)
+            )?;
+        }
+
+        let streets = [
+            (Street::Preflop, 0, ""),
+            (Street::Flop, 3, "FLOP"),
+            (Street::Turn, 4, "TURN"),
+            (Street::River, 5, "RIVER"),
+        ];
+        for (street, dealt, name) in streets {
+            if street != Street::Preflop {
+                if self.board.len() < dealt {
+                    break;
+                }
+                street_bet = 0;
+                // Later streets show the board so far, then the new card
+                let (before, new) = self.board[..dealt].split_at(3.max(dealt - 1));
+                write!(out, "*** {} *** [{}]", name, format_cards(before))?;
+                if !new.is_empty() {
+                    write!(out, " [{}]", format_cards(new))?;
+                }
+                writeln!(out)?;
+            }
+            for action in self
+                .actions
+                .iter()
+                .filter(|action| action.street == street && !is_blind(action))
+            {
+                self.write_action(out, action, &mut street_bet)?;
+            }
+        }
+        if let Some((seat, amount)) = uncalled {
+            writeln!(
+                out,
+                "Uncalled bet ({}) returned to {}",
+                amount,
+                self.name(seat)
+            )?;
+        }
+
+        let showdown = !self.shown.is_empty() || !self.mucked.is_empty();
+        if showdown {
+            writeln!(out, "*** SHOW DOWN ***")?;
+            for shown in &self.shown {
+                write!(
+                    out,
+                    "{}: shows [{}]",
+                    self.name(shown.seat),
+                    format_cards(&shown.cards)
+                )?;
+                if let Some(description) = &shown.description {
+                    write!(out, " ({description})")?;
+                }
+                writeln!(out)?;
+            }
+            for seat in &self.mucked {
+                writeln!(out, "{}: mucks hand", self.name(*seat))?;
+            }
+        }
+        for (i, pot) in pots.iter().enumerate() {
+            for winner in &pot.winners {
+                writeln!(
+                    out,
+                    "{} collected {} from {}",
+                    self.name(winner.seat),
+                    winner.amount,
+                    pot_name(i)
+                )?;
+            }
+        }
+
+        writeln!(out, "*** SUMMARY ***")?;
+        let total: ChipInt = pots.iter().map(|pot| pot.amount).sum();
+        write!(out, "Total pot {total}")?;
+        if pots.len() > 1 {
+            for (i, pot) in pots.iter().enumerate() {
+                let name = pot_name(i);
+                let mut chars = name.chars();
+                let capitalized = chars.next().map_or(String::new(), |first| {
+                    first.to_uppercase().chain(chars).collect()
+                });
+                write!(out, " {} {}.", capitalized, pot.amount)?;
+            }
+        }
+        writeln!(out, " | Rake 0")?;
+        if !self.board.is_empty() {
+            writeln!(out, "Board [{}]", format_cards(&self.board))?;
+        }
+
+        for seat in &self.seats {
+            write!(out, "Seat {}: {}", seat.seat + 1, seat.player.username)?;
+            if seat.seat == self.button_seat {
+                write!(out, " (button)")?;
+            }
+            let posted = |kind| {
+                self.actions
+                    .iter()
+                    .any(|action| action.seat == seat.seat && action.kind == kind)
+            };
+            if posted(HandActionKind::SmallBlind) {
+                write!(out, " (small blind)")?;
+            } else if posted(HandActionKind::BigBlind) {
+                write!(out, " (big blind)")?;
+            }
+
+            let won: ChipInt = pots
+                .iter()
+                .flat_map(|pot| &pot.winners)
+                .filter(|winner| winner.seat == seat.seat)
+                .map(|winner| winner.amount)
+                .sum();
+            let folded = self
+                .actions
+                .iter()
+                .find(|action| action.seat == seat.seat && action.kind == HandActionKind::Fold);
+            if let Some(fold) = folded {
+                match fold.street {
+                    Street::Preflop => {
+                        write!(out, " folded before Flop")?;
+                        // Antes don't count as betting
+                        let put_in = self.actions.iter().any(|action| {
+                            action.seat == seat.seat
+                                && action.amount > 0
+                                && action.kind != HandActionKind::Ante
+                        });
+                        if !put_in {
+                            write!(out, " (didn't bet)")?;
+                        }
+                    }
+                    street => write!(out, " folded on the {street:?}")?,
+                }
+            } else if let Some(shown) = self.shown.iter().find(|shown| shown.seat == seat.seat) {
+                write!(out, " showed [{}]", format_cards(&shown.cards))?;
+                if won > 0 {
+                    write!(out, " and won ({won})")?;
+                } else {
+                    write!(out, " and lost")?;
+                }
+                if let Some(description) = &shown.description {
+                    write!(out, " with {description}")?;
+                }
+            } else if self.mucked.contains(&seat.seat) {
+                write!(out, " mucked")?;
+            } else if won > 0 {
+                write!(out, " collected ({won})")?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    fn write_action(
+        &self,
+        out: &mut String,
+        action: &HandAction,
+        street_bet: &mut ChipInt,
+    ) -> std::fmt::Result {
+        write!(out, "{}: ", self.name(action.seat))?;
+        match action.kind {
+            HandActionKind::Ante => write!(out, "posts the ante {}", action.amount)?,
+            HandActionKind::SmallBlind => write!(out, "posts small blind {}", action.amount)?,
+            HandActionKind::BigBlind => write!(out, "posts big blind {}", action.amount)?,
+            HandActionKind::Check => write!(out, "checks")?,
+            HandActionKind::Call => write!(out, "calls {}", action.amount)?,
+            HandActionKind::Bet => write!(out, "bets {}", action.amount)?,
+            HandActionKind::Raise => write!(
+                out,
+                "raises {} to {}",
+                action.total - *street_bet,
+                action.total
+            )?,
+            HandActionKind::Fold => write!(out, "folds")?,
+        }
+        if action.all_in {
+            write!(out, " and is all-in")?;
+        }
+        writeln!(out)?;
+        *street_bet = (*street_bet).max(action.total);
+        Ok(())
+    }
+}
+
+fn format_cards(cards: &[Card]) -> String {
+    cards
+        .iter()
+        .map(Card::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Formats Unix milliseconds as `YYYY/MM/DD HH:MM:SS`
+fn format_utc(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (days, time) = (secs / 86400, secs % 86400);
+    // Civil date from days since the epoch, counting years from March so
+    // leap days fall at the end
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}/{:02}/{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_util::*;
+    use test_log::test;
+
+    fn action(
+        street: Street,
+        seat: usize,
+        kind: HandActionKind,
+        amount: ChipInt,
+        total: ChipInt,
+    ) -> HandAction {
+        HandAction {
+            street,
+            seat,
+            kind,
+            amount,
+            total,
+            all_in: false,
+        }
+    }
+
+    /// Bob is all-in preflop and wins the main pot against Carol, whose flop
+    /// bet nobody calls
+    fn all_in_history() -> HandHistory {
+        let seat = |seat: usize, name: &str, stack: ChipInt, hand: &str| HistorySeat {
+            seat,
+            player: player(name),
+            stack,
+            cards: cards(hand),
+        };
+        use HandActionKind::*;
+        use Street::*;
+        HandHistory {
+            id: 7,
+            table_id: "test".to_owned(),
+            table_name: "Test Table".to_owned(),
+            max_players: 9,
+            variant: GameVariant::Holdem,
+            betting: BettingStructure::NoLimit,
+            started_at: 1_700_000_000_000,
+            small_blind: 1,
+            big_blind: 2,
+            ante: None,
+            button_seat: 0,
+            seats: vec![
+                seat(0, "alice", 100, "QhJh"),
+                seat(1, "bob", 30, "AsAd"),
+                seat(3, "carol", 100, "KsKd"),
+            ],
+            actions: vec![
+                action(Preflop, 1, SmallBlind, 1, 1),
+                action(Preflop, 3, BigBlind, 2, 2),
+                action(Preflop, 0, Raise, 6, 6),
+                HandAction {
+                    all_in: true,
+                    ..action(Preflop, 1, Raise, 29, 30)
+                },
+                action(Preflop, 3, Call, 28, 30),
+                action(Preflop, 0, Call, 24, 30),
+                action(Flop, 3, Bet, 20, 20),
+                action(Flop, 0, Fold, 0, 0),
+            ],
+            board: cards("2c7h9dTc3s"),
+            shown: vec![ShowdownHand {
+                seat: 1,
+                cards: cards("AsAd"),
+                description: Some("Pair of Aces".to_owned()),
+            }],
+            mucked: vec![3],
+            pots: vec![
+                HistoryPot {
+                    amount: 90,
+                    winners: vec![SeatWinnings {
+                        seat: 1,
+                        amount: 90,
+                    }],
+                },
+                HistoryPot {
+                    amount: 20,
+                    winners: vec![SeatWinnings {
+                        seat: 3,
+                        amount: 20,
+                    }],
+                },
+            ],
+            shuffle: None,
+        }
+    }
+
+    #[test]
+    fn test_seen_by_hides_unshown_cards() {
+        let history = all_in_history();
+        let seen = history.seen_by(&"carol".to_owned());
+        let dealt: Vec<_> = seen.seats.iter().map(|seat| seat.cards.clone()).collect();
+        assert_eq!(dealt, [vec![], cards("AsAd"), cards("KsKd")]);
+
+        let seen = history.seen_by(&"alice".to_owned());
+        let dealt: Vec<_> = seen.seats.iter().map(|seat| seat.cards.clone()).collect();
+        assert_eq!(dealt, [cards("QhJh"), cards("AsAd"), vec![]]);
+    }
+
+    #[test]
+    fn test_pokerstars_export() {
+        let text = all_in_history().seen_by(&"bob".to_owned()).to_pokerstars();
+        let expected = "\
+PokerStars Hand #7:  Hold'em No Limit (1/2) - 2023/11/14 22:13:20 UTC
+Table 'Test Table' 9-max Seat #1 is the button
+Seat 1: alice (100 in chips)
+Seat 2: bob (30 in chips)
+Seat 4: carol (100 in chips)
+bob: posts small blind 1
+carol: posts big blind 2
+*** HOLE CARDS ***
+Dealt to bob [As Ad]
+alice: raises 4 to 6
+bob: raises 24 to 30 and is all-in
+carol: calls 28
+alice: calls 24
+*** FLOP *** [2c 7h 9d]
+carol: bets 20
+alice: folds
+*** TURN *** [2c 7h 9d] [Tc]
+*** RIVER *** [2c 7h 9d Tc] [3s]
+Uncalled bet (20) returned to carol
+*** SHOW DOWN ***
+bob: shows [As Ad] (Pair of Aces)
+carol: mucks hand
+bob collected 90 from pot
+*** SUMMARY ***
+Total pot 90 | Rake 0
+Board [2c 7h 9d Tc 3s]
+Seat 1: alice (button) folded on the Flop
+Seat 2: bob (small blind) showed [As Ad] and won (90) with Pair of Aces
+Seat 4: carol (big blind) mucked
+";
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn test_side_pots_export() {
+        // Carol's flop bet is called this time, and she wins the side pot
+        let mut history = all_in_history();
+        history.actions.pop();
+        history
+            .actions
+            .push(action(Street::Flop, 0, HandActionKind::Call, 20, 20));
+        history.pots[1].amount = 40;
+        history.pots[1].winners[0].amount = 40;
+        history.mucked = vec![0];
+        history.shown.push(ShowdownHand {
+            seat: 3,
+            cards: cards("KsKd"),
+            description: Some("Pair of Kings".to_owned()),
+        });
+
+        let text = history.to_pokerstars();
+        assert!(!text.contains("Uncalled bet"));
+        assert!(
+            text.contains("bob collected 90 from main pot\ncarol collected 40 from side pot-1\n")
+        );
+        assert!(text.contains("Total pot 130 Main pot 90. Side pot-1 40. | Rake 0\n"));
+        assert!(text.contains("Seat 1: alice (button) mucked"));
+        assert!(text
+            .contains("Seat 4: carol (big blind) showed [Ks Kd] and won (40) with Pair of Kings"));
+    }
+
+    #[test]
+    fn test_recorded_from_game() {
+        let mut table = Table::default();
+        sit_alice_bob_carol(&mut table);
+        table
+            .set_client_seed(&table.seats[1].clone().unwrap().info, "bob".to_owned())
+            .unwrap();
+        let commitment = table.commitment();
+        table.start_new_game().unwrap();
+        let game = table.game_mut().unwrap();
+        game.act(Action::Raise(6)).unwrap();
+        game.act(Action::Fold).unwrap();
+        game.act(Action::Fold).unwrap();
+        assert!(game.is_over());
+
+        let history = HandHistory::new(&table.config, table.game().unwrap(), &[]);
+        let kinds: Vec<_> = history.actions.iter().map(|action| action.kind).collect();
+        use HandActionKind::*;
+        assert_eq!(kinds, [SmallBlind, BigBlind, Raise, Fold, Fold]);
+        assert!(history.shown.is_empty() && history.mucked.is_empty());
+
+        let text = history.to_pokerstars();
+        let raiser = history.name(history.actions[2].seat);
+        assert!(text.contains(&format!("Uncalled bet (4) returned to {raiser}\n")));
+        assert!(text.contains(&format!("{raiser} collected 5 from pot\n")));
+        assert!(text.contains(" collected (5)\n"));
+        assert_eq!(text.matches("folded before Flop\n").count(), 2);
+
+        let shuffle = history.shuffle.as_ref().unwrap();
+        assert_eq!(shuffle.client_seeds, ["", "bob", ""]);
+        history.verify_shuffle(&commitment).unwrap();
+        // Players can check the deal from their own copy
+        let seen = history.seen_by(&"alice".to_owned());
+        seen.verify_shuffle(&commitment).unwrap();
+        assert!(seen.verify_shuffle(&table.commitment()).is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut table = Table::default();
+        for (seat, name) in [(0, "alice"), (2, "bob"), (4, "carol")] {
+            table.sit_player(player(name), 100, Some(seat)).unwrap();
+        }
+        table.decks.push_back(DeckSource::Seeded { seed: 1 });
+
+        let mut histories = vec![];
+        for _ in 0..2 {
+            table.start_new_game().unwrap();
+            let game = table.game_mut().unwrap();
+            game.act(Action::Raise(6)).unwrap();
+            game.act(Action::Call).unwrap();
+            game.act(Action::Fold).unwrap();
+            while !game.is_over() {
+                game.act(Action::Check).unwrap();
+            }
+            let shown = [game.showdown_order()[0]];
+            let game = table.game().unwrap();
+            histories.push(HandHistory::new(&table.config, game, &shown));
+            table.complete_game();
+        }
+
+        for history in &histories {
+            let mut steps = 0;
+            let game = history.replay(|_| steps += 1).unwrap();
+            // Once dealt, then once for each action after the blinds
+            assert_eq!(steps, history.actions.len() - 1);
+            assert_eq!(game.state.board, history.board);
+        }
+
+        // Without a shuffle, the deck can only be put back from every hand
+        let (seeded, fair) = (&histories[0], &histories[1]);
+        assert!(seeded.shuffle.is_none());
+        assert!(seeded.seen_by(&"nobody".to_owned()).replay(|_| {}).is_err());
+        fair.seen_by(&"nobody".to_owned()).replay(|_| {}).unwrap();
+
+        let mut tampered = fair.clone();
+        tampered.actions[2].total += 2;
+        assert!(tampered.replay(|_| {}).is_err());
+    }
+
+    #[test]
+    fn test_revealed_seed_deals_mucked_hands_again() {
+        let mut table = Table::default();
+        sit_alice_bob_carol(&mut table);
+        let commitment = table.commitment();
+        table.start_new_game().unwrap();
+        let game = table.game_mut().unwrap();
+        game.act(Action::Call).unwrap();
+        game.act(Action::Call).unwrap();
+        while !game.is_over() {
+            game.act(Action::Check).unwrap();
+        }
+        let game = table.game().unwrap();
+        let order = game.showdown_order();
+        let (shown, mucked) = (order[0], order[1]);
+        let history = HandHistory::new(&table.config, game, &[shown]);
+
+        // The winner's announcement reveals the seed whatever was shown
+        match PokerMessage::declare_winner("room".to_owned(), game, &[shown]) {
+            PokerMessage::Server(Either::Room(RoomMessage {
+                payload: ServerRoomPayload::DeclareWinner { shuffle, .. },
+                ..
+            })) => assert_eq!(shuffle.as_ref(), game.shuffle()),
+            msg => panic!("Expected winners, received {:?}", msg),
+        }
+
+        // Copies leave the mucked cards out, but keep the seed, which deals
+        // them again once the hand is over
+        let mucked_player = &game.players[mucked].info;
+        let seen = history.seen_by(&"nobody".to_owned());
+        seen.verify_shuffle(&commitment).unwrap();
+        let mucked_seat = seen
+            .seats
+            .iter()
+            .find(|seat| seat.player.id == mucked_player.id)
+            .unwrap();
+        assert!(mucked_seat.cards.is_empty());
+        let replayed = seen.replay(|_| {}).unwrap();
+        assert_eq!(replayed.players_hands(), game.players_hands());
+    }
+
+    #[test]
+    fn test_big_blind_ante() {
+        let mut table = Table::from_config(TableConfig {
+            ante: Some(Ante {
+                kind: AnteKind::BigBlind,
+                amount: 2,
+            }),
+            ..Default::default()
+        });
+        sit_alice_bob_carol(&mut table);
+        table.start_new_game().unwrap();
+        let game = table.game_mut().unwrap();
+        game.act(Action::Fold).unwrap();
+        game.act(Action::Fold).unwrap();
+
+        let history = HandHistory::new(&table.config, table.game().unwrap(), &[]);
+        let kinds: Vec<_> = history.actions.iter().map(|action| action.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                HandActionKind::SmallBlind,
+                HandActionKind::BigBlind,
+                HandActionKind::Ante,
+                HandActionKind::Fold,
+                HandActionKind::Fold
+            ]
+        );
+        history.replay(|_| {}).unwrap();
+
+        // The ante is dead money, so only the big blind's bet comes back
+        let text = history.to_pokerstars();
+        let big_blind = history.name(history.actions[1].seat);
+        assert!(text.contains(&format!("{big_blind}: posts the ante 2\n")));
+        assert!(text.contains(&format!("Uncalled bet (1) returned to {big_blind}\n")));
+        assert!(text.contains(&format!("{big_blind} collected 4 from pot\n")));
+        assert!(text.contains(" folded before Flop (didn't bet)\n"));
+    }
+}
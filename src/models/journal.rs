@@ -0,0 +1,413 @@
+use crate::*;
+
+/// What happens to a hand the server stopped in the middle of
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HandRecovery {
+    /// Plays the hand back from the journal and carries on from there
+    #[default]
+    Resume,
+    /// Calls the hand off, giving everyone in it back what they put in
+    Void,
+}
+
+/// A hand dealt straight after a snapshot
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JournaledHand {
+    pub next: NextGame,
+    pub deck: DeckSource,
+    /// Unix time in milliseconds the hand was dealt
+    pub started_at: u64,
+}
+
+/// The table's seats, taken when a hand is about to be dealt or has finished.
+/// A hand about to be dealt has already been shuffled, so `server_seed` is
+/// the one for the hand after.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TableSnapshot {
+    pub table_id: TableId,
+    pub seats: Vec<Option<TablePlayer>>,
+    pub button_seat: Option<usize>,
+    pub small_blind_seat: Option<usize>,
+    pub big_blind_seat: Option<usize>,
+    pub server_seed: B256,
+    pub hand: Option<JournaledHand>,
+}
+
+impl TableSnapshot {
+    pub fn new(table: &Table) -> Self {
+        Self {
+            table_id: table.id().clone(),
+            seats: table.seats.clone(),
+            button_seat: table.button_seat,
+            small_blind_seat: table.small_blind_seat,
+            big_blind_seat: table.big_blind_seat,
+            server_seed: table.server_seed,
+            hand: None,
+        }
+    }
+}
+
+/// A change at the table since its snapshot
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(tag = "entry", rename_all = "camelCase")]
+pub enum JournalEntry {
+    Sat {
+        player: Player,
+        chips: ChipInt,
+        seat: usize,
+    },
+    /// `pending` chips wait for the player's hand to finish
+    ToppedUp {
+        player: Player,
+        chips: ChipInt,
+        pending: bool,
+    },
+    Vacated {
+        seat: usize,
+    },
+    /// The player leaves once the hand in progress is over
+    StandingUp {
+        seat: usize,
+    },
+    Setting {
+        player: Player,
+        setting: PlayerSetting,
+        value: bool,
+    },
+    ClientSeed {
+        player: Player,
+        seed: String,
+    },
+    /// Taken by the player whose turn it was
+    Acted {
+        action: Action,
+    },
+    /// A player folded out of turn by standing up
+    Folded {
+        player: Player,
+    },
+    /// Seconds the player took out of their time bank
+    UsedTimeBank {
+        player: Player,
+        secs: u64,
+    },
+    /// Turned up at showdown
+    Showed {
+        player: Player,
+    },
+    /// Mucked at showdown
+    Mucked {
+        player: Player,
+    },
+}
+
+/// A table's last snapshot and everything that changed since, so the table
+/// can be put back together after the server stops without warning
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TableJournal {
+    pub snapshot: TableSnapshot,
+    pub entries: Vec<JournalEntry>,
+}
+
+impl TableJournal {
+    /// Puts the table back together. A hand in progress is played back up to
+    /// its last action, or called off with `HandRecovery::Void` so everyone in
+    /// it keeps the stack they started it with. Everyone seated is marked
+    /// disconnected until they come back.
+    pub fn restore(&self, config: TableConfig, recovery: HandRecovery) -> Result<Table> {
+        let snapshot = &self.snapshot;
+        if snapshot.seats.len() != config.max_players {
+            bail!(
+                "Journal has {} seats but the table has {}",
+                snapshot.seats.len(),
+                config.max_players
+            )
+        }
+        let mut table = Table::from_config(config);
+        table.seats = snapshot.seats.clone();
+        table.button_seat = snapshot.button_seat;
+        table.small_blind_seat = snapshot.small_blind_seat;
+        table.big_blind_seat = snapshot.big_blind_seat;
+        table.server_seed = snapshot.server_seed;
+
+        let resume = recovery == HandRecovery::Resume;
+        if let Some(hand) = snapshot.hand.as_ref().filter(|_| resume) {
+            table.deal(&hand.next, hand.deck.clone())?;
+            table.game_mut().expect("Game was dealt").started_at = hand.started_at;
+        }
+
+        for entry in self.entries.iter().cloned() {
+            match entry {
+                JournalEntry::Sat {
+                    player,
+                    chips,
+                    seat,
+                } => {
+                    table.sit_player(player, chips, Some(seat))?;
+                }
+                JournalEntry::ToppedUp {
+                    player,
+                    chips,
+                    pending,
+                } => {
+                    let pending = pending && table.game().is_some();
+                    table.top_up(&player, Some(chips), chips, pending)?;
+                }
+                JournalEntry::Vacated { seat } => {
+                    table
+                        .seats
+                        .get_mut(seat)
+                        .ok_or_else(|| eyre!("No seat {}", seat))?
+                        .take();
+                }
+                JournalEntry::StandingUp { seat } if resume => {
+                    if let Some(Some(player)) = table.seats.get_mut(seat) {
+                        player.standing_up = true;
+                    }
+                }
+                JournalEntry::Setting {
+                    player,
+                    setting,
+                    value,
+                } => {
+                    // Settings for the hand go with it when it's called off
+                    let for_hand =
+                        matches!(setting, PlayerSetting::CheckFold | PlayerSetting::CallAny);
+                    if !for_hand || table.game().is_some() {
+                        table.set_setting(&player, setting, value)?;
+                    }
+                }
+                JournalEntry::ClientSeed { player, seed } => {
+                    table.set_client_seed(&player, seed)?;
+                }
+                JournalEntry::UsedTimeBank { player, secs } => {
+                    table.use_time_bank(&player, secs)?;
+                }
+                JournalEntry::Acted { action } if resume => {
+                    let game = table.game_mut().ok_or_else(|| eyre!("No hand to act in"))?;
+                    game.act(action)?;
+                }
+                JournalEntry::Folded { player } if resume => {
+                    let game = table
+                        .game_mut()
+                        .ok_or_else(|| eyre!("No hand to fold from"))?;
+                    let idx = game
+                        .player_idx(&player)
+                        .ok_or_else(|| eyre!("{} wasn't dealt in", player.id))?;
+                    game.fold_player(idx)?;
+                }
+                // The showdown is picked up again by the room
+                JournalEntry::StandingUp { .. }
+                | JournalEntry::Acted { .. }
+                | JournalEntry::Folded { .. }
+                | JournalEntry::Showed { .. }
+                | JournalEntry::Mucked { .. } => {}
+            }
+        }
+
+        // Streets dealt while playing the hand back were announced at the time
+        if let Some(game) = table.game_mut() {
+            game.take_events();
+        }
+        for player in table.seats.iter_mut().flatten() {
+            player.connected = false;
+        }
+        Ok(table)
+    }
+
+    /// Players who showed (`true`) or mucked at the showdown the hand
+    /// stopped in, in the order they did
+    pub fn showdown(&self) -> Vec<(Player, bool)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                JournalEntry::Showed { player } => Some((player.clone(), true)),
+                JournalEntry::Mucked { player } => Some((player.clone(), false)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// What each player seated at the table would get back if the hand in
+    /// progress were called off, for a table that can't be put back
+    pub fn refunds(&self) -> Vec<(Player, ChipInt)> {
+        let mut seats: Vec<_> = self
+            .snapshot
+            .seats
+            .iter()
+            .map(|seat| seat.as_ref().map(|p| (p.info.clone(), p.chips + p.top_up)))
+            .collect();
+        for entry in &self.entries {
+            match entry {
+                JournalEntry::Sat {
+                    player,
+                    chips,
+                    seat,
+                } => {
+                    if *seat >= seats.len() {
+                        seats.resize(*seat + 1, None);
+                    }
+                    seats[*seat] = Some((player.clone(), *chips));
+                }
+                JournalEntry::ToppedUp { player, chips, .. } => {
+                    if let Some((_, stack)) =
+                        seats.iter_mut().flatten().find(|(p, _)| p.id == player.id)
+                    {
+                        *stack += chips;
+                    }
+                }
+                JournalEntry::Vacated { seat } => {
+                    if let Some(slot) = seats.get_mut(*seat) {
+                        *slot = None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        seats.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_util::*;
+    use test_log::test;
+
+    /// Deals a hand at a table of three and plays some of it, journaling it
+    /// the way the room does
+    fn journaled_hand() -> (Table, TableJournal) {
+        let mut table = Table::default();
+        sit_alice_bob_carol(&mut table);
+        let next = table.next_game().unwrap();
+        let deck = table.shuffle(&next);
+        let mut snapshot = TableSnapshot::new(&table);
+        table.deal(&next, deck.clone()).unwrap();
+        snapshot.hand = Some(JournaledHand {
+            next,
+            deck,
+            started_at: table.game().unwrap().started_at,
+        });
+
+        let mut entries = vec![];
+        let raiser = table.current_player().unwrap().clone();
+        let action = Action::Raise(6);
+        table.game_mut().unwrap().act(action).unwrap();
+        entries.push(JournalEntry::Acted { action });
+        // The raise took 7 seconds of the raiser's time bank
+        table.use_time_bank(&raiser, 7).unwrap();
+        entries.push(JournalEntry::UsedTimeBank {
+            player: raiser,
+            secs: 7,
+        });
+
+        let dave = player("dave");
+        table.sit_player(dave.clone(), 50, Some(5)).unwrap();
+        entries.push(JournalEntry::Sat {
+            player: dave.clone(),
+            chips: 50,
+            seat: 5,
+        });
+        let seed = "dave's seed".to_owned();
+        table.set_client_seed(&dave, seed.clone()).unwrap();
+        entries.push(JournalEntry::ClientSeed { player: dave, seed });
+        // The player after next calls automatically, which deals the flop
+        let game = table.game().unwrap();
+        let calling = game.players[(game.current_player_idx() + 1) % 3]
+            .info
+            .clone();
+        table
+            .set_setting(&calling, PlayerSetting::CallAny, true)
+            .unwrap();
+        entries.push(JournalEntry::Setting {
+            player: calling,
+            setting: PlayerSetting::CallAny,
+            value: true,
+        });
+        let alice = player("alice");
+        table.top_up(&alice, Some(20), 20, true).unwrap();
+        entries.push(JournalEntry::ToppedUp {
+            player: alice,
+            chips: 20,
+            pending: true,
+        });
+
+        let action = Action::BetTo(6);
+        table.game_mut().unwrap().act(action).unwrap();
+        entries.push(JournalEntry::Acted { action });
+        (table, TableJournal { snapshot, entries })
+    }
+
+    #[test]
+    fn test_resume_plays_hand_back() {
+        let (table, journal) = journaled_hand();
+        let restored = journal
+            .restore(table.config.clone(), HandRecovery::Resume)
+            .unwrap();
+
+        let (game, restored_game) = (table.game().unwrap(), restored.game().unwrap());
+        assert_eq!(restored_game.state.hands, game.state.hands);
+        assert_eq!(restored_game.state.board, game.state.board);
+        assert_eq!(restored_game.state.stacks, game.state.stacks);
+        assert_eq!(restored_game.current_player(), game.current_player());
+        assert_eq!(restored_game.actions, game.actions);
+        assert_eq!(restored_game.started_at, game.started_at);
+        assert_eq!(restored_game.source, game.source);
+        assert_eq!(restored.commitment(), table.commitment());
+        assert!(restored_game.state.board.len() >= 3);
+
+        for (seat, restored_seat) in table.seats.iter().zip(&restored.seats) {
+            let mut seat = seat.clone();
+            if let Some(player) = &mut seat {
+                player.connected = false;
+            }
+            assert_eq!(restored_seat, &seat);
+        }
+        assert_eq!(restored.button_seat, table.button_seat);
+    }
+
+    #[test]
+    fn test_void_gives_back_what_was_put_in() {
+        let (table, journal) = journaled_hand();
+        let restored = journal
+            .restore(table.config.clone(), HandRecovery::Void)
+            .unwrap();
+
+        assert!(restored.game().is_none());
+        let stacks: Vec<_> = restored.players().map(|p| p.chips).collect();
+        // Alice's top-up no longer has a hand to wait for
+        assert_eq!(stacks, [120, 100, 100, 50]);
+        assert!(restored.players().all(|p| !p.connected && p.top_up == 0));
+        let time_banks: Vec<_> = restored.players().map(|p| p.time_bank).collect();
+        assert_eq!(time_banks.iter().filter(|secs| **secs == 23).count(), 1);
+
+        let refunds: Vec<_> = restored
+            .players()
+            .map(|p| (p.info.clone(), p.chips))
+            .collect();
+        assert_eq!(journal.refunds(), refunds);
+    }
+
+    #[test]
+    fn test_restore_needs_the_same_seats() {
+        let (table, journal) = journaled_hand();
+        let config = TableConfig {
+            max_players: 6,
+            ..table.config.clone()
+        };
+        assert!(journal.restore(config, HandRecovery::Resume).is_err());
+
+        let mut broken = journal.clone();
+        broken.entries.push(JournalEntry::Acted {
+            action: Action::Raise(1),
+        });
+        assert!(broken
+            .restore(table.config.clone(), HandRecovery::Resume)
+            .is_err());
+        assert!(broken.restore(table.config, HandRecovery::Void).is_ok());
+    }
+}
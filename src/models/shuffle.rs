@@ -0,0 +1,265 @@
+use crate::*;
+use alloy_primitives::keccak256;
+use rs_poker::core::{Card, FlatDeck, Hand, Suit, Value};
+
+/// Longest client seed a player can add to the shuffle
+pub const MAX_CLIENT_SEED_LEN: usize = 64;
+
+/// How a hand's deck was shuffled, from a server seed committed to before the
+/// deal and seeds the players dealt in chose themselves, so players can check
+/// the deal once the server seed is revealed. That shows the whole deck, so
+/// every hand dealt can be worked out after the hand, mucked ones included.
+///
+/// The deck starts in order, twos to aces with spades, clubs, hearts then
+/// diamonds of each. Mixing the seeds gives
+/// `seed = keccak256(server_seed ++ keccak256(client_seeds[0]) ++ ...)`, and
+/// swap `n` of a Fisher-Yates shuffle, counting from 0, swaps the card at
+/// `51 - n` with the one at `r % (52 - n)`, where `r` is the first 8 bytes of
+/// `keccak256(seed ++ [n])` read big-endian. Cards are dealt from the end:
+/// one at a time around the players until everyone has their hole cards, then
+/// the flop, turn and river.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Shuffle {
+    /// Kept secret until the hand is over
+    pub server_seed: B256,
+    /// What each player dealt in added, in the order they were dealt to
+    pub client_seeds: Vec<String>,
+}
+
+/// Where a hand's cards come from
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(tag = "source", rename_all = "camelCase")]
+pub enum DeckSource {
+    /// Provably fair, from a committed server seed and the players' seeds
+    Shuffle(Shuffle),
+    /// Shuffled the same way every time for the same seed, for tests
+    Seeded { seed: u64 },
+    /// Dealt in the order given, the first card first, followed by the rest
+    /// of the deck in order. Made with `DeckSource::stacked`, which checks
+    /// every card is different.
+    Stacked { cards: Vec<Card> },
+}
+
+impl DeckSource {
+    /// Deals `cards` first: hole cards one at a time around the players until
+    /// everyone has theirs, then the board
+    pub fn stacked(cards: Vec<Card>) -> Result<Self> {
+        for (idx, card) in cards.iter().enumerate() {
+            if cards[..idx].contains(card) {
+                bail!("{} is stacked twice", card)
+            }
+        }
+        Ok(Self::Stacked { cards })
+    }
+
+    /// The deck to deal from, which deals from the end
+    pub fn deck(&self) -> FlatDeck {
+        match self {
+            Self::Shuffle(shuffle) => shuffle.deck(),
+            Self::Seeded { seed } => shuffled_deck(keccak256(seed.to_be_bytes())),
+            Self::Stacked { cards } => {
+                let rest = ordered_deck()
+                    .into_iter()
+                    .filter(|card| !cards.contains(card));
+                let mut deck: Vec<Card> = cards.iter().copied().chain(rest).collect();
+                deck.reverse();
+                FlatDeck::from(deck)
+            }
+        }
+    }
+
+    /// The provably fair shuffle the cards came from, if they did
+    pub fn shuffle(&self) -> Option<&Shuffle> {
+        match self {
+            Self::Shuffle(shuffle) => Some(shuffle),
+            _ => None,
+        }
+    }
+}
+
+/// Twos to aces, with spades, clubs, hearts then diamonds of each
+fn ordered_deck() -> Vec<Card> {
+    Value::values()
+        .into_iter()
+        .flat_map(|value| Suit::suits().map(|suit| Card { value, suit }))
+        .collect()
+}
+
+/// Fisher-Yates shuffles the ordered deck with random numbers drawn from `seed`
+fn shuffled_deck(seed: B256) -> FlatDeck {
+    let mut cards = ordered_deck();
+    for n in 0..cards.len() - 1 {
+        let i = cards.len() - 1 - n;
+        let mut input = seed.to_vec();
+        input.push(n as u8);
+        let hash = keccak256(input);
+        let r = u64::from_be_bytes(hash[..8].try_into().expect("Hash has 32 bytes"));
+        // Bias from the modulo is under 2^-58, too small to matter
+        cards.swap(i, (r % (i as u64 + 1)) as usize);
+    }
+    FlatDeck::from(cards)
+}
+
+/// A new secret server seed
+pub fn random_seed() -> B256 {
+    B256::from(rand::random::<[u8; 32]>())
+}
+
+/// The hash of a server seed, published before the seed is used
+pub fn commitment(server_seed: &B256) -> B256 {
+    keccak256(server_seed)
+}
+
+impl Shuffle {
+    pub fn new(server_seed: B256, client_seeds: Vec<String>) -> Self {
+        Self {
+            server_seed,
+            client_seeds,
+        }
+    }
+
+    pub fn commitment(&self) -> B256 {
+        commitment(&self.server_seed)
+    }
+
+    /// The deck the seeds shuffle, dealt from the end
+    pub fn deck(&self) -> FlatDeck {
+        let mut mixed = self.server_seed.to_vec();
+        for client_seed in &self.client_seeds {
+            mixed.extend_from_slice(keccak256(client_seed.as_bytes()).as_slice());
+        }
+        shuffled_deck(keccak256(mixed))
+    }
+
+    /// Checks the server seed is the one committed to, and that the deck it
+    /// shuffles deals `hands` of `hole_cards` cards each and `board`. Hands
+    /// not known, left empty, are skipped.
+    pub fn verify(
+        &self,
+        commitment: &B256,
+        hole_cards: usize,
+        hands: &[Vec<Card>],
+        board: &[Card],
+    ) -> Result<()> {
+        if &self.commitment() != commitment {
+            bail!("Server seed doesn't match the commitment")
+        }
+        if hands.len() != self.client_seeds.len() {
+            bail!(
+                "{} players were dealt in but {} client seeds were used",
+                hands.len(),
+                self.client_seeds.len()
+            )
+        }
+        let mut deck = self.deck();
+        let mut dealt = vec![Hand::default(); hands.len()];
+        for _ in 0..hole_cards {
+            for hand in &mut dealt {
+                hand.push(deck.deal().expect("Deck should not be empty"));
+            }
+        }
+        for (idx, (hand, dealt)) in hands.iter().zip(&dealt).enumerate() {
+            if !hand.is_empty() && hand[..] != dealt[..] {
+                bail!("Player {}'s hole cards weren't dealt from the deck", idx)
+            }
+        }
+        for card in board {
+            if Some(*card) != deck.deal() {
+                bail!("Board wasn't dealt from the deck")
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn shuffle(client_seeds: &[&str]) -> Shuffle {
+        Shuffle::new(
+            B256::repeat_byte(7),
+            client_seeds.iter().map(|seed| seed.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_seeds_decide_the_deck() {
+        let deck = shuffle(&["alice", "bob"]).deck();
+        let mut cards = deck[..].to_vec();
+        assert_eq!(deck[..], shuffle(&["alice", "bob"]).deck()[..]);
+        assert_ne!(deck[..], shuffle(&["alice", "carol"]).deck()[..]);
+        assert_ne!(deck[..], shuffle(&["bob", "alice"]).deck()[..]);
+        // Seeds are hashed before they're joined, so where they split matters
+        assert_ne!(
+            shuffle(&["ab", "c"]).deck()[..],
+            shuffle(&["a", "bc"]).deck()[..]
+        );
+
+        cards.sort();
+        cards.dedup();
+        assert_eq!(cards.len(), 52);
+    }
+
+    #[test]
+    fn test_other_sources() {
+        let seeded = |seed| DeckSource::Seeded { seed }.deck()[..].to_vec();
+        assert_eq!(seeded(7), seeded(7));
+        assert_ne!(seeded(7), seeded(8));
+
+        let aces = ["As", "Ad", "Ah", "Ac"].map(|card| Card::try_from(card).unwrap());
+        let mut deck = DeckSource::stacked(aces.to_vec()).unwrap().deck();
+        for ace in aces {
+            assert_eq!(deck.deal(), Some(ace));
+        }
+        assert_eq!(deck.len(), 48);
+        assert!(DeckSource::stacked(vec![aces[0], aces[1], aces[0]]).is_err());
+    }
+
+    #[test]
+    fn test_verify_game() {
+        let game = Game::with_source(
+            "test".to_owned(),
+            ["alice", "bob", "carol"]
+                .map(|name| {
+                    GamePlayer::from(TablePlayer::new(
+                        Player::new(name.to_owned(), name.to_owned()),
+                        100,
+                    ))
+                })
+                .to_vec(),
+            Blinds::from_dealer(0, 3),
+            1,
+            2,
+            GameVariant::Holdem,
+            DeckSource::Shuffle(shuffle(&["alice", "", "carol"])),
+        );
+        let shuffle = game.shuffle().cloned().unwrap();
+        let commitment = shuffle.commitment();
+        let mut hands: Vec<_> = game
+            .state
+            .hands
+            .iter()
+            .map(|hand| hand[..].to_vec())
+            .collect();
+        let board = shuffle.deck()[43..46]
+            .iter()
+            .rev()
+            .copied()
+            .collect::<Vec<_>>();
+        shuffle.verify(&commitment, 2, &hands, &board).unwrap();
+
+        // Anyone's hand may be hidden, but none can be swapped
+        hands[1].clear();
+        shuffle.verify(&commitment, 2, &hands, &board[..1]).unwrap();
+        hands.swap(0, 2);
+        assert!(shuffle.verify(&commitment, 2, &hands, &board).is_err());
+        hands.swap(0, 2);
+        assert!(shuffle.verify(&commitment, 2, &hands, &board[1..]).is_err());
+
+        let other = Shuffle::new(B256::repeat_byte(8), shuffle.client_seeds.clone());
+        assert!(other.verify(&commitment, 2, &hands, &board).is_err());
+    }
+}
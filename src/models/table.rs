@@ -1,4 +1,5 @@
 use crate::*;
+use std::collections::VecDeque;
 
 pub type ChipInt = u64;
 pub type TableId = RoomId;
@@ -6,11 +7,39 @@ pub type TableId = RoomId;
 #[derive(Debug)]
 pub struct Table {
     pub config: TableConfig,
-    pub players: Vec<TablePlayer>,
+    /// Fixed seats up to `max_players`, `None` when empty
+    pub seats: Vec<Option<TablePlayer>>,
     pub game: Option<Game>,
+    /// Seats of the button and blinds in the last game, kept when players leave
+    pub button_seat: Option<usize>,
+    pub small_blind_seat: Option<usize>,
+    pub big_blind_seat: Option<usize>,
+    /// Seed the next hand is shuffled with, kept secret until it is over
+    pub server_seed: B256,
+    /// Decks to deal the next hands from, in order, before going back to
+    /// shuffling fairly, so tests can script hands
+    pub decks: VecDeque<DeckSource>,
+}
+
+/// Who gets dealt into the next game and where the button and blinds go,
+/// worked out before any flags change
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct NextGame {
+    /// Seats dealt in, in seat order
+    seats: Vec<usize>,
+    /// Seat of the button, which may be empty or sitting out
+    button: usize,
+    /// Seat of the small blind, which is dead if not dealt in
+    small_blind: usize,
+    big_blind: usize,
+    /// Seats entering out of position by posting a big blind
+    posting: Vec<usize>,
+    /// Seats that asked to sit out when the big blind reached them
+    sitting_out: Vec<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct TableConfig {
     pub id: TableId,
     pub name: String,
@@ -18,48 +47,207 @@ pub struct TableConfig {
     pub max_players: usize,
     pub small_blind: ChipInt,
     pub big_blind: ChipInt,
+    /// Smallest stack to sit down with, in big blinds
+    pub min_buy_in: ChipInt,
+    /// Largest stack to sit down with or top up to, in big blinds
+    pub max_buy_in: ChipInt,
+    /// Seconds of time bank each player starts with, and the most it holds
+    pub time_bank: u64,
+    /// Seconds added back to each player's time bank every
+    /// `time_bank_refill_hands` hands they are dealt in
+    pub time_bank_refill: u64,
+    pub time_bank_refill_hands: u32,
+    /// Seconds spectators are kept behind the game, so they can't relay it
+    /// to players
+    pub spectator_delay: u64,
+    /// Broadcasts kept for each subscriber before one who falls behind misses
+    /// some and is resynced
+    pub broadcast_capacity: usize,
+    pub variant: GameVariant,
+    /// Left out for the variant's usual structure
+    pub betting: Option<BettingStructure>,
+    pub ante: Option<Ante>,
+}
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            min_players: 2,
+            max_players: 9,
+            small_blind: 1,
+            big_blind: 2,
+            min_buy_in: 20,
+            max_buy_in: 100,
+            time_bank: 30,
+            time_bank_refill: 5,
+            time_bank_refill_hands: 10,
+            spectator_delay: 0,
+            broadcast_capacity: 64,
+            variant: GameVariant::Holdem,
+            betting: None,
+            ante: None,
+        }
+    }
+}
+
+/// A table as the lobby lists it
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct TableListing {
+    #[serde(flatten)]
+    pub config: TableConfig,
+    /// Player who created the table through the lobby, who may close it
+    pub owner: Option<PlayerId>,
+    /// Subscribers not sitting at the table
+    pub spectators: usize,
+}
+
+impl TableConfig {
+    pub fn betting_structure(&self) -> BettingStructure {
+        self.betting
+            .unwrap_or_else(|| self.variant.betting_structure())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("Table needs a name")
+        }
+        if self.min_players < 2 || self.max_players > 10 || self.min_players > self.max_players {
+            bail!("Tables seat between 2 and 10 players")
+        }
+        if self.max_players > self.variant.max_players() {
+            bail!(
+                "There aren't enough cards to deal {} players in",
+                self.max_players
+            )
+        }
+        if self.small_blind == 0 || self.big_blind < self.small_blind {
+            bail!("Big blind must be at least the small blind, which can't be 0")
+        }
+        self.betting_structure().validate()?;
+        if self.ante.is_some_and(|ante| ante.amount == 0) {
+            bail!("Ante can't be 0, leave it out instead")
+        }
+        if self.min_buy_in == 0 || self.min_buy_in > self.max_buy_in {
+            bail!("Minimum buy-in must be between 1 and the maximum buy-in")
+        }
+        if self.broadcast_capacity == 0 {
+            bail!("Broadcast capacity must be at least 1")
+        }
+        if self.time_bank_refill_hands == 0 {
+            bail!("Time bank must refill every 1 or more hands")
+        }
+        Ok(())
+    }
+}
+
+/// Chips added to a player's stack once the hand they were in finished
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopUp {
+    pub seat: usize,
+    pub player: Player,
+    /// Stack after the top-up
+    pub stack: ChipInt,
+    /// Chips that would have gone over the maximum buy-in
+    pub refunded: ChipInt,
+}
+
+/// What changed at the table once a game finished
+#[derive(Debug, Default)]
+pub struct CompletedGame {
+    pub top_ups: Vec<TopUp>,
+    /// Players who stood up or ran out of chips, with the seat they left
+    pub vacated: Vec<(usize, TablePlayer)>,
+}
+
+/// A choice a seated player makes about how they play
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PlayerSetting {
+    SitOutNextHand,
+    SitOutNextBigBlind,
+    WaitForBigBlind,
+    /// Only lasts for the hand in progress
+    CheckFold,
+    /// Only lasts for the hand in progress
+    CallAny,
+    AutoMuck,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub struct TablePlayer {
     pub info: Player,
+    pub chips: ChipInt,
+    /// Whether the player has paid a big blind since sitting down or coming back
     pub has_paid_big_blind: bool,
+    /// Whether a player entering the game waits for the big blind to reach them,
+    /// rather than posting a big blind to play straight away
     pub wait_for_big_blind: bool,
+    /// Whether the player is sitting out, keeping their seat without being dealt in
     pub sit_out_next_hand: bool,
+    /// Whether the player sits out once the big blind reaches them
     pub sit_out_next_big_blind: bool,
+    /// Whether the player stands up once the current hand is over
+    pub standing_up: bool,
+    /// Chips to add to the stack once the current hand is over
+    pub top_up: ChipInt,
+    pub auto_muck: bool,
+    /// Seconds left to act once the turn timer runs out
+    pub time_bank: u64,
+    /// Hands dealt in since the time bank was last refilled
+    pub hands_since_refill: u32,
+    /// Whether the player's socket is connected
+    pub connected: bool,
+    /// What the player adds to the shuffle of each hand they are dealt into
+    pub client_seed: String,
 }
 
 impl TablePlayer {
-    fn new(player: Player) -> Self {
+    pub fn new(player: Player, chips: ChipInt) -> Self {
         Self {
             info: player,
+            chips,
             has_paid_big_blind: false,
             wait_for_big_blind: true,
             sit_out_next_hand: false,
             sit_out_next_big_blind: false,
+            standing_up: false,
+            top_up: 0,
+            auto_muck: true,
+            time_bank: 0,
+            hands_since_refill: 0,
+            connected: true,
+            client_seed: String::new(),
         }
     }
-}
 
-impl From<Player> for TablePlayer {
-    fn from(player: Player) -> Self {
-        Self::new(player)
+    /// What goes back to the player when they leave, including a top-up
+    /// still waiting for the hand to finish
+    pub fn stack(&self) -> ChipInt {
+        self.chips + self.top_up
     }
 }
 
 impl Table {
     pub fn new(id: String, name: String) -> Self {
+        Self::from_config(TableConfig {
+            id,
+            name,
+            ..Default::default()
+        })
+    }
+
+    pub fn from_config(config: TableConfig) -> Self {
         Self {
-            config: TableConfig {
-                id,
-                name,
-                min_players: 2,
-                max_players: 9,
-                small_blind: 1,
-                big_blind: 2,
-            },
-            players: vec![],
+            seats: vec![None; config.max_players],
+            config,
             game: None,
+            button_seat: None,
+            small_blind_seat: None,
+            big_blind_seat: None,
+            server_seed: random_seed(),
+            decks: VecDeque::new(),
         }
     }
 
@@ -68,7 +256,62 @@ impl Table {
     }
 
     pub fn num_players(&self) -> usize {
-        self.players.len()
+        self.players().count()
+    }
+
+    /// Seated players in seat order
+    pub fn players(&self) -> impl Iterator<Item = &TablePlayer> {
+        self.seats.iter().flatten()
+    }
+
+    pub fn player_seat(&self, player: &Player) -> Option<usize> {
+        self.seats
+            .iter()
+            .position(|seat| seat.as_ref().is_some_and(|p| p.info.id == player.id))
+    }
+
+    fn table_player_mut(&mut self, player: &Player) -> Result<&mut TablePlayer> {
+        self.seats
+            .iter_mut()
+            .flatten()
+            .find(|p| p.info.id == player.id)
+            .ok_or_else(|| eyre!("Player not found"))
+    }
+
+    /// Sits the player in their preferred seat, or the first empty one.
+    /// Returns the seat number.
+    pub fn sit_player(
+        &mut self,
+        player: Player,
+        chips: ChipInt,
+        seat: Option<usize>,
+    ) -> Result<usize> {
+        if self.player_seat(&player).is_some() {
+            bail!("Already sitting at table")
+        }
+        if chips == 0 {
+            bail!("Must buy in with chips")
+        }
+        if chips < self.min_buy_in() {
+            bail!("Minimum buy-in is {}", self.min_buy_in())
+        }
+        if chips > self.max_buy_in() {
+            bail!("Maximum buy-in is {}", self.max_buy_in())
+        }
+        let seat = match seat {
+            Some(seat) if seat >= self.seats.len() => bail!("No seat {}", seat),
+            Some(seat) if self.seats[seat].is_some() => bail!("Seat {} is taken", seat),
+            Some(seat) => seat,
+            None => self
+                .seats
+                .iter()
+                .position(Option::is_none)
+                .ok_or_else(|| eyre!("Table is full"))?,
+        };
+        let mut table_player = TablePlayer::new(player, chips);
+        table_player.time_bank = self.config.time_bank;
+        self.seats[seat] = Some(table_player);
+        Ok(seat)
     }
 
     pub fn min_players(&self) -> usize {
@@ -87,6 +330,49 @@ impl Table {
         self.config.small_blind
     }
 
+    /// Minimum buy-in in chips
+    pub fn min_buy_in(&self) -> ChipInt {
+        self.config.min_buy_in * self.config.big_blind
+    }
+
+    /// Maximum buy-in in chips
+    pub fn max_buy_in(&self) -> ChipInt {
+        self.config.max_buy_in * self.config.big_blind
+    }
+
+    /// Adds chips to a seated player's stack, up to the maximum buy-in. `None`
+    /// adds on as much as `balance` allows towards the maximum. Players in a
+    /// hand get their chips once it is over, which also lets a busted player
+    /// rebuy. Returns the chips taken from the balance.
+    pub fn top_up(
+        &mut self,
+        player: &Player,
+        chips: Option<ChipInt>,
+        balance: ChipInt,
+        in_hand: bool,
+    ) -> Result<ChipInt> {
+        let max_buy_in = self.max_buy_in();
+        let table_player = self.table_player_mut(player)?;
+        let stack = table_player.chips + table_player.top_up;
+        let room = max_buy_in.saturating_sub(stack);
+        if room == 0 {
+            bail!("Stack is already at the maximum buy-in of {}", max_buy_in)
+        }
+        let chips = chips.unwrap_or(room.min(balance));
+        if chips == 0 {
+            bail!("Must top up with chips")
+        }
+        if chips > room {
+            bail!("Can top up by at most {}", room)
+        }
+        if in_hand {
+            table_player.top_up += chips;
+        } else {
+            table_player.chips += chips;
+        }
+        Ok(chips)
+    }
+
     pub fn game(&self) -> Option<&Game> {
         self.game.as_ref()
     }
@@ -108,30 +394,57 @@ impl Table {
         self.config.big_blind = big_blind;
     }
 
-    pub fn set_wait_for_big_blind(&mut self, player: &Player, value: bool) -> Result<()> {
-        if let Some(idx) = self.players.iter().position(|p| p.info.id == player.id) {
-            self.players.get_mut(idx).unwrap().wait_for_big_blind = value;
-            Ok(())
-        } else {
-            bail!("Player not found")
+    pub fn set_setting(
+        &mut self,
+        player: &Player,
+        setting: PlayerSetting,
+        value: bool,
+    ) -> Result<()> {
+        match setting {
+            PlayerSetting::SitOutNextHand => self.set_sit_out_next_hand(player, value),
+            PlayerSetting::SitOutNextBigBlind => self.set_sit_out_next_big_blind(player, value),
+            PlayerSetting::WaitForBigBlind => self.set_wait_for_big_blind(player, value),
+            PlayerSetting::CheckFold => self.set_check_fold(player, value),
+            PlayerSetting::CallAny => self.set_call_any(player, value),
+            PlayerSetting::AutoMuck => self.set_auto_muck(player, value),
+        }
+    }
+
+    /// Hash of the seed the next hand is shuffled with
+    pub fn commitment(&self) -> B256 {
+        commitment(&self.server_seed)
+    }
+
+    pub fn set_client_seed(&mut self, player: &Player, seed: String) -> Result<()> {
+        if seed.len() > MAX_CLIENT_SEED_LEN {
+            bail!("Client seed can be at most {} bytes", MAX_CLIENT_SEED_LEN)
         }
+        self.table_player_mut(player)?.client_seed = seed;
+        Ok(())
+    }
+
+    pub fn set_wait_for_big_blind(&mut self, player: &Player, value: bool) -> Result<()> {
+        self.table_player_mut(player)?.wait_for_big_blind = value;
+        Ok(())
     }
     pub fn set_sit_out_next_hand(&mut self, player: &Player, value: bool) -> Result<()> {
-        if let Some(idx) = self.players.iter().position(|p| p.info.id == player.id) {
-            self.players.get_mut(idx).unwrap().sit_out_next_hand = value;
-            Ok(())
-        } else {
-            bail!("Player not found")
+        let table_player = self.table_player_mut(player)?;
+        table_player.sit_out_next_hand = value;
+        if value {
+            // Coming back means waiting for, or posting, a big blind again
+            table_player.has_paid_big_blind = false;
         }
+        Ok(())
     }
 
     pub fn set_sit_out_next_big_blind(&mut self, player: &Player, value: bool) -> Result<()> {
-        if let Some(idx) = self.players.iter().position(|p| p.info.id == player.id) {
-            self.players.get_mut(idx).unwrap().sit_out_next_big_blind = value;
-            Ok(())
-        } else {
-            bail!("Player not found")
-        }
+        self.table_player_mut(player)?.sit_out_next_big_blind = value;
+        Ok(())
+    }
+
+    pub fn set_auto_muck(&mut self, player: &Player, value: bool) -> Result<()> {
+        self.table_player_mut(player)?.auto_muck = value;
+        Ok(())
     }
 
     pub fn set_check_fold(&mut self, player: &Player, value: bool) -> Result<()> {
@@ -161,73 +474,323 @@ impl Table {
     }
 
     pub fn start_new_game(&mut self) -> Result<()> {
-        let players = self.get_players_for_next_game();
-        if players.len() < self.min_players() {
+        let next = self.next_game()?;
+        let source = self.shuffle(&next);
+        self.deal(&next, source)
+    }
+
+    /// Who would be dealt into a game started now
+    pub fn next_game(&self) -> Result<NextGame> {
+        let next = self.get_players_for_next_game();
+        if next.seats.len() < self.min_players().max(2) {
             bail!("Not enough players to start game");
         }
-        let dealer_idx = self.get_next_dealer_idx(&players);
-        let new_game = Game::new(
+        Ok(next)
+    }
+
+    /// Shuffles for the players in `next` with the committed server seed and
+    /// their client seeds, committing to a new seed for the hand after.
+    /// Decks lined up in `decks` are dealt first.
+    pub fn shuffle(&mut self, next: &NextGame) -> DeckSource {
+        if let Some(source) = self.decks.pop_front() {
+            return source;
+        }
+        let client_seeds = next
+            .seats
+            .iter()
+            .map(|seat| {
+                self.seats
+                    .get(*seat)
+                    .and_then(Option::as_ref)
+                    .map(|player| player.client_seed.clone())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let server_seed = std::mem::replace(&mut self.server_seed, random_seed());
+        DeckSource::Shuffle(Shuffle::new(server_seed, client_seeds))
+    }
+
+    /// Starts a game with the players in `next`, dealt from the deck `source`
+    /// gives
+    pub fn deal(&mut self, next: &NextGame, source: DeckSource) -> Result<()> {
+        let seats = next
+            .seats
+            .iter()
+            .chain(&next.posting)
+            .chain(&next.sitting_out);
+        for seat in seats.chain([&next.button, &next.small_blind, &next.big_blind]) {
+            if *seat >= self.seats.len() {
+                bail!("No seat {}", seat)
+            }
+        }
+        if let Some(seat) = next.seats.iter().find(|seat| self.seats[**seat].is_none()) {
+            bail!("Seat {} is empty", seat)
+        }
+
+        for seat in &next.sitting_out {
+            if let Some(player) = &mut self.seats[*seat] {
+                player.sit_out_next_hand = true;
+                player.sit_out_next_big_blind = false;
+                player.has_paid_big_blind = false;
+            }
+        }
+
+        let players: Vec<GamePlayer> = next
+            .seats
+            .iter()
+            .map(|seat| {
+                let table_player = self.seats[*seat].clone().expect("Seat should be taken");
+                let mut player = GamePlayer::from(table_player);
+                player.seat = *seat;
+                player.posts_big_blind = next.posting.contains(seat);
+                player
+            })
+            .collect();
+        let game_idx = |seat: usize| next.seats.iter().position(|s| *s == seat);
+        // With a dead button, the last player before it acts last after the flop
+        let dealer = (0..next.seats.len())
+            .map(|offset| (next.button + self.seats.len() - offset) % self.seats.len())
+            .find_map(game_idx)
+            .expect("Players should be dealt in");
+        let blinds = Blinds {
+            dealer,
+            small_blind: game_idx(next.small_blind),
+            big_blind: game_idx(next.big_blind).expect("Big blind should be dealt in"),
+            ante: self.config.ante,
+        };
+        let mut new_game = Game::with_source(
             self.id().clone(),
             players,
-            dealer_idx,
+            blinds,
             self.small_blind(),
             self.big_blind(),
+            self.config.variant,
+            source,
         );
-
+        new_game.button_seat = next.button;
+        new_game.betting = self.config.betting_structure();
+        new_game.next_commitment = Some(self.commitment());
         self.game = Some(new_game);
 
-        let big_blind_idx = self.get_big_blind_idx();
-        self.players
-            .get_mut(big_blind_idx)
-            .unwrap()
-            .has_paid_big_blind = true;
+        self.button_seat = Some(next.button);
+        self.small_blind_seat = Some(next.small_blind);
+        self.big_blind_seat = Some(next.big_blind);
+        for seat in next.posting.iter().chain([&next.big_blind]) {
+            if let Some(player) = &mut self.seats[*seat] {
+                player.has_paid_big_blind = true;
+            }
+        }
+        self.refill_time_banks(&next.seats);
         Ok(())
     }
 
-    fn get_next_dealer_idx(&self, next_players: &[GamePlayer]) -> usize {
-        self.game
-            .as_ref()
-            .map_or(0, |game| (game.state.dealer_idx + 1) % next_players.len())
+    /// Counts a hand dealt to each of `seats`, topping up time banks every
+    /// `time_bank_refill_hands` hands
+    fn refill_time_banks(&mut self, seats: &[usize]) {
+        let config = &self.config;
+        for seat in seats {
+            if let Some(player) = &mut self.seats[*seat] {
+                player.hands_since_refill += 1;
+                if player.hands_since_refill >= config.time_bank_refill_hands {
+                    player.hands_since_refill = 0;
+                    player.time_bank =
+                        (player.time_bank + config.time_bank_refill).min(config.time_bank);
+                }
+            }
+        }
     }
 
-    fn get_dealer_idx(&self) -> usize {
-        self.game.as_ref().map_or(0, |game| game.state.dealer_idx)
+    /// Takes `secs` the player used from their time bank
+    pub fn use_time_bank(&mut self, player: &Player, secs: u64) -> Result<()> {
+        let table_player = self.table_player_mut(player)?;
+        table_player.time_bank = table_player.time_bank.saturating_sub(secs);
+        Ok(())
     }
 
-    fn get_big_blind_idx(&self) -> usize {
-        let dealer_idx = self.get_dealer_idx();
-        let current_players = self.game.as_ref().unwrap().players.as_slice();
-        (dealer_idx + 2) % current_players.len()
+    pub fn set_connected(&mut self, player: &Player, value: bool) -> Result<()> {
+        self.table_player_mut(player)?.connected = value;
+        Ok(())
     }
 
-    fn get_players_for_next_game(&self) -> Vec<GamePlayer> {
+    /// Writes the stacks from the finished game back to the seated players and
+    /// adds any top-ups, then stands up every player who has no chips left or
+    /// asked to stand up
+    pub fn complete_game(&mut self) -> CompletedGame {
+        let Some(game) = self.game() else {
+            return CompletedGame::default();
+        };
+        let stacks: Vec<_> = game
+            .players
+            .iter()
+            .map(|p| p.seat)
+            .zip(game.state.stacks.iter().map(|s| *s as ChipInt))
+            .collect();
+
+        for (seat, stack) in stacks {
+            if let Some(player) = &mut self.seats[seat] {
+                player.chips = stack;
+            }
+        }
+
+        let max_buy_in = self.max_buy_in();
+        let mut top_ups = vec![];
+        for (seat, slot) in self.seats.iter_mut().enumerate() {
+            let Some(player) = slot.as_mut().filter(|p| p.top_up > 0 && !p.standing_up) else {
+                continue;
+            };
+            // Winning the hand can leave less room under the maximum
+            let added = player.top_up.min(max_buy_in.saturating_sub(player.chips));
+            player.chips += added;
+            top_ups.push(TopUp {
+                seat,
+                player: player.info.clone(),
+                stack: player.chips,
+                refunded: player.top_up - added,
+            });
+            player.top_up = 0;
+        }
+
+        let mut vacated = vec![];
+        for (seat, slot) in self.seats.iter_mut().enumerate() {
+            if slot.as_ref().is_some_and(|p| p.chips == 0 || p.standing_up) {
+                vacated.push((seat, slot.take().unwrap()));
+            }
+        }
+        CompletedGame { top_ups, vacated }
+    }
+
+    /// Works out who is dealt into the next game, using dead button rules.
+    ///
+    /// Players who played the last game stay in. The big blind moves to the next
+    /// seat that isn't sitting out, which deals in a player waiting for it, or
+    /// sits out a player who asked to sit out at their next big blind. The small
+    /// blind goes to the last big blind and the button to the last small blind,
+    /// so either can be dead when those players have left. Players not waiting
+    /// for the big blind are dealt in straight away and post one, unless they
+    /// sat down between the button and the big blind. A table without two
+    /// players from the last game starts fresh and deals everyone in.
+    fn get_players_for_next_game(&self) -> NextGame {
         let ids_in_last_game = self.game().map_or(vec![], |g| {
             g.players.iter().map(|p| p.info.id.clone()).collect()
         });
+        let num_seats = self.seats.len();
+        let player = |seat: usize| self.seats[seat].as_ref();
+        let can_play =
+            |seat: usize| player(seat).is_some_and(|p| !p.sit_out_next_hand && p.chips > 0);
+        let in_last_game =
+            |seat: usize| player(seat).is_some_and(|p| ids_in_last_game.contains(&p.info.id));
+        let in_rotation = |seat: &usize| can_play(*seat) && in_last_game(*seat);
+        // Seats clockwise from `seat`, not including it
+        let clockwise = |seat: usize| (1..num_seats).map(move |offset| (seat + offset) % num_seats);
 
-        // TODO: Filter players sitting out next big blind
-        let players_from_last_game: Vec<_> = self
-            .players
-            .clone()
-            .into_iter()
-            .filter(|p| ids_in_last_game.contains(&p.info.id))
-            .filter(|p| !p.sit_out_next_hand)
-            .map(GamePlayer::from)
-            .collect();
+        let (Some(last_small_blind), Some(last_big_blind)) =
+            (self.small_blind_seat, self.big_blind_seat)
+        else {
+            return self.fresh_start();
+        };
+        if (0..num_seats).filter(in_rotation).count() < 2 {
+            return self.fresh_start();
+        }
 
-        // TODO: Filter players waiting for big blind
-        let new_players: Vec<_> = self
-            .players
-            .clone()
-            .into_iter()
-            .filter(|p| !ids_in_last_game.contains(&p.info.id))
-            .map(GamePlayer::from)
+        let mut sitting_out = vec![];
+        let mut big_blind = None;
+        for seat in clockwise(last_big_blind).chain([last_big_blind]) {
+            if !can_play(seat) {
+                continue;
+            }
+            if player(seat).is_some_and(|p| p.sit_out_next_big_blind) {
+                sitting_out.push(seat);
+                continue;
+            }
+            big_blind = Some(seat);
+            break;
+        }
+        let Some(big_blind) = big_blind else {
+            return NextGame::default();
+        };
+
+        // Coming from heads-up the last small blind was on the button, so the
+        // button goes just before the new small blind instead
+        let small_blind = last_big_blind;
+        let button = if last_small_blind == big_blind {
+            (small_blind + num_seats - 1) % num_seats
+        } else {
+            last_small_blind
+        };
+        // Players entering now can't be dealt in on the button or in the blinds
+        let is_before_big_blind = |seat: usize| {
+            seat == button
+                || clockwise(button)
+                    .take_while(|s| *s != big_blind)
+                    .any(|s| s == seat)
+        };
+        let posting: Vec<usize> = (0..num_seats)
+            .filter(|seat| {
+                can_play(*seat)
+                    && !in_last_game(*seat)
+                    && *seat != big_blind
+                    && !sitting_out.contains(seat)
+                    && player(*seat).is_some_and(|p| !p.wait_for_big_blind)
+                    && !is_before_big_blind(*seat)
+            })
+            .collect();
+        let seats: Vec<usize> = (0..num_seats)
+            .filter(|seat| {
+                (in_rotation(seat) && !sitting_out.contains(seat))
+                    || *seat == big_blind
+                    || posting.contains(seat)
+            })
             .collect();
 
-        players_from_last_game
-            .into_iter()
-            .chain(new_players)
-            .collect()
+        if seats.len() == 2 {
+            // Heads-up the button posts the small blind
+            let other = seats.iter().copied().find(|s| *s != big_blind).unwrap();
+            return NextGame {
+                seats,
+                button: other,
+                small_blind: other,
+                big_blind,
+                posting,
+                sitting_out,
+            };
+        }
+
+        NextGame {
+            seats,
+            button,
+            small_blind,
+            big_blind,
+            posting,
+            sitting_out,
+        }
+    }
+
+    /// Deals in everyone who can play, with the button on the next seat after
+    /// the last button
+    fn fresh_start(&self) -> NextGame {
+        let seats: Vec<usize> = (0..self.seats.len())
+            .filter(|seat| {
+                self.seats[*seat]
+                    .as_ref()
+                    .is_some_and(|p| !p.sit_out_next_hand && p.chips > 0)
+            })
+            .collect();
+        let num_players = seats.len();
+        if num_players < 2 {
+            return NextGame::default();
+        }
+        let button = self.button_seat.map_or(0, |last| {
+            seats.iter().position(|seat| *seat > last).unwrap_or(0)
+        });
+        let blinds = Blinds::from_dealer(button, num_players);
+        NextGame {
+            button: seats[button],
+            small_blind: seats[blinds.small_blind.unwrap()],
+            big_blind: seats[blinds.big_blind],
+            seats,
+            posting: vec![],
+            sitting_out: vec![],
+        }
     }
 }
 
@@ -236,3 +799,450 @@ impl Default for Table {
         Table::new(69420.to_string(), "Pocket Rocket Dreams".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rs_poker::core::Card;
+    use test_log::test;
+
+    #[test]
+    fn test_config_leaves_lobby_fields_to_the_server() {
+        for field in ["owner", "spectators"] {
+            let json = format!(r#"{{ "name": "Mine", "{field}": null }}"#);
+            assert!(serde_json::from_str::<TableConfig>(&json).is_err());
+        }
+    }
+
+    fn sit_players(table: &mut Table, seats: &[(usize, ChipInt)]) {
+        for (seat, chips) in seats {
+            let player = Player::new(seat.to_string(), seat.to_string());
+            table.sit_player(player, *chips, Some(*seat)).unwrap();
+        }
+    }
+
+    fn seated(table: &Table, seat: usize) -> &TablePlayer {
+        table.seats[seat].as_ref().unwrap()
+    }
+
+    fn fold_to_winner(table: &mut Table) {
+        let game = table.game_mut().unwrap();
+        while !game.is_over() {
+            game.fold();
+        }
+        table.complete_game();
+    }
+
+    fn game_seats(table: &Table) -> Vec<usize> {
+        let game = table.game().unwrap();
+        game.players.iter().map(|p| p.seat).collect()
+    }
+
+    #[test]
+    fn test_sit_player() -> Result<()> {
+        let mut table = Table::default();
+        let player = |id: &str| Player::new(id.into(), id.into());
+        assert_eq!(table.sit_player(player("a"), 100, Some(4))?, 4);
+        assert_eq!(table.sit_player(player("b"), 100, None)?, 0);
+        assert_eq!(
+            table
+                .sit_player(player("c"), 100, Some(4))
+                .unwrap_err()
+                .to_string(),
+            "Seat 4 is taken"
+        );
+        assert_eq!(
+            table
+                .sit_player(player("c"), 100, Some(9))
+                .unwrap_err()
+                .to_string(),
+            "No seat 9"
+        );
+        assert_eq!(
+            table
+                .sit_player(player("a"), 100, None)
+                .unwrap_err()
+                .to_string(),
+            "Already sitting at table"
+        );
+        for id in 0..7 {
+            table.sit_player(player(&id.to_string()), 100, None)?;
+        }
+        assert_eq!(
+            table
+                .sit_player(player("c"), 100, None)
+                .unwrap_err()
+                .to_string(),
+            "Table is full"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let config = Table::default().config;
+        assert!(config.validate().is_ok());
+        for (config, err) in [
+            (
+                TableConfig {
+                    name: " ".into(),
+                    ..config.clone()
+                },
+                "Table needs a name",
+            ),
+            (
+                TableConfig {
+                    max_players: 11,
+                    ..config.clone()
+                },
+                "Tables seat between 2 and 10 players",
+            ),
+            (
+                TableConfig {
+                    big_blind: 0,
+                    ..config.clone()
+                },
+                "Big blind must be at least the small blind, which can't be 0",
+            ),
+            (
+                TableConfig {
+                    min_buy_in: 200,
+                    ..config.clone()
+                },
+                "Minimum buy-in must be between 1 and the maximum buy-in",
+            ),
+        ] {
+            assert_eq!(config.validate().unwrap_err().to_string(), err);
+        }
+    }
+
+    #[test]
+    fn test_buy_in_limits() {
+        let mut table = Table::default();
+        let player = Player::new("a".into(), "a".into());
+        for (chips, err) in [
+            (0, "Must buy in with chips"),
+            (39, "Minimum buy-in is 40"),
+            (201, "Maximum buy-in is 200"),
+        ] {
+            let result = table.sit_player(player.clone(), chips, None);
+            assert_eq!(result.unwrap_err().to_string(), err);
+        }
+        assert!(table.sit_player(player, 40, None).is_ok());
+    }
+
+    #[test]
+    fn test_top_up_between_hands() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100)]);
+        let player = seated(&table, 0).info.clone();
+
+        assert_eq!(table.top_up(&player, Some(50), 1000, false)?, 50);
+        // Adding on to the maximum is limited by the balance
+        assert_eq!(table.top_up(&player, None, 30, false)?, 30);
+        assert_eq!(seated(&table, 0).chips, 180);
+        let err = table.top_up(&player, Some(50), 1000, false).unwrap_err();
+        assert_eq!(err.to_string(), "Can top up by at most 20");
+        assert_eq!(table.top_up(&player, None, 1000, false)?, 20);
+        let err = table.top_up(&player, None, 1000, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Stack is already at the maximum buy-in of 200"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_bank_refills() -> Result<()> {
+        let mut table = Table::default();
+        table.config.time_bank_refill_hands = 2;
+        sit_players(&mut table, &[(0, 100), (1, 100)]);
+        let player = seated(&table, 0).info.clone();
+        assert_eq!(seated(&table, 0).time_bank, 30);
+
+        table.use_time_bank(&player, 12)?;
+        table.start_new_game()?;
+        fold_to_winner(&mut table);
+        assert_eq!(seated(&table, 0).time_bank, 18);
+        table.start_new_game()?;
+        fold_to_winner(&mut table);
+        assert_eq!(seated(&table, 0).time_bank, 23);
+        // Refills don't go over the starting time bank
+        assert_eq!(seated(&table, 1).time_bank, 30);
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_up_during_hand() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100), (1, 100)]);
+        table.start_new_game()?;
+        for seat in [0, 1] {
+            let player = seated(&table, seat).info.clone();
+            table.top_up(&player, Some(100), 1000, true)?;
+        }
+        assert_eq!(seated(&table, 0).chips, 100);
+
+        // The button folds the small blind, the big blind wins one chip
+        table.game_mut().unwrap().fold();
+        let completed = table.complete_game();
+        assert_eq!(seated(&table, 0).chips, 199);
+        assert_eq!(seated(&table, 1).chips, 200);
+        let refunds: Vec<_> = completed
+            .top_ups
+            .iter()
+            .map(|t| (t.seat, t.stack, t.refunded))
+            .collect();
+        assert_eq!(refunds, vec![(0, 199, 0), (1, 200, 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebuy_keeps_busted_player_seated() -> Result<()> {
+        let mut table = Table::default();
+        table.config.min_buy_in = 1;
+        sit_players(&mut table, &[(0, 100), (1, 2)]);
+        table.start_new_game()?;
+        let player = seated(&table, 1).info.clone();
+        table.top_up(&player, Some(50), 1000, true)?;
+
+        // Big blind is all-in from posting
+        table.game_mut().unwrap().act(Action::Call)?;
+        let completed = table.complete_game();
+        assert!(completed.vacated.is_empty());
+        assert!(seated(&table, 1).chips >= 50);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stacks_carry_between_games() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 50), (1, 150)]);
+        table.start_new_game()?;
+
+        let game = table.game_mut().unwrap();
+        assert_eq!(game.state.stacks, vec![49, 148]);
+        // Heads-up the button posts the small blind and folds to the big blind
+        game.fold();
+        assert!(game.is_over());
+
+        let completed = table.complete_game();
+        assert!(completed.vacated.is_empty());
+        assert_eq!(seated(&table, 0).chips, 49);
+        assert_eq!(seated(&table, 1).chips, 151);
+
+        table.start_new_game()?;
+        let game = table.game().unwrap();
+        assert_eq!(game.players[0].chips, 49);
+        assert_eq!(game.players[1].chips, 151);
+        Ok(())
+    }
+
+    #[test]
+    fn test_busted_players_stand_up() -> Result<()> {
+        let mut table = Table::default();
+        table.config.min_buy_in = 1;
+        sit_players(&mut table, &[(0, 100), (3, 2)]);
+        // Aces for the small blind against seven-deuce for the big blind
+        let stacked = ["As", "7c", "Ah", "2d", "Kd", "Qh", "9s", "5c", "4h"]
+            .map(|card| Card::try_from(card).unwrap());
+        table
+            .decks
+            .push_back(DeckSource::stacked(stacked.to_vec())?);
+        table.start_new_game()?;
+
+        let game = table.game_mut().unwrap();
+        // Big blind is all-in from posting, small blind calls it off
+        game.act(Action::Call).unwrap();
+        assert!(game.is_over());
+        assert!(game.is_winner(0) && !game.is_winner(1));
+
+        let busted = table.complete_game().vacated;
+        assert_eq!(busted.len(), 1);
+        assert_eq!(busted[0].0, 3);
+        assert_eq!(busted[0].1.info, Player::new("3".into(), "3".into()));
+        assert!(table.seats[3].is_none());
+        assert_eq!(table.num_players(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_standing_up_after_hand() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100), (1, 100), (5, 100)]);
+        table.start_new_game()?;
+        table.seats[1].as_mut().unwrap().standing_up = true;
+        fold_to_winner(&mut table);
+
+        assert!(table.seats[1].is_none());
+        assert_eq!(table.num_players(), 2);
+        table.start_new_game()?;
+        assert_eq!(game_seats(&table), vec![0, 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_button_moves_around_fixed_seats() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(1, 100), (4, 100), (6, 100)]);
+
+        let mut positions = vec![];
+        for _ in 0..4 {
+            table.start_new_game()?;
+            positions.push((
+                table.button_seat.unwrap(),
+                table.small_blind_seat.unwrap(),
+                table.big_blind_seat.unwrap(),
+            ));
+            fold_to_winner(&mut table);
+        }
+        assert_eq!(positions, vec![(1, 4, 6), (4, 6, 1), (6, 1, 4), (1, 4, 6)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dead_button() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100), (1, 100), (2, 100), (3, 100)]);
+        table.start_new_game()?;
+        fold_to_winner(&mut table);
+
+        // The small blind leaves, so the button stays on their empty seat
+        table.seats[1] = None;
+        table.start_new_game()?;
+        assert_eq!(table.button_seat, Some(1));
+        assert_eq!(table.small_blind_seat, Some(2));
+        assert_eq!(table.big_blind_seat, Some(3));
+
+        let game = table.game().unwrap();
+        assert_eq!(game.button_seat, 1);
+        assert_eq!(game.state.dealer_idx, 0);
+        assert_eq!(game.players_bet(1), 1);
+        assert_eq!(game.players_bet(2), 2);
+        assert_eq!(game.current_player().id, "0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_dead_small_blind() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100), (1, 100), (2, 100), (3, 100)]);
+        table.start_new_game()?;
+        fold_to_winner(&mut table);
+
+        // The big blind leaves, so nobody posts the small blind
+        table.seats[2] = None;
+        table.start_new_game()?;
+        assert_eq!(table.button_seat, Some(1));
+        assert_eq!(table.small_blind_seat, Some(2));
+        assert_eq!(table.big_blind_seat, Some(3));
+
+        let game = table.game().unwrap();
+        assert_eq!(game_seats(&table), vec![0, 1, 3]);
+        assert_eq!(game.state.total_pot, 2);
+        assert_eq!(game.players_bet(2), 2);
+        assert_eq!(game.current_player().id, "0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_waiting_player_dealt_in_at_big_blind() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100), (2, 100)]);
+        table.start_new_game()?;
+        assert_eq!(table.big_blind_seat, Some(2));
+        fold_to_winner(&mut table);
+
+        sit_players(&mut table, &[(1, 100)]);
+        table.start_new_game()?;
+        // The big blind moved to seat 0, so the new player keeps waiting
+        assert_eq!(table.big_blind_seat, Some(0));
+        assert_eq!(game_seats(&table), vec![0, 2]);
+        fold_to_winner(&mut table);
+
+        table.start_new_game()?;
+        assert_eq!(table.big_blind_seat, Some(1));
+        assert_eq!(game_seats(&table), vec![0, 1, 2]);
+        assert_eq!(table.game().unwrap().players_bet(1), 2);
+        assert!(seated(&table, 1).has_paid_big_blind);
+        Ok(())
+    }
+
+    #[test]
+    fn test_player_posts_big_blind_to_play_now() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100), (2, 100), (4, 100)]);
+        table.start_new_game()?;
+        fold_to_winner(&mut table);
+
+        sit_players(&mut table, &[(1, 100), (3, 100)]);
+        for seat in [1, 3] {
+            let player = seated(&table, seat).info.clone();
+            table.set_wait_for_big_blind(&player, false)?;
+        }
+        table.start_new_game()?;
+        assert_eq!(table.big_blind_seat, Some(0));
+        // Seat 3 is between the button and the big blind, so has to wait
+        assert_eq!(game_seats(&table), vec![0, 1, 2, 4]);
+
+        let game = table.game().unwrap();
+        // Blinds from seats 4 and 0, a live post from seat 1
+        assert_eq!(game.players_bet(3), 1);
+        assert_eq!(game.players_bet(0), 2);
+        assert_eq!(game.players_bet(1), 2);
+        assert_eq!(game.state.stacks[1], 98);
+        assert_eq!(game.state.total_pot, 5);
+        assert!(seated(&table, 1).has_paid_big_blind);
+        assert!(!seated(&table, 3).has_paid_big_blind);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sit_out_at_next_big_blind() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100), (1, 100), (2, 100)]);
+        table.start_new_game()?;
+        assert_eq!(table.big_blind_seat, Some(2));
+        fold_to_winner(&mut table);
+
+        let player = seated(&table, 0).info.clone();
+        table.set_sit_out_next_big_blind(&player, true)?;
+        table.start_new_game()?;
+        // Seat 0 sits out rather than paying the big blind, keeping their seat
+        assert_eq!(table.big_blind_seat, Some(1));
+        assert_eq!(game_seats(&table), vec![1, 2]);
+        assert_eq!(table.num_players(), 3);
+        assert!(seated(&table, 0).sit_out_next_hand);
+        assert!(!seated(&table, 0).sit_out_next_big_blind);
+        fold_to_winner(&mut table);
+
+        // Still sitting out until they come back
+        table.start_new_game()?;
+        assert_eq!(game_seats(&table), vec![1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lined_up_decks_are_dealt_first() -> Result<()> {
+        let mut table = Table::default();
+        sit_players(&mut table, &[(0, 100), (1, 100)]);
+        let commitment = table.commitment();
+        table.decks.push_back(DeckSource::Seeded { seed: 1 });
+        table.decks.push_back(DeckSource::Seeded { seed: 2 });
+
+        for seed in [1, 2] {
+            table.start_new_game()?;
+            let game = table.game().unwrap();
+            assert_eq!(game.source, Some(DeckSource::Seeded { seed }));
+            assert_eq!(game.shuffle(), None);
+            fold_to_winner(&mut table);
+        }
+        // The committed seed waits for the first fair shuffle
+        assert_eq!(table.commitment(), commitment);
+        table.start_new_game()?;
+        let shuffle = table.game().unwrap().shuffle().unwrap();
+        assert_eq!(shuffle.commitment(), commitment);
+        assert_ne!(table.commitment(), commitment);
+        Ok(())
+    }
+}
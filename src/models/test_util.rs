@@ -0,0 +1,23 @@
+//! Helpers shared by the model tests
+
+use super::*;
+use rs_poker::core::Card;
+
+pub fn player(name: &str) -> Player {
+    Player::new(name.to_owned(), name.to_owned())
+}
+
+/// Parses cards in order, unlike `Hand`
+pub fn cards(cards: &str) -> Vec<Card> {
+    (0..cards.len())
+        .step_by(2)
+        .map(|i| Card::try_from(&cards[i..i + 2]).unwrap())
+        .collect()
+}
+
+/// Seats alice, bob and carol in the first three seats with 100 chips each
+pub fn sit_alice_bob_carol(table: &mut Table) {
+    for (seat, name) in ["alice", "bob", "carol"].into_iter().enumerate() {
+        table.sit_player(player(name), 100, Some(seat)).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use crate::*;
+use rs_poker::core::{Card, Rank, Rankable};
+
+/// The game dealt at a table
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum GameVariant {
+    /// Texas Hold'em
+    #[default]
+    Holdem,
+    /// Omaha with four hole cards
+    Plo,
+    /// Omaha with five hole cards
+    Plo5,
+}
+
+impl GameVariant {
+    /// Cards dealt to each player
+    pub fn hole_cards(self) -> usize {
+        match self {
+            Self::Holdem => 2,
+            Self::Plo => 4,
+            Self::Plo5 => 5,
+        }
+    }
+
+    /// How the variant is usually bet: Hold'em with no limit and Omaha
+    /// limited to the pot
+    pub fn betting_structure(self) -> BettingStructure {
+        match self {
+            Self::Holdem => BettingStructure::NoLimit,
+            Self::Plo | Self::Plo5 => BettingStructure::PotLimit,
+        }
+    }
+
+    /// Most players there are cards in the deck to deal to
+    pub fn max_players(self) -> usize {
+        (52 - 5) / self.hole_cards()
+    }
+
+    /// The game as PokerStars names it in hand histories
+    pub fn pokerstars_name(self) -> &'static str {
+        match self {
+            Self::Holdem => "Hold'em",
+            Self::Plo => "Omaha",
+            Self::Plo5 => "5 Card Omaha",
+        }
+    }
+
+    /// The best hand `hand` makes with `board`. Hold'em plays any five of the
+    /// cards, while Omaha plays exactly two from the hand and three from the
+    /// board, or as much of the board as has been dealt.
+    pub fn rank(self, hand: &[Card], board: &[Card]) -> Rank {
+        if self == Self::Holdem {
+            let mut cards = hand.to_vec();
+            cards.extend_from_slice(board);
+            return cards.rank();
+        }
+        let from_board = combinations(board, board.len().min(3));
+        combinations(hand, 2)
+            .iter()
+            .flat_map(|two| {
+                from_board.iter().map(move |three| {
+                    let mut cards = two.clone();
+                    cards.extend_from_slice(three);
+                    cards.rank()
+                })
+            })
+            .max()
+            .expect("Hand should have at least two cards")
+    }
+}
+
+/// Every way to pick `k` of `cards`, keeping their order
+fn combinations(cards: &[Card], k: usize) -> Vec<Vec<Card>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    (0..cards.len())
+        .flat_map(|i| {
+            combinations(&cards[i + 1..], k - 1)
+                .into_iter()
+                .map(move |mut rest| {
+                    rest.insert(0, cards[i]);
+                    rest
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_util::*;
+    use test_log::test;
+
+    fn describe(variant: GameVariant, hand: &str, board: &str) -> String {
+        describe_rank(&variant.rank(&cards(hand), &cards(board)))
+    }
+
+    #[test]
+    fn test_omaha_plays_two_from_hand() {
+        use GameVariant::*;
+        // Four hearts on the board make a flush in Hold'em with one more, but
+        // Omaha needs two from the hand
+        assert_eq!(describe(Holdem, "Ah2c", "KhQh9h3h8s"), "Flush, Ace high");
+        assert_eq!(describe(Plo, "Ah2cTc4d", "KhQh9h3h8s"), "High card, Ace");
+        assert_eq!(describe(Plo, "AhJh2c4d", "KhQh9h3h8s"), "Flush, Ace high");
+        // Nor can the hand play three or four of its own cards
+        assert_eq!(describe(Plo, "AsAdAcKs", "2h7d9cJhQs"), "Pair of Aces");
+        assert_eq!(
+            describe(Plo5, "AsAd9s8s7s", "2h7d9cJhTs"),
+            "Straight, Jack high"
+        );
+        // Before the board is out, only what's been dealt counts
+        assert_eq!(describe(Plo, "AsAdKsKd", ""), "Pair of Aces");
+        assert_eq!(
+            describe(Plo, "AsKd7c2h", "Ah7d2s"),
+            "Two pair, Aces and Sevens"
+        );
+    }
+
+    #[test]
+    fn test_deck_fits_every_seat() {
+        assert_eq!(GameVariant::Holdem.max_players(), 23);
+        assert_eq!(GameVariant::Plo.max_players(), 11);
+        assert_eq!(GameVariant::Plo5.max_players(), 9);
+    }
+}
@@ -2,13 +2,15 @@ use crate::*;
 use axum::{
     extract::{ws::WebSocketUpgrade, ConnectInfo, State},
     response::IntoResponse,
-    routing::get,
+    routing::{delete, get, post},
     Router, TypedHeader,
 };
 use handle_socket::handle_socket;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
 
+pub mod admin;
 pub mod context;
 pub mod cookie;
 pub mod handle_socket;
@@ -20,35 +22,140 @@ pub use cookie::*;
 pub struct AppState {
     room_registry: RegistryHandle<RoomId, RoomHandle>,
     player_registry: RegistryHandle<PlayerId, PlayerHandle>,
+    storage: StorageHandle,
 }
 
 pub async fn run() {
-    let table = Table::default();
+    let storage =
+        open_storage(&crate::CONFIG).unwrap_or_else(|err| panic!("Invalid storage: {err:?}"));
+    let addr = crate::ADDR.parse().expect("Invalid binding address");
+    serve(addr, storage).await;
+}
+
+/// Opens the tables and serves them on `addr`, picking up where the last
+/// server to use `storage` left off
+pub async fn serve(addr: SocketAddr, storage: Box<dyn Storage>) {
     let player_registry = RegistryHandle::new();
     let room_registry = RegistryHandle::new();
-    let room = RoomHandle::new(table, player_registry.clone(), room_registry.clone());
-    room_registry.set(room.id.clone(), room).await;
+    let storage = StorageHandle::new(storage);
+    // Tables opened at runtime come back, unless the config file now has one
+    // with the same id
+    let stored = storage.load_tables().await.expect("Couldn't load tables");
+    let configured: Vec<_> = crate::CONFIG.tables.iter().map(|t| &t.id).collect();
+    let stored = stored
+        .into_iter()
+        .filter(|stored| !configured.contains(&&stored.config.id))
+        .map(|stored| (stored.config, stored.owner));
+    let tables = crate::CONFIG
+        .tables
+        .iter()
+        .map(|config| (config.clone(), None));
+    for (config, owner) in tables.chain(stored) {
+        let table = Table::from_config(config);
+        let room = RoomHandle::new(
+            table,
+            owner,
+            player_registry.clone(),
+            room_registry.clone(),
+            storage.clone(),
+        );
+        room_registry.set(room.id.clone(), room).await;
+    }
+    recover_tables(&storage, &player_registry, &room_registry).await;
     // Spawns an actor to manage the player registry
     let app_state = AppState {
         room_registry,
         player_registry,
+        storage,
     };
 
     let app = Router::new()
         .route("/ws", get(ws_handler))
+        .route("/admin/tables", post(admin::create_table))
+        .route("/admin/tables/:id", delete(admin::close_table))
+        .route("/admin/hands/:id", get(admin::get_hand))
+        .route("/admin/hands/:id/pokerstars", get(admin::export_hand))
+        .route("/admin/hands/:id/replay", get(admin::replay_hand))
+        .route("/admin/players/:id/hands", get(admin::player_hands))
         .with_state(app_state)
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::default().include_headers(true)),
         );
 
-    let addr = crate::ADDR.parse().expect("Invalid binding address");
     axum::Server::bind(&addr)
         .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Server Failed");
 }
 
+/// Puts tables back from their journals and brings back the players sitting
+/// at them, who have until the reconnect timeout to come back. Players at
+/// tables that are no longer opened get their chips back.
+async fn recover_tables(
+    storage: &StorageHandle,
+    player_registry: &RegistryHandle<PlayerId, PlayerHandle>,
+    room_registry: &RegistryHandle<RoomId, RoomHandle>,
+) {
+    let journals = storage
+        .load_journals()
+        .await
+        .expect("Couldn't load journals");
+    let mut seated: HashMap<PlayerId, (Player, HashSet<RoomId>)> = HashMap::new();
+    for journal in journals {
+        let id = journal.snapshot.table_id.clone();
+        let recovered = match room_registry.get(id.clone()).await {
+            Some(room) => room
+                .recover(journal.clone(), crate::CONFIG.hand_recovery)
+                .await
+                .inspect_err(|e| error!(table = id, err = ?e, "Couldn't recover table")),
+            None => Err(eyre!("Table is no longer open")),
+        };
+        let Ok(players) = recovered else {
+            refund_journal(storage, journal).await;
+            continue;
+        };
+        info!(table = id, players = players.len(), "Recovered table");
+        for player in players {
+            let (_, rooms) = seated
+                .entry(player.id.clone())
+                .or_insert_with(|| (player, HashSet::new()));
+            rooms.insert(id.clone());
+        }
+    }
+
+    for (player, rooms) in seated.into_values() {
+        let handle = PlayerHandle::reseat(
+            player,
+            rooms,
+            player_registry.clone(),
+            room_registry.clone(),
+            storage.clone(),
+        )
+        .await;
+        player_registry.set(handle.id.clone(), handle).await;
+    }
+}
+
+/// Adds what everyone at a table that can't be put back had at it to their
+/// balance
+async fn refund_journal(storage: &StorageHandle, journal: TableJournal) {
+    for (player, chips) in journal.refunds() {
+        let refunded = async {
+            let balance = storage.load_balance(player.id.clone()).await?;
+            let balance = balance.unwrap_or(*crate::DEFAULT_CHIPS) + chips;
+            storage.save_player(player.clone(), balance).await
+        };
+        if let Err(e) = refunded.await {
+            error!(player = player.id, chips, err = ?e, "Error refunding player");
+        }
+    }
+    let table = journal.snapshot.table_id;
+    if let Err(e) = storage.delete_journal(table.clone()).await {
+        error!(table, err = ?e, "Error deleting journal");
+    }
+}
+
 /// The handler for the HTTP request (this gets called when the HTTP GET lands at the start
 /// of websocket negotiation). After this completes, the actual switching from HTTP to
 /// websocket protocol will occur.
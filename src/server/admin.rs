@@ -0,0 +1,118 @@
+use crate::*;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json, TypedHeader,
+};
+use headers::{authorization::Bearer, Authorization};
+use subtle::ConstantTimeEq;
+
+type AdminResult<T> = std::result::Result<T, (StatusCode, String)>;
+
+/// Admin routes need `POKER_ADMIN_TOKEN` set and sent as a bearer token
+fn authorize(auth: Option<TypedHeader<Authorization<Bearer>>>) -> AdminResult<()> {
+    let Some(token) = ADMIN_TOKEN.as_ref() else {
+        return Err((StatusCode::FORBIDDEN, "Admin API is disabled".to_owned()));
+    };
+    match auth {
+        // Compared in constant time so the token can't be guessed byte by byte
+        Some(TypedHeader(auth)) if bool::from(auth.token().as_bytes().ct_eq(token.as_bytes())) => {
+            Ok(())
+        }
+        _ => Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_owned())),
+    }
+}
+
+pub async fn create_table(
+    State(app_state): State<AppState>,
+    auth: Option<TypedHeader<Authorization<Bearer>>>,
+    Json(config): Json<TableConfig>,
+) -> AdminResult<Json<TableListing>> {
+    authorize(auth)?;
+    let listing = RoomHandle::open(
+        config,
+        None,
+        app_state.player_registry,
+        app_state.room_registry,
+        app_state.storage,
+    )
+    .await
+    .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(listing))
+}
+
+pub async fn close_table(
+    State(app_state): State<AppState>,
+    auth: Option<TypedHeader<Authorization<Bearer>>>,
+    Path(id): Path<TableId>,
+) -> AdminResult<StatusCode> {
+    authorize(auth)?;
+    let room = app_state
+        .room_registry
+        .get(id)
+        .await
+        .ok_or((StatusCode::NOT_FOUND, "Not a valid room id".to_owned()))?;
+    room.close(None)
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, e.to_string()))?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn find_hand(app_state: &AppState, id: HandId) -> AdminResult<HandHistory> {
+    app_state
+        .storage
+        .load_hand(id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "No hand with that id".to_owned()))
+}
+
+/// The whole history of a hand, with every player's hole cards
+pub async fn get_hand(
+    State(app_state): State<AppState>,
+    auth: Option<TypedHeader<Authorization<Bearer>>>,
+    Path(id): Path<HandId>,
+) -> AdminResult<Json<HandHistory>> {
+    authorize(auth)?;
+    Ok(Json(find_hand(&app_state, id).await?))
+}
+
+pub async fn export_hand(
+    State(app_state): State<AppState>,
+    auth: Option<TypedHeader<Authorization<Bearer>>>,
+    Path(id): Path<HandId>,
+) -> AdminResult<String> {
+    authorize(auth)?;
+    Ok(find_hand(&app_state, id).await?.to_pokerstars())
+}
+
+/// Plays a hand back, giving the game as it was once dealt and after each
+/// action. Fails if the replay doesn't come out as recorded.
+pub async fn replay_hand(
+    State(app_state): State<AppState>,
+    auth: Option<TypedHeader<Authorization<Bearer>>>,
+    Path(id): Path<HandId>,
+) -> AdminResult<Json<Vec<PublicGameState>>> {
+    authorize(auth)?;
+    let mut states = vec![];
+    find_hand(&app_state, id)
+        .await?
+        .replay(|game| states.push(PokerMessage::public_game_state_from_game(game)))
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    Ok(Json(states))
+}
+
+/// The latest hands a player was dealt into, newest first
+pub async fn player_hands(
+    State(app_state): State<AppState>,
+    auth: Option<TypedHeader<Authorization<Bearer>>>,
+    Path(id): Path<PlayerId>,
+) -> AdminResult<Json<Vec<HandHistory>>> {
+    authorize(auth)?;
+    let hands = app_state
+        .storage
+        .player_hands(id, MAX_HAND_HISTORIES)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(hands))
+}
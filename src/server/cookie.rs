@@ -13,7 +13,7 @@ struct SessionCookie {
     address: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Session {
     pub address: Address,
 }
@@ -12,19 +12,8 @@ pub async fn handle_socket(socket: WebSocket, app_state: AppState, ctx: Context)
     let id = ctx.session.address.to_string();
     let username = ctx.session.address.to_string();
 
-    // Spawn new Player actor
-    let player = PlayerHandle::new(
-        Player::new(id, username),
-        app_state.room_registry.clone(),
-        player_send.clone(),
-    );
-
-    // Add to player to registry actor
-    // TODO: Do we need to check for error and close socket here if this fails ?
-    app_state
-        .player_registry
-        .set(player.id.clone(), player.clone())
-        .await;
+    let (player, connection) =
+        connect_player(Player::new(id, username), &app_state, player_send).await;
 
     debug!(id = ?ctx.session.address, "Registered player socket");
 
@@ -32,28 +21,72 @@ pub async fn handle_socket(socket: WebSocket, app_state: AppState, ctx: Context)
         loop {
             tokio::select! {
                 // Process websocket messages from player
-                Some(Ok(msg)) = rx.next() => {
-                    if handle_recv(msg, &player, &app_state).await.is_err() {
-                        break;
+                msg = rx.next() => match msg {
+                    Some(Ok(msg)) => {
+                        if handle_recv(msg, &player).await.is_err() {
+                            break;
+                        }
                     }
+                    // The connection dropped without closing
+                    _ => break,
                 },
                 // recv messages from server and forward to client
-                Some(msg) = player_recv.recv() => {
-                    if handle_send(msg, &mut tx).await.is_err() {
-                        break;
+                msg = player_recv.recv() => match msg {
+                    Some(msg) => {
+                        if handle_send(msg, &mut tx).await.is_err() {
+                            break;
+                        }
                     }
-                }
+                    // The player connected again from somewhere else
+                    None => break,
+                },
             }
         }
+        player.disconnect(connection).await;
     });
 }
 
-async fn handle_recv(msg: Message, player: &PlayerHandle, app_state: &AppState) -> Result<()> {
+/// Rebinds a player who is still around to the new socket, or spawns a Player
+/// actor for them
+async fn connect_player(
+    player: Player,
+    app_state: &AppState,
+    socket: mpsc::Sender<PokerMessage>,
+) -> (PlayerHandle, usize) {
+    if let Some(handle) = app_state.player_registry.get(player.id.clone()).await {
+        if let Ok(connection) = handle.connect(socket.clone()).await {
+            debug!(id = player.id, "Player reconnected");
+            return (handle, connection);
+        }
+    }
+
+    let handle = PlayerHandle::new(
+        player,
+        app_state.player_registry.clone(),
+        app_state.room_registry.clone(),
+        app_state.storage.clone(),
+    );
+    app_state
+        .player_registry
+        .set(handle.id.clone(), handle.clone())
+        .await;
+    let connection = handle
+        .connect(socket)
+        .await
+        .expect("New player should be connected");
+    (handle, connection)
+}
+
+async fn handle_recv(msg: Message, player: &PlayerHandle) -> Result<()> {
     match msg {
         Message::Text(text) => {
             debug!("Received message from client: {}", &text);
             let result = serde_json::from_str::<PokerMessage>(&text);
             match result {
+                Ok(PokerMessage::Server(_)) => {
+                    let _ = player.send_error(eyre!("Invalid Message".to_owned()));
+                    Ok(())
+                }
                 Ok(poker_msg) => {
                     if player.send_message(poker_msg).is_err() {
                         bail!("Socket overwhelmed; dropping connection");
@@ -66,13 +99,12 @@ async fn handle_recv(msg: Message, player: &PlayerHandle, app_state: &AppState)
                 }
             }
         }
-        // Remove player from registry when their connection closes
         // `break` to close the connection server side
         Message::Close(_) => {
-            app_state.player_registry.delete(player.id.clone()).await;
             bail!("Received Message::Close, dropping connection");
         }
-        _ => unimplemented!(),
+        // Pings are answered by axum, keeping mobile connections alive
+        _ => Ok(()),
     }
 }
 
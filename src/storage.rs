@@ -0,0 +1,550 @@
+use crate::*;
+use tokio::sync::{mpsc, oneshot};
+
+mod memory;
+mod sqlite;
+
+pub use memory::*;
+pub use sqlite::*;
+
+/// Hands sent to a player who doesn't say how many they want
+pub const DEFAULT_HAND_HISTORIES: usize = 20;
+/// Most hands sent at once
+pub const MAX_HAND_HISTORIES: usize = 100;
+
+/// A table opened at runtime, kept so it comes back after a restart
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StoredTable {
+    pub config: TableConfig,
+    /// Player who opened it through the lobby, who may close it
+    pub owner: Option<PlayerId>,
+}
+
+/// A change to a table's journal
+#[derive(Debug, Clone)]
+pub enum JournalChange {
+    /// Starts the journal over
+    Snapshot(TableSnapshot),
+    Entry(TableId, JournalEntry),
+}
+
+/// Chips leaving a table, saved together with the journal change that takes
+/// them off it so a crash can't lose them in between
+#[derive(Debug, Clone)]
+pub struct Settlement {
+    pub change: JournalChange,
+    /// A finished hand, recorded with the snapshot that moves the table on
+    /// from it so it can't be played back and recorded again
+    pub history: Option<HandHistory>,
+    /// Chips going back to each player's balance
+    pub credits: Vec<(PlayerId, ChipInt)>,
+}
+
+/// Where players, their balances, tables opened at runtime and hand histories
+/// are kept between restarts
+pub trait Storage: Send {
+    /// The player's balance, or `None` if they have never been seen
+    fn load_balance(&mut self, player: &PlayerId) -> Result<Option<ChipInt>>;
+    /// Saves the player's profile along with the chips they hold away from tables
+    fn save_player(&mut self, player: &Player, balance: ChipInt) -> Result<()>;
+    /// Adds chips coming back from a table to a saved player's balance,
+    /// returning the new balance
+    fn credit_player(&mut self, player: &PlayerId, chips: ChipInt) -> Result<ChipInt>;
+    /// Takes chips brought to a table from a saved player's balance,
+    /// returning what's left. Fails if they don't have them.
+    fn debit_player(&mut self, player: &PlayerId, chips: ChipInt) -> Result<ChipInt>;
+    fn load_tables(&mut self) -> Result<Vec<StoredTable>>;
+    fn save_table(&mut self, table: &StoredTable) -> Result<()>;
+    fn delete_table(&mut self, id: &TableId) -> Result<()>;
+    /// Stores a finished hand, returning the id it was given
+    fn record_hand(&mut self, history: &HandHistory) -> Result<HandId>;
+    fn load_hand(&mut self, id: HandId) -> Result<Option<HandHistory>>;
+    /// The latest hands a player was dealt into, newest first
+    fn player_hands(&mut self, player: &PlayerId, limit: usize) -> Result<Vec<HandHistory>>;
+    fn load_journals(&mut self) -> Result<Vec<TableJournal>>;
+    /// Starts the table's journal over from `snapshot`
+    fn save_snapshot(&mut self, snapshot: &TableSnapshot) -> Result<()>;
+    /// Adds to a journal started by `save_snapshot`
+    fn append_journal(&mut self, table: &TableId, entry: &JournalEntry) -> Result<()>;
+    fn delete_journal(&mut self, table: &TableId) -> Result<()>;
+    /// Saves everything in the settlement or nothing. Credits for players
+    /// who were never saved are left out.
+    fn settle(&mut self, settlement: &Settlement) -> Result<()>;
+}
+
+/// Opens the database named in the config, or keeps everything in memory
+/// when there isn't one
+pub fn open_storage(config: &Config) -> Result<Box<dyn Storage>> {
+    match &config.database {
+        Some(path) => Ok(Box::new(SqliteStorage::open(path)?)),
+        None => Ok(Box::<MemoryStorage>::default()),
+    }
+}
+
+enum StorageMessage {
+    LoadBalance {
+        player: PlayerId,
+        respond_to: oneshot::Sender<Result<Option<ChipInt>>>,
+    },
+    SavePlayer {
+        player: Player,
+        balance: ChipInt,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+    DebitPlayer {
+        player: PlayerId,
+        chips: ChipInt,
+        respond_to: oneshot::Sender<Result<ChipInt>>,
+    },
+    LoadTables {
+        respond_to: oneshot::Sender<Result<Vec<StoredTable>>>,
+    },
+    SaveTable {
+        table: StoredTable,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+    DeleteTable {
+        id: TableId,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+    RecordHand {
+        history: Box<HandHistory>,
+        respond_to: oneshot::Sender<Result<HandId>>,
+    },
+    LoadHand {
+        id: HandId,
+        respond_to: oneshot::Sender<Result<Option<HandHistory>>>,
+    },
+    PlayerHands {
+        player: PlayerId,
+        limit: usize,
+        respond_to: oneshot::Sender<Result<Vec<HandHistory>>>,
+    },
+    LoadJournals {
+        respond_to: oneshot::Sender<Result<Vec<TableJournal>>>,
+    },
+    SaveSnapshot {
+        snapshot: Box<TableSnapshot>,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+    AppendJournal {
+        table: TableId,
+        entry: JournalEntry,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+    DeleteJournal {
+        table: TableId,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+    Settle {
+        settlement: Box<Settlement>,
+        respond_to: oneshot::Sender<Result<()>>,
+    },
+}
+
+/// Runs a `Storage` on blocking threads, so database calls never hold up the
+/// actors using it
+#[derive(Clone)]
+pub struct StorageHandle {
+    sender: mpsc::Sender<StorageMessage>,
+}
+
+impl StorageHandle {
+    pub fn new(storage: Box<dyn Storage>) -> Self {
+        let (sender, receiver) = mpsc::channel(*CHANNEL_SIZE);
+        tokio::spawn(run(storage, receiver));
+
+        Self { sender }
+    }
+
+    /// Storage that forgets everything when dropped, for tests
+    pub fn in_memory() -> Self {
+        Self::new(Box::<MemoryStorage>::default())
+    }
+
+    pub async fn load_balance(&self, player: PlayerId) -> Result<Option<ChipInt>> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::LoadBalance {
+            player,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn save_player(&self, player: Player, balance: ChipInt) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::SavePlayer {
+            player,
+            balance,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    /// Returns the player's new balance
+    /// Returns the player's new balance
+    pub async fn debit_player(&self, player: PlayerId, chips: ChipInt) -> Result<ChipInt> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::DebitPlayer {
+            player,
+            chips,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn load_tables(&self) -> Result<Vec<StoredTable>> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::LoadTables { respond_to: send };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn save_table(&self, table: StoredTable) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::SaveTable {
+            table,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn delete_table(&self, id: TableId) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::DeleteTable {
+            id,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn record_hand(&self, history: HandHistory) -> Result<HandId> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::RecordHand {
+            history: Box::new(history),
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn load_hand(&self, id: HandId) -> Result<Option<HandHistory>> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::LoadHand {
+            id,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn player_hands(&self, player: PlayerId, limit: usize) -> Result<Vec<HandHistory>> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::PlayerHands {
+            player,
+            limit,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn load_journals(&self) -> Result<Vec<TableJournal>> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::LoadJournals { respond_to: send };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn save_snapshot(&self, snapshot: TableSnapshot) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::SaveSnapshot {
+            snapshot: Box::new(snapshot),
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn append_journal(&self, table: TableId, entry: JournalEntry) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::AppendJournal {
+            table,
+            entry,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn delete_journal(&self, table: TableId) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::DeleteJournal {
+            table,
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+
+    pub async fn settle(&self, settlement: Settlement) -> Result<()> {
+        let (send, recv) = oneshot::channel();
+        let msg = StorageMessage::Settle {
+            settlement: Box::new(settlement),
+            respond_to: send,
+        };
+        self.sender.send(msg).await?;
+        recv.await?
+    }
+}
+
+fn handle_message(storage: &mut dyn Storage, msg: StorageMessage) {
+    match msg {
+        StorageMessage::LoadBalance { player, respond_to } => {
+            let _ = respond_to.send(storage.load_balance(&player));
+        }
+        StorageMessage::SavePlayer {
+            player,
+            balance,
+            respond_to,
+        } => {
+            let _ = respond_to.send(storage.save_player(&player, balance));
+        }
+        StorageMessage::DebitPlayer {
+            player,
+            chips,
+            respond_to,
+        } => {
+            let _ = respond_to.send(storage.debit_player(&player, chips));
+        }
+        StorageMessage::LoadTables { respond_to } => {
+            let _ = respond_to.send(storage.load_tables());
+        }
+        StorageMessage::SaveTable { table, respond_to } => {
+            let _ = respond_to.send(storage.save_table(&table));
+        }
+        StorageMessage::DeleteTable { id, respond_to } => {
+            let _ = respond_to.send(storage.delete_table(&id));
+        }
+        StorageMessage::RecordHand {
+            history,
+            respond_to,
+        } => {
+            let _ = respond_to.send(storage.record_hand(&history));
+        }
+        StorageMessage::LoadHand { id, respond_to } => {
+            let _ = respond_to.send(storage.load_hand(id));
+        }
+        StorageMessage::PlayerHands {
+            player,
+            limit,
+            respond_to,
+        } => {
+            let _ = respond_to.send(storage.player_hands(&player, limit));
+        }
+        StorageMessage::LoadJournals { respond_to } => {
+            let _ = respond_to.send(storage.load_journals());
+        }
+        StorageMessage::SaveSnapshot {
+            snapshot,
+            respond_to,
+        } => {
+            let _ = respond_to.send(storage.save_snapshot(&snapshot));
+        }
+        StorageMessage::AppendJournal {
+            table,
+            entry,
+            respond_to,
+        } => {
+            let _ = respond_to.send(storage.append_journal(&table, &entry));
+        }
+        StorageMessage::DeleteJournal { table, respond_to } => {
+            let _ = respond_to.send(storage.delete_journal(&table));
+        }
+        StorageMessage::Settle {
+            settlement,
+            respond_to,
+        } => {
+            let _ = respond_to.send(storage.settle(&settlement));
+        }
+    }
+}
+
+async fn run(mut storage: Box<dyn Storage>, mut receiver: mpsc::Receiver<StorageMessage>) {
+    while let Some(msg) = receiver.recv().await {
+        // The storage is handed to a blocking thread for each call, rather than
+        // one thread holding it, so an idle actor doesn't hold up the runtime
+        let handled = tokio::task::spawn_blocking(move || {
+            handle_message(storage.as_mut(), msg);
+            storage
+        })
+        .await;
+        match handled {
+            Ok(returned) => storage = returned,
+            Err(e) => {
+                error!(err = ?e, "Storage call panicked");
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn history(id: &str, players: &[&str]) -> HandHistory {
+        let mut table = Table::new(id.to_owned(), id.to_owned());
+        for (seat, name) in players.iter().enumerate() {
+            let player = Player::new(name.to_string(), name.to_string());
+            table.sit_player(player, 100, Some(seat)).unwrap();
+        }
+        table.start_new_game().unwrap();
+        let game = table.game_mut().unwrap();
+        while !game.is_over() {
+            game.fold();
+        }
+        HandHistory::new(&table.config, table.game().unwrap(), &[])
+    }
+
+    /// Runs the same checks against every backend
+    fn check_storage(mut storage: Box<dyn Storage>) {
+        let alice = Player::new("alice".to_owned(), "Alice".to_owned());
+        assert_eq!(storage.load_balance(&alice.id).unwrap(), None);
+        storage.save_player(&alice, 100).unwrap();
+        storage.save_player(&alice, 75).unwrap();
+        assert_eq!(storage.load_balance(&alice.id).unwrap(), Some(75));
+        assert_eq!(storage.credit_player(&alice.id, 25).unwrap(), 100);
+        assert_eq!(storage.debit_player(&alice.id, 60).unwrap(), 40);
+        assert!(storage.debit_player(&alice.id, 41).is_err());
+        assert_eq!(storage.load_balance(&alice.id).unwrap(), Some(40));
+        assert!(storage.credit_player(&"dave".to_owned(), 10).is_err());
+        storage.save_player(&alice, 75).unwrap();
+
+        let config = TableConfig {
+            id: "abc".to_owned(),
+            name: "Saved".to_owned(),
+            ..Default::default()
+        };
+        let stored = StoredTable {
+            config: config.clone(),
+            owner: Some(alice.id.clone()),
+        };
+        storage.save_table(&stored).unwrap();
+        assert_eq!(
+            storage.load_tables().unwrap(),
+            std::slice::from_ref(&stored)
+        );
+        storage.delete_table(&config.id).unwrap();
+        assert!(storage.load_tables().unwrap().is_empty());
+
+        let first = storage
+            .record_hand(&history("one", &["alice", "bob"]))
+            .unwrap();
+        let second = storage
+            .record_hand(&history("two", &["bob", "carol"]))
+            .unwrap();
+        let third = storage
+            .record_hand(&history("three", &["alice", "carol"]))
+            .unwrap();
+        assert!(first < second && second < third);
+
+        let hand = storage.load_hand(second).unwrap().unwrap();
+        assert_eq!(hand.id, second);
+        assert_eq!(hand.table_id, "two");
+        assert_eq!(storage.load_hand(third + 1).unwrap(), None);
+
+        let ids = |hands: Vec<HandHistory>| hands.iter().map(|h| h.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(storage.player_hands(&alice.id, 10).unwrap()),
+            [third, first]
+        );
+        assert_eq!(ids(storage.player_hands(&alice.id, 1).unwrap()), [third]);
+        assert!(storage
+            .player_hands(&"dave".to_owned(), 10)
+            .unwrap()
+            .is_empty());
+
+        let entry = JournalEntry::Vacated { seat: 1 };
+        assert!(storage.append_journal(&config.id, &entry).is_err());
+        let table = Table::from_config(config.clone());
+        let snapshot = TableSnapshot::new(&table);
+        storage.save_snapshot(&snapshot).unwrap();
+        storage.append_journal(&config.id, &entry).unwrap();
+        let seated = JournalEntry::Sat {
+            player: alice.clone(),
+            chips: 40,
+            seat: 0,
+        };
+        storage.append_journal(&config.id, &seated).unwrap();
+        let journal = TableJournal {
+            snapshot: snapshot.clone(),
+            entries: vec![entry, seated],
+        };
+        assert_eq!(storage.load_journals().unwrap(), [journal]);
+        // A new snapshot starts the journal over
+        storage.save_snapshot(&snapshot).unwrap();
+        assert!(storage.load_journals().unwrap()[0].entries.is_empty());
+
+        // A settlement is saved whole or not at all
+        let missing = JournalChange::Entry("missing".to_owned(), JournalEntry::Vacated { seat: 0 });
+        let settlement = Settlement {
+            change: missing,
+            history: Some(history("four", &["alice", "bob"])),
+            credits: vec![(alice.id.clone(), 10), ("dave".to_owned(), 10)],
+        };
+        assert!(storage.settle(&settlement).is_err());
+        assert_eq!(storage.load_balance(&alice.id).unwrap(), Some(75));
+        assert_eq!(storage.player_hands(&alice.id, 10).unwrap().len(), 2);
+        storage
+            .append_journal(&config.id, &JournalEntry::Vacated { seat: 1 })
+            .unwrap();
+        let settlement = Settlement {
+            change: JournalChange::Snapshot(snapshot.clone()),
+            ..settlement
+        };
+        storage.settle(&settlement).unwrap();
+        assert_eq!(storage.load_balance(&alice.id).unwrap(), Some(85));
+        assert_eq!(storage.player_hands(&alice.id, 10).unwrap().len(), 3);
+        assert!(storage.load_journals().unwrap()[0].entries.is_empty());
+        storage.delete_journal(&config.id).unwrap();
+        assert!(storage.load_journals().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        check_storage(Box::<MemoryStorage>::default());
+    }
+
+    #[test]
+    fn test_sqlite_storage() {
+        check_storage(Box::new(SqliteStorage::open(":memory:").unwrap()));
+    }
+
+    #[test]
+    fn test_sqlite_survives_reopening() {
+        let path = std::env::temp_dir().join(format!(
+            "poker-test-{}.db",
+            hex::encode(rand::random::<[u8; 8]>())
+        ));
+        let alice = Player::new("alice".to_owned(), "Alice".to_owned());
+        {
+            let mut storage = SqliteStorage::open(&path).unwrap();
+            storage.save_player(&alice, 42).unwrap();
+            storage
+                .record_hand(&history("one", &["alice", "bob"]))
+                .unwrap();
+        }
+        let mut storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.load_balance(&alice.id).unwrap(), Some(42));
+        assert_eq!(storage.player_hands(&alice.id, 10).unwrap().len(), 1);
+        drop(storage);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// Keeps everything in memory, so nothing survives a restart
+#[derive(Default)]
+pub struct MemoryStorage {
+    balances: HashMap<PlayerId, ChipInt>,
+    tables: BTreeMap<TableId, StoredTable>,
+    /// Hand `id` is at index `id - 1`
+    hands: Vec<HandHistory>,
+    journals: BTreeMap<TableId, TableJournal>,
+}
+
+impl Storage for MemoryStorage {
+    fn load_balance(&mut self, player: &PlayerId) -> Result<Option<ChipInt>> {
+        Ok(self.balances.get(player).copied())
+    }
+
+    fn save_player(&mut self, player: &Player, balance: ChipInt) -> Result<()> {
+        self.balances.insert(player.id.clone(), balance);
+        Ok(())
+    }
+
+    fn credit_player(&mut self, player: &PlayerId, chips: ChipInt) -> Result<ChipInt> {
+        let balance = self
+            .balances
+            .get_mut(player)
+            .ok_or_else(|| eyre!("Player {} not found", player))?;
+        *balance += chips;
+        Ok(*balance)
+    }
+
+    fn debit_player(&mut self, player: &PlayerId, chips: ChipInt) -> Result<ChipInt> {
+        let balance = self
+            .balances
+            .get_mut(player)
+            .ok_or_else(|| eyre!("Player {} not found", player))?;
+        if chips > *balance {
+            bail!("Insufficient Chips");
+        }
+        *balance -= chips;
+        Ok(*balance)
+    }
+
+    fn load_tables(&mut self) -> Result<Vec<StoredTable>> {
+        Ok(self.tables.values().cloned().collect())
+    }
+
+    fn save_table(&mut self, table: &StoredTable) -> Result<()> {
+        self.tables.insert(table.config.id.clone(), table.clone());
+        Ok(())
+    }
+
+    fn delete_table(&mut self, id: &TableId) -> Result<()> {
+        self.tables.remove(id);
+        Ok(())
+    }
+
+    fn record_hand(&mut self, history: &HandHistory) -> Result<HandId> {
+        let mut history = history.clone();
+        history.id = self.hands.len() as HandId + 1;
+        self.hands.push(history);
+        Ok(self.hands.len() as HandId)
+    }
+
+    fn load_hand(&mut self, id: HandId) -> Result<Option<HandHistory>> {
+        Ok(id
+            .checked_sub(1)
+            .and_then(|idx| self.hands.get(idx as usize))
+            .cloned())
+    }
+
+    fn player_hands(&mut self, player: &PlayerId, limit: usize) -> Result<Vec<HandHistory>> {
+        Ok(self
+            .hands
+            .iter()
+            .rev()
+            .filter(|history| history.was_dealt(player))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    fn load_journals(&mut self) -> Result<Vec<TableJournal>> {
+        Ok(self.journals.values().cloned().collect())
+    }
+
+    fn save_snapshot(&mut self, snapshot: &TableSnapshot) -> Result<()> {
+        let journal = TableJournal {
+            snapshot: snapshot.clone(),
+            entries: vec![],
+        };
+        self.journals.insert(snapshot.table_id.clone(), journal);
+        Ok(())
+    }
+
+    fn append_journal(&mut self, table: &TableId, entry: &JournalEntry) -> Result<()> {
+        self.journals
+            .get_mut(table)
+            .ok_or_else(|| eyre!("Table {} has no journal", table))?
+            .entries
+            .push(entry.clone());
+        Ok(())
+    }
+
+    fn delete_journal(&mut self, table: &TableId) -> Result<()> {
+        self.journals.remove(table);
+        Ok(())
+    }
+
+    fn settle(&mut self, settlement: &Settlement) -> Result<()> {
+        match &settlement.change {
+            JournalChange::Snapshot(snapshot) => self.save_snapshot(snapshot)?,
+            JournalChange::Entry(table, entry) => self.append_journal(table, entry)?,
+        }
+        if let Some(history) = &settlement.history {
+            self.record_hand(history)?;
+        }
+        for (player, chips) in &settlement.credits {
+            if let Some(balance) = self.balances.get_mut(player) {
+                *balance += chips;
+            }
+        }
+        Ok(())
+    }
+}
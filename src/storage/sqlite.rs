@@ -0,0 +1,295 @@
+use crate::*;
+use eyre::WrapErr;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::path::Path;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS players (
+        id TEXT PRIMARY KEY,
+        username TEXT NOT NULL,
+        balance INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tables (
+        id TEXT PRIMARY KEY,
+        config TEXT NOT NULL,
+        owner TEXT
+    );
+    CREATE TABLE IF NOT EXISTS hands (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        table_id TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        history TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS hand_players (
+        player_id TEXT NOT NULL,
+        hand_id INTEGER NOT NULL REFERENCES hands (id),
+        PRIMARY KEY (player_id, hand_id)
+    );
+    CREATE TABLE IF NOT EXISTS journals (
+        table_id TEXT PRIMARY KEY,
+        snapshot TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS journal_entries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        table_id TEXT NOT NULL REFERENCES journals (table_id),
+        entry TEXT NOT NULL
+    );
+";
+
+/// Keeps everything in an SQLite database file. Tables, hand histories and
+/// journals are stored as JSON.
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path)
+            .wrap_err_with(|| format!("Couldn't open database {}", path.display()))?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    fn insert_hand(tx: &Transaction, history: &HandHistory) -> Result<HandId> {
+        tx.execute(
+            "INSERT INTO hands (table_id, started_at, history) VALUES (?1, ?2, ?3)",
+            params![
+                history.table_id,
+                history.started_at as i64,
+                serde_json::to_string(history)?
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        for seat in &history.seats {
+            tx.execute(
+                "INSERT OR IGNORE INTO hand_players (player_id, hand_id) VALUES (?1, ?2)",
+                params![seat.player.id, id],
+            )?;
+        }
+        Ok(id as HandId)
+    }
+
+    fn replace_snapshot(tx: &Transaction, snapshot: &TableSnapshot) -> Result<()> {
+        tx.execute(
+            "DELETE FROM journal_entries WHERE table_id = ?1",
+            params![snapshot.table_id],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO journals (table_id, snapshot) VALUES (?1, ?2)",
+            params![snapshot.table_id, serde_json::to_string(snapshot)?],
+        )?;
+        Ok(())
+    }
+
+    fn insert_entry(conn: &Connection, table: &TableId, entry: &JournalEntry) -> Result<()> {
+        let added = conn.execute(
+            "INSERT INTO journal_entries (table_id, entry)
+             SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM journals WHERE table_id = ?1)",
+            params![table, serde_json::to_string(entry)?],
+        )?;
+        if added == 0 {
+            bail!("Table {} has no journal", table)
+        }
+        Ok(())
+    }
+
+    fn parse_hand(id: i64, history: &str) -> Result<HandHistory> {
+        let mut history: HandHistory = serde_json::from_str(history)?;
+        history.id = id as HandId;
+        Ok(history)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_balance(&mut self, player: &PlayerId) -> Result<Option<ChipInt>> {
+        let balance = self
+            .conn
+            .query_row(
+                "SELECT balance FROM players WHERE id = ?1",
+                params![player],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?;
+        Ok(balance.map(|balance| balance as ChipInt))
+    }
+
+    fn save_player(&mut self, player: &Player, balance: ChipInt) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO players (id, username, balance) VALUES (?1, ?2, ?3)
+             ON CONFLICT (id) DO UPDATE SET username = ?2, balance = ?3",
+            params![player.id, player.username, balance as i64],
+        )?;
+        Ok(())
+    }
+
+    fn credit_player(&mut self, player: &PlayerId, chips: ChipInt) -> Result<ChipInt> {
+        let updated = self.conn.execute(
+            "UPDATE players SET balance = balance + ?2 WHERE id = ?1",
+            params![player, chips as i64],
+        )?;
+        if updated == 0 {
+            bail!("Player {} not found", player);
+        }
+        Ok(self.load_balance(player)?.unwrap_or_default())
+    }
+
+    fn debit_player(&mut self, player: &PlayerId, chips: ChipInt) -> Result<ChipInt> {
+        let balance = self
+            .load_balance(player)?
+            .ok_or_else(|| eyre!("Player {} not found", player))?;
+        if chips > balance {
+            bail!("Insufficient Chips");
+        }
+        self.conn.execute(
+            "UPDATE players SET balance = balance - ?2 WHERE id = ?1",
+            params![player, chips as i64],
+        )?;
+        Ok(balance - chips)
+    }
+
+    fn load_tables(&mut self) -> Result<Vec<StoredTable>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT config, owner FROM tables ORDER BY id")?;
+        let tables = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tables
+            .into_iter()
+            .map(|(config, owner)| {
+                Ok(StoredTable {
+                    config: serde_json::from_str(&config)?,
+                    owner,
+                })
+            })
+            .collect()
+    }
+
+    fn save_table(&mut self, table: &StoredTable) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO tables (id, config, owner) VALUES (?1, ?2, ?3)",
+            params![
+                table.config.id,
+                serde_json::to_string(&table.config)?,
+                table.owner
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_table(&mut self, id: &TableId) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM tables WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn record_hand(&mut self, history: &HandHistory) -> Result<HandId> {
+        let tx = self.conn.transaction()?;
+        let id = Self::insert_hand(&tx, history)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    fn load_hand(&mut self, id: HandId) -> Result<Option<HandHistory>> {
+        let history = self
+            .conn
+            .query_row(
+                "SELECT history FROM hands WHERE id = ?1",
+                params![id as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        history
+            .map(|history| Self::parse_hand(id as i64, &history))
+            .transpose()
+    }
+
+    fn player_hands(&mut self, player: &PlayerId, limit: usize) -> Result<Vec<HandHistory>> {
+        let mut statement = self.conn.prepare(
+            "SELECT hands.id, hands.history FROM hands
+             JOIN hand_players ON hand_players.hand_id = hands.id
+             WHERE hand_players.player_id = ?1
+             ORDER BY hands.id DESC LIMIT ?2",
+        )?;
+        let rows = statement
+            .query_map(params![player, limit as i64], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.iter()
+            .map(|(id, history)| Self::parse_hand(*id, history))
+            .collect()
+    }
+
+    fn load_journals(&mut self) -> Result<Vec<TableJournal>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT table_id, snapshot FROM journals ORDER BY table_id")?;
+        let snapshots = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut statement = self
+            .conn
+            .prepare("SELECT entry FROM journal_entries WHERE table_id = ?1 ORDER BY id")?;
+        let mut journals = vec![];
+        for (table_id, snapshot) in snapshots {
+            let entries = statement
+                .query_map(params![table_id], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            journals.push(TableJournal {
+                snapshot: serde_json::from_str(&snapshot)?,
+                entries: entries
+                    .iter()
+                    .map(|entry| serde_json::from_str(entry))
+                    .collect::<serde_json::Result<_>>()?,
+            });
+        }
+        Ok(journals)
+    }
+
+    fn save_snapshot(&mut self, snapshot: &TableSnapshot) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        Self::replace_snapshot(&tx, snapshot)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn append_journal(&mut self, table: &TableId, entry: &JournalEntry) -> Result<()> {
+        Self::insert_entry(&self.conn, table, entry)
+    }
+
+    fn delete_journal(&mut self, table: &TableId) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "DELETE FROM journal_entries WHERE table_id = ?1",
+            params![table],
+        )?;
+        tx.execute("DELETE FROM journals WHERE table_id = ?1", params![table])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn settle(&mut self, settlement: &Settlement) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        match &settlement.change {
+            JournalChange::Snapshot(snapshot) => Self::replace_snapshot(&tx, snapshot)?,
+            JournalChange::Entry(table, entry) => Self::insert_entry(&tx, table, entry)?,
+        }
+        if let Some(history) = &settlement.history {
+            Self::insert_hand(&tx, history)?;
+        }
+        for (player, chips) in &settlement.credits {
+            tx.execute(
+                "UPDATE players SET balance = balance + ?2 WHERE id = ?1",
+                params![player, *chips as i64],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}